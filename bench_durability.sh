@@ -0,0 +1,44 @@
+#!/bin/bash
+
+# Compare the bulk indexing throughput of the `strict` and `relaxed` durability modes.
+# Usage: ./bench_durability.sh [requests] [documents per request]
+
+REQUESTS=${1:-200}
+DOCUMENTS=${2:-50}
+PORT=12399
+BIN="$(pwd)/target/release/kw-search-server"
+
+cargo build --release || exit 1
+
+WORK_DIR=$(mktemp -d)
+trap 'rm -rf "$WORK_DIR"' EXIT
+
+# Build the request body
+{
+    printf '{"documents":['
+    for i in $(seq 1 "$DOCUMENTS"); do
+        [ "$i" -gt 1 ] && printf ','
+        printf '{"title":"Document %d","content":"Document %d about keyword search, indexing throughput and the durability of commits."}' "$i" "$i"
+    done
+    printf ']}'
+} > "$WORK_DIR/request.json"
+
+for MODE in strict relaxed; do
+    mkdir -p "$WORK_DIR/$MODE"
+    (cd "$WORK_DIR/$MODE" && exec "$BIN" --port "$PORT" --durability "$MODE" > server.log 2>&1) &
+    sleep 1
+
+    START=$(date +%s%N)
+    for _ in $(seq 1 "$REQUESTS"); do
+        curl -s "http://localhost:$PORT/v1/index/create" \
+            --header 'Content-Type: application/json' \
+            --data @"$WORK_DIR/request.json" > /dev/null
+    done
+    END=$(date +%s%N)
+
+    kill %1
+    wait
+
+    ELAPSED_MS=$(( (END - START) / 1000000 ))
+    echo "$MODE: $REQUESTS requests of $DOCUMENTS documents in ${ELAPSED_MS}ms ($(( REQUESTS * 1000 / (ELAPSED_MS + 1) )) requests/s)"
+done
@@ -0,0 +1,46 @@
+#!/bin/bash
+
+# Compare the search latency on a large multi-segment index with and without parallel collection
+# of the segments.
+# Usage: ./bench_search_executor.sh [threads] [searches] [segments] [documents per segment]
+
+THREADS=${1:-4}
+SEARCHES=${2:-200}
+SEGMENTS=${3:-8}
+DOCUMENTS=${4:-50000}
+PORT=12399
+BIN="$(pwd)/target/release/kw-search-server"
+
+cargo build --release || exit 1
+
+WORK_DIR=$(mktemp -d)
+trap 'rm -rf "$WORK_DIR"' EXIT
+
+# Build the index
+cargo run --release --example build_segmented_index -- \
+    "$WORK_DIR/index_storage/bench" "$SEGMENTS" "$DOCUMENTS" > /dev/null || exit 1
+
+for N in 1 "$THREADS"; do
+    (cd "$WORK_DIR" && exec "$BIN" --port "$PORT" --search-executor-threads "$N" > "server-$N.log" 2>&1) &
+    SERVER_PID=$!
+    sleep 1
+
+    # Warm up the reader cache
+    curl -s "http://localhost:$PORT/v1/search" \
+        --header 'Content-Type: application/json' \
+        --data '{"query": "keyword search", "index": "bench"}' > /dev/null
+
+    START=$(date +%s%N)
+    for _ in $(seq 1 "$SEARCHES"); do
+        curl -s "http://localhost:$PORT/v1/search" \
+            --header 'Content-Type: application/json' \
+            --data '{"query": "keyword search latency", "index": "bench", "top_k": 10}' > /dev/null
+    done
+    END=$(date +%s%N)
+
+    kill "$SERVER_PID"
+    wait "$SERVER_PID"
+
+    ELAPSED_US=$(( (END - START) / 1000 ))
+    echo "$N thread(s): $SEARCHES searches on $SEGMENTS segments of $DOCUMENTS documents, $(( ELAPSED_US / SEARCHES / 1000 )).$(( ELAPSED_US / SEARCHES % 1000 / 100 ))ms per search"
+done
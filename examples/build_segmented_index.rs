@@ -0,0 +1,89 @@
+//! Build a large index with many segments in the index storage directory of the server, to
+//! benchmark searches collecting segments in parallel (see `bench_search_executor.sh`).
+//!
+//! Usage: cargo run --release --example build_segmented_index -- <index path> [segments] [documents per segment]
+
+use tantivy::{
+    doc,
+    indexer::NoMergePolicy,
+    schema::{Schema, FAST, STORED, STRING, TEXT},
+    Index, IndexWriter,
+};
+
+// words the documents are made of
+const VOCABULARY: [&str; 16] = [
+    "keyword",
+    "search",
+    "index",
+    "segment",
+    "query",
+    "document",
+    "relevance",
+    "score",
+    "server",
+    "thread",
+    "latency",
+    "collector",
+    "term",
+    "field",
+    "snippet",
+    "commit",
+];
+
+// number of words of a document
+const WORDS_PER_DOCUMENT: usize = 40;
+
+fn main() -> tantivy::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let index_path = args.next().expect("missing index path");
+    let segments: usize = args
+        .next()
+        .map_or(8, |arg| arg.parse().expect("invalid segments"));
+    let documents_per_segment: usize = args.next().map_or(50_000, |arg| {
+        arg.parse().expect("invalid documents per segment")
+    });
+
+    // same schema as the indexes created by the server
+    let mut schema_builder = Schema::builder();
+    let title = schema_builder.add_text_field("title", TEXT | STORED);
+    let body = schema_builder.add_text_field("body", TEXT | STORED);
+    let content_hash = schema_builder.add_text_field("content_hash", STRING | STORED);
+    schema_builder.add_f64_field("quality", FAST | STORED);
+    let body_length = schema_builder.add_u64_field("body_length", FAST);
+    schema_builder.add_json_field("fields", TEXT | STORED);
+    let schema = schema_builder.build();
+
+    std::fs::create_dir_all(&index_path)?;
+    let index = Index::create_in_dir(&index_path, schema)?;
+
+    // one commit per segment, which are never merged
+    let mut index_writer: IndexWriter = index.writer_with_num_threads(1, 100_000_000)?;
+    index_writer.set_merge_policy(Box::new(NoMergePolicy));
+
+    // deterministic pseudo-random words
+    let mut state: u64 = 42;
+    let mut next_word = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        VOCABULARY[(state >> 33) as usize % VOCABULARY.len()]
+    };
+
+    for segment in 0..segments {
+        for i in 0..documents_per_segment {
+            let content: Vec<&str> = (0..WORDS_PER_DOCUMENT).map(|_| next_word()).collect();
+            let content = content.join(" ");
+            index_writer.add_document(doc!(
+                title => format!("Document {segment}-{i}"),
+                content_hash => format!("{segment}-{i}"),
+                body_length => content.chars().count() as u64,
+                body => content,
+            ))?;
+        }
+        index_writer.commit()?;
+        println!("Committed segment {}/{segments}", segment + 1);
+    }
+    index_writer.wait_merging_threads()?;
+
+    Ok(())
+}
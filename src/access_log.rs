@@ -0,0 +1,59 @@
+//! Middleware emitting one structured log line per completed request.
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::time::Instant;
+use tracing::info;
+
+/// Outcome of a request, attached by handlers to the response extensions so that it shows up in
+/// the access log.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestOutcome {
+    /// Name of the index the request operated on
+    pub(crate) index_name: Option<String>,
+    /// Number of successful results, e.g. indexed documents or search hits
+    pub(crate) successful: usize,
+    /// Number of failed results, e.g. documents that failed to index
+    pub(crate) failed: usize,
+}
+
+/// Log method, path, status, latency and outcome of each request once the response is ready.
+pub(crate) async fn log_request(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let latency_ms = start.elapsed().as_millis() as u64;
+    let outcome = response
+        .extensions()
+        .get::<RequestOutcome>()
+        .cloned()
+        .unwrap_or_default();
+
+    info!(
+        target: "access_log",
+        method = %method,
+        path = %path,
+        status = response.status().as_u16(),
+        latency_ms = latency_ms,
+        latency_bucket = latency_bucket(latency_ms),
+        index_name = outcome.index_name.as_deref().unwrap_or(""),
+        successful = outcome.successful,
+        failed = outcome.failed,
+        "Request completed"
+    );
+
+    response
+}
+
+// Coarse latency bucket, handy for grouping requests in log analytics
+fn latency_bucket(latency_ms: u64) -> &'static str {
+    match latency_ms {
+        0..10 => "<10ms",
+        10..100 => "10ms-100ms",
+        100..1000 => "100ms-1s",
+        1000..10000 => "1s-10s",
+        _ => ">=10s",
+    }
+}
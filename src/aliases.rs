@@ -0,0 +1,189 @@
+//! Registry of index aliases, persisted in `aliases.json` under the index storage directory.
+//!
+//! Updates hold the write lock while the new registry is written to a temporary file and renamed
+//! over `aliases.json`, so concurrent updates are serialized and the persisted file always holds
+//! one complete version of the registry.
+
+use crate::{error::ServerError, index_storage_dir, validate_index_name};
+use axum::{extract::Path, response::IntoResponse, Json};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf, sync::RwLock};
+use tracing::{error, info, warn};
+
+const ALIASES_FILE: &str = "aliases.json";
+
+// registry of aliases, mapping alias names to index names
+static ALIASES: Lazy<RwLock<BTreeMap<String, String>>> = Lazy::new(|| RwLock::new(load()));
+
+/// Request body of the `PUT /v1/aliases/{alias}` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct AliasRequest {
+    /// Name of the index the alias points to
+    pub(crate) index: String,
+}
+
+// path of the persisted registry
+fn aliases_path() -> PathBuf {
+    index_storage_dir().join(ALIASES_FILE)
+}
+
+// load the persisted registry, starting empty if it does not exist or cannot be read
+fn load() -> BTreeMap<String, String> {
+    let path = aliases_path();
+    if !path.exists() {
+        return BTreeMap::new();
+    }
+
+    match std::fs::read(&path).map(|bytes| serde_json::from_slice(&bytes)) {
+        Ok(Ok(aliases)) => aliases,
+        Ok(Err(e)) => {
+            warn!(path = %path.display(), error = %e, "Failed to parse aliases file");
+            BTreeMap::new()
+        }
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read aliases file");
+            BTreeMap::new()
+        }
+    }
+}
+
+// write the registry to a temporary file and rename it over the persisted one
+fn persist(aliases: &BTreeMap<String, String>) -> Result<(), ServerError> {
+    let path = aliases_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            ServerError::Operation(format!("Failed to create index storage directory: {e}"))
+        })?;
+    }
+
+    let content = serde_json::to_vec_pretty(aliases)
+        .map_err(|e| ServerError::Operation(format!("Failed to serialize aliases: {e}")))?;
+    let tmp_path = path.with_extension(format!("json.{}.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp_path, content)
+        .and_then(|_| std::fs::rename(&tmp_path, &path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&tmp_path);
+            ServerError::Operation(format!("Failed to persist aliases: {e}"))
+        })
+}
+
+/// Return the index an alias points to, or `name` itself if it is not an alias.
+pub(crate) fn resolve(name: &str) -> String {
+    ALIASES
+        .read()
+        .unwrap()
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Point `alias` to `index`, returning the index it pointed to before, if any.
+pub(crate) fn set(alias: &str, index: &str) -> Result<Option<String>, ServerError> {
+    let mut aliases = ALIASES.write().unwrap();
+
+    let mut updated = aliases.clone();
+    let previous = updated.insert(alias.to_string(), index.to_string());
+    persist(&updated)?;
+    *aliases = updated;
+
+    Ok(previous)
+}
+
+/// Remove the aliases pointing to `index`, e.g. once it is deleted, returning their names.
+pub(crate) fn remove_pointing_to(index: &str) -> Result<Vec<String>, ServerError> {
+    let mut aliases = ALIASES.write().unwrap();
+
+    let (removed, kept): (BTreeMap<_, _>, BTreeMap<_, _>) = aliases
+        .clone()
+        .into_iter()
+        .partition(|(_, target)| target == index);
+    if removed.is_empty() {
+        return Ok(Vec::new());
+    }
+    persist(&kept)?;
+    *aliases = kept;
+
+    Ok(removed.into_keys().collect())
+}
+
+// point an alias to an index
+pub(crate) async fn put_alias_handler(
+    Path(alias): Path<String>,
+    Json(request): Json<AliasRequest>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    info!(alias = %alias, index = %request.index, "Received alias update request");
+
+    for name in [&alias, &request.index] {
+        if validate_index_name(name).is_err() {
+            let err_msg = format!("Invalid name '{name}'");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    }
+
+    let index_path = index_storage_dir().join(&request.index);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{}' does not exist", request.index);
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    match set(&alias, &request.index) {
+        Ok(previous) => {
+            info!(alias = %alias, index = %request.index, previous = ?previous, "Alias updated");
+
+            Ok(Json(serde_json::json!({
+                "alias": alias,
+                "index": request.index,
+                "previous_index": previous,
+            })))
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to update alias");
+
+            Err(e)
+        }
+    }
+}
+
+// list all aliases
+pub(crate) async fn list_aliases_handler() -> impl IntoResponse {
+    info!("Received alias list request");
+
+    Json(ALIASES.read().unwrap().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{storage_dir, unique_index_name};
+
+    #[test]
+    fn concurrent_updates_leave_a_single_target() {
+        storage_dir();
+        let alias = unique_index_name("alias");
+        let indexes: Vec<String> = (0..8).map(|_| unique_index_name("target")).collect();
+
+        std::thread::scope(|scope| {
+            for index in &indexes {
+                let alias = &alias;
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        set(alias, index).unwrap();
+                    }
+                });
+            }
+        });
+
+        let target = resolve(&alias);
+        assert!(indexes.contains(&target));
+        let persisted: BTreeMap<String, String> =
+            serde_json::from_slice(&std::fs::read(aliases_path()).unwrap()).unwrap();
+        assert_eq!(persisted.get(&alias), Some(&target));
+    }
+}
@@ -0,0 +1,87 @@
+//! Ad-hoc analysis of a text with one of the built-in tokenizers, to understand how documents and
+//! queries are tokenized.
+
+use crate::error::ServerError;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tantivy::tokenizer::TokenizerManager;
+use tracing::{error, info};
+
+// tokenizers registered by default in every index
+const TOKENIZERS: [&str; 4] = ["default", "en_stem", "raw", "whitespace"];
+
+/// Request body of the `POST /v1/analyze` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct AnalyzeRequest {
+    /// Text to analyze
+    pub(crate) text: String,
+    /// Name of the tokenizer. Defaults to `default`, the default tokenizer of the `title` and `body`
+    /// fields.
+    #[serde(default = "default_tokenizer")]
+    pub(crate) tokenizer: String,
+}
+
+fn default_tokenizer() -> String {
+    "default".to_string()
+}
+
+/// A token produced by the analysis
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AnalyzedToken {
+    /// Text of the token, as indexed
+    pub(crate) token: String,
+    /// Position of the token in the token stream
+    pub(crate) position: usize,
+    /// Byte offset of the start of the token in the text
+    pub(crate) start_offset: usize,
+    /// Byte offset of the end of the token in the text
+    pub(crate) end_offset: usize,
+}
+
+/// Response body of the `POST /v1/analyze` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct AnalyzeResponse {
+    /// Name of the tokenizer
+    pub(crate) tokenizer: String,
+    /// Tokens produced by the tokenizer
+    pub(crate) tokens: Vec<AnalyzedToken>,
+}
+
+// tokenize a text with the requested tokenizer
+pub(crate) async fn analyze_handler(
+    Json(request): Json<AnalyzeRequest>,
+) -> Result<Json<AnalyzeResponse>, ServerError> {
+    info!(tokenizer = %request.tokenizer, "Received analyze request");
+
+    let tokenizer = TOKENIZERS
+        .contains(&request.tokenizer.as_str())
+        .then(|| TokenizerManager::default().get(&request.tokenizer))
+        .flatten();
+    let Some(mut tokenizer) = tokenizer else {
+        let err_msg = format!(
+            "Unknown tokenizer '{}'. Available tokenizers are: {}",
+            request.tokenizer,
+            TOKENIZERS.join(", ")
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    };
+
+    let mut tokens = Vec::new();
+    let mut token_stream = tokenizer.token_stream(&request.text);
+    while let Some(token) = token_stream.next() {
+        tokens.push(AnalyzedToken {
+            token: token.text.clone(),
+            position: token.position,
+            start_offset: token.offset_from,
+            end_offset: token.offset_to,
+        });
+    }
+
+    Ok(Json(AnalyzeResponse {
+        tokenizer: request.tokenizer,
+        tokens,
+    }))
+}
@@ -0,0 +1,142 @@
+//! Archives of indexes, as downloaded from `/v1/index/download/{index_name}` and uploaded to
+//! `/v1/index/upload`: tarballs of the index directory, compressed with gzip or zstd. The archive
+//! of each format is cached separately in the index storage directory.
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+use tracing::warn;
+
+/// Lowest compression level
+pub(crate) const MIN_COMPRESSION_LEVEL: u32 = 1;
+
+/// Highest compression level, the highest one of gzip
+pub(crate) const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+// magic numbers of the compressed archives
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// compression level of both codecs, if configured
+static COMPRESSION_LEVEL: OnceCell<u32> = OnceCell::new();
+
+/// Compression of an index archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ArchiveFormat {
+    /// `.tar.gz`
+    #[default]
+    Gzip,
+    /// `.tar.zst`, faster to build and smaller
+    Zstd,
+}
+
+impl ArchiveFormat {
+    /// All the formats, e.g. to remove the cached archives of an index
+    pub(crate) const ALL: [Self; 2] = [Self::Gzip, Self::Zstd];
+
+    /// Name of the archive of the index `index_name`
+    pub(crate) fn file_name(self, index_name: &str) -> String {
+        match self {
+            Self::Gzip => format!("{index_name}.tar.gz"),
+            Self::Zstd => format!("{index_name}.tar.zst"),
+        }
+    }
+
+    /// Content type of the archive
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Gzip => "application/gzip",
+            Self::Zstd => "application/zstd",
+        }
+    }
+}
+
+/// Set the compression level of the archives, from 1 (fastest) to 9 (smallest), for both codecs.
+pub(crate) fn init_compression_level(level: u32) -> Result<(), String> {
+    if !(MIN_COMPRESSION_LEVEL..=MAX_COMPRESSION_LEVEL).contains(&level) {
+        return Err(format!(
+            "The compression level must be between {MIN_COMPRESSION_LEVEL} and {MAX_COMPRESSION_LEVEL}"
+        ));
+    }
+
+    COMPRESSION_LEVEL
+        .set(level)
+        .map_err(|_| "The compression level is already set".to_string())
+}
+
+/// Compression level of the archives, if configured. Otherwise, each codec uses its default level.
+pub(crate) fn compression_level() -> Option<u32> {
+    COMPRESSION_LEVEL.get().copied()
+}
+
+/// Build the archive of the index directory at `index_path` at `archive_path`.
+pub(crate) fn build(
+    index_path: &Path,
+    archive_path: &Path,
+    format: ArchiveFormat,
+) -> io::Result<()> {
+    let file = File::create(archive_path)?;
+    match format {
+        ArchiveFormat::Gzip => {
+            let level = compression_level()
+                .map(Compression::new)
+                .unwrap_or_default();
+            append_index(GzEncoder::new(file, level), index_path)?.finish()?;
+        }
+        ArchiveFormat::Zstd => {
+            // level 0 is the default level of zstd
+            let level = compression_level().unwrap_or(0) as i32;
+            append_index(zstd::Encoder::new(file, level)?, index_path)?.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+// write the tarball of the index directory at `index_path` to `writer`
+fn append_index<W: Write>(writer: W, index_path: &Path) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.append_dir_all(".", index_path)?;
+    builder.into_inner()
+}
+
+/// Open the tarball of the archive at `path`, decompressing it according to its content. Archives
+/// built by earlier versions of the server are uncompressed tarballs.
+pub(crate) fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let mut file = File::open(path)?;
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    (&mut file)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if magic.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::new(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Remove the cached archives of the index `index_name`, which are rebuilt on the next download.
+pub(crate) fn remove_cached(index_storage_dir: &Path, index_name: &str) {
+    for format in ArchiveFormat::ALL {
+        let archive_path = index_storage_dir.join(format.file_name(index_name));
+        match std::fs::remove_file(&archive_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!(
+                error = %e,
+                path = %archive_path.display(),
+                "Failed to delete the cached archive of the index"
+            ),
+        }
+    }
+}
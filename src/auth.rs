@@ -0,0 +1,169 @@
+//! Middleware authenticating the requests with an API key, set with `--api-key`. Clients send
+//! the key in an `Authorization: Bearer <key>` or an `X-API-Key: <key>` header. Without
+//! `--api-key`, the server is open to anyone who can reach it.
+
+use crate::error::ServerError;
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    HeaderMap, HeaderValue,
+};
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::warn;
+
+// header carrying the API key, as an alternative to `Authorization`
+const API_KEY_HEADER: &str = "x-api-key";
+
+// digest of the API key, if authentication is enabled
+static API_KEY_DIGEST: OnceCell<[u8; 32]> = OnceCell::new();
+
+/// Require the clients to send `api_key` with their requests.
+pub(crate) fn init(api_key: &str) -> Result<(), String> {
+    if api_key.trim().is_empty() {
+        return Err("The API key must not be empty".to_string());
+    }
+
+    API_KEY_DIGEST
+        .set(digest(api_key))
+        .map_err(|_| "The API key is already set".to_string())
+}
+
+/// Whether the requests must be authenticated with an API key
+pub(crate) fn enabled() -> bool {
+    API_KEY_DIGEST.get().is_some()
+}
+
+/// Reject the requests to `/v1/*` without the API key, if authentication is enabled.
+pub(crate) async fn require_api_key(request: Request, next: Next) -> Response {
+    let Some(expected) = API_KEY_DIGEST.get() else {
+        return next.run(request).await;
+    };
+
+    if !request.uri().path().starts_with("/v1/") {
+        return next.run(request).await;
+    }
+
+    if !is_authorized(request.headers(), expected) {
+        warn!(path = %request.uri().path(), "Unauthorized request");
+
+        let mut response = ServerError::Unauthorized(
+            "Missing or invalid API key. Send it in an `Authorization: Bearer <key>` or an `X-API-Key` header"
+                .to_string(),
+        )
+        .into_response();
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    }
+
+    next.run(request).await
+}
+
+// whether the API key sent by the client has the digest `expected`. The digests have the same
+// length whatever the keys, so that the comparison takes the same time for any key.
+fn is_authorized(headers: &HeaderMap, expected: &[u8; 32]) -> bool {
+    api_key(headers).is_some_and(|api_key| bool::from(digest(api_key).ct_eq(expected)))
+}
+
+// API key sent by the client, if any
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(authorization) = headers.get(AUTHORIZATION) {
+        let (scheme, credentials) = authorization.to_str().ok()?.split_once(' ')?;
+        return scheme
+            .eq_ignore_ascii_case("bearer")
+            .then(|| credentials.trim());
+    }
+
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|api_key| api_key.to_str().ok())
+        .map(str::trim)
+}
+
+fn digest(api_key: &str) -> [u8; 32] {
+    Sha256::digest(api_key.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use axum::{routing::get, Router};
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn key_is_accepted_in_either_header() {
+        let expected = digest("secret");
+
+        assert!(is_authorized(
+            &headers("authorization", "Bearer secret"),
+            &expected
+        ));
+        assert!(is_authorized(
+            &headers("authorization", "bearer  secret "),
+            &expected
+        ));
+        assert!(is_authorized(&headers("x-api-key", "secret"), &expected));
+    }
+
+    #[test]
+    fn missing_or_wrong_key_is_rejected() {
+        let expected = digest("secret");
+
+        assert!(!is_authorized(&HeaderMap::new(), &expected));
+        assert!(!is_authorized(
+            &headers("authorization", "Bearer guess"),
+            &expected
+        ));
+        assert!(!is_authorized(
+            &headers("authorization", "Basic secret"),
+            &expected
+        ));
+        assert!(!is_authorized(&headers("x-api-key", "secrets"), &expected));
+    }
+
+    #[tokio::test]
+    async fn only_authorized_requests_reach_the_routes() {
+        init("secret").unwrap();
+        let app = Router::new()
+            .route("/v1/indexes", get(|| async { "indexes" }))
+            .layer(axum::middleware::from_fn(require_api_key))
+            .route("/v1/health", get(|| async { "healthy" }));
+        let (base_url, client) = test_support::serve(app).await;
+
+        let unauthorized = client
+            .get(format!("{base_url}/v1/indexes"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unauthorized.status(), http::StatusCode::UNAUTHORIZED);
+        assert_eq!(unauthorized.headers()[WWW_AUTHENTICATE], "Bearer");
+
+        let authorized = client
+            .get(format!("{base_url}/v1/indexes"))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(authorized.status(), http::StatusCode::OK);
+
+        let health = client
+            .get(format!("{base_url}/v1/health"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(health.status(), http::StatusCode::OK);
+    }
+}
@@ -0,0 +1,112 @@
+//! Byte ranges of the index archives, so that interrupted downloads can be resumed. A single
+//! range of a `Range: bytes=...` header is honored; multiple ranges, other units and malformed
+//! headers are ignored, and the whole archive is sent.
+
+use http::HeaderValue;
+use std::ops::RangeInclusive;
+
+/// What to send for the `Range` header of a request, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ByteRange {
+    /// The whole file, with `200 OK`
+    Full,
+    /// The given bytes of the file, with `206 Partial Content`
+    Partial(RangeInclusive<u64>),
+    /// Nothing, with `416 Range Not Satisfiable`, as the range starts past the end of the file
+    Unsatisfiable,
+}
+
+/// Resolve the `Range` header of a request against a file of `len` bytes.
+pub(crate) fn parse(header: Option<&HeaderValue>, len: u64) -> ByteRange {
+    let Some(spec) = header
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.trim().strip_prefix("bytes="))
+        .filter(|spec| !spec.contains(','))
+    else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    match (start.trim(), end.trim()) {
+        // the last `suffix` bytes, e.g. `bytes=-500`
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(len.saturating_sub(suffix)..=len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        // the bytes from `start` to the end, e.g. `bytes=500-`
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) if start >= len => ByteRange::Unsatisfiable,
+            Ok(start) => ByteRange::Partial(start..=len - 1),
+            Err(_) => ByteRange::Full,
+        },
+        // the bytes from `start` to `end` included, the end being capped to the file
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start > end => ByteRange::Full,
+            (Ok(start), Ok(_)) if start >= len => ByteRange::Unsatisfiable,
+            (Ok(start), Ok(end)) => ByteRange::Partial(start..=end.min(len - 1)),
+            _ => ByteRange::Full,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_range(range: &str, len: u64) -> ByteRange {
+        parse(Some(&HeaderValue::from_str(range).unwrap()), len)
+    }
+
+    #[test]
+    fn open_ended_range_runs_to_the_end() {
+        assert_eq!(parse_range("bytes=0-", 100), ByteRange::Partial(0..=99));
+        assert_eq!(parse_range("bytes=40-", 100), ByteRange::Partial(40..=99));
+    }
+
+    #[test]
+    fn suffix_range_is_the_last_bytes() {
+        assert_eq!(parse_range("bytes=-10", 100), ByteRange::Partial(90..=99));
+        assert_eq!(parse_range("bytes=-500", 100), ByteRange::Partial(0..=99));
+        assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn bounded_range_is_capped_to_the_file() {
+        assert_eq!(parse_range("bytes=10-19", 100), ByteRange::Partial(10..=19));
+        assert_eq!(
+            parse_range("bytes=90-500", 100),
+            ByteRange::Partial(90..=99)
+        );
+    }
+
+    #[test]
+    fn range_starting_past_the_end_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=150-200", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn multiple_ranges_send_the_whole_file() {
+        assert_eq!(parse_range("bytes=0-9,20-29", 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn malformed_ranges_send_the_whole_file() {
+        assert_eq!(parse(None, 100), ByteRange::Full);
+        for range in [
+            "bytes=abc-",
+            "bytes=5",
+            "bytes=20-10",
+            "items=0-9",
+            "0-9",
+            "bytes=-x",
+        ] {
+            assert_eq!(parse_range(range, 100), ByteRange::Full, "{range}");
+        }
+    }
+}
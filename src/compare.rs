@@ -0,0 +1,246 @@
+//! Comparison of the documents of two indexes, matched by content hash, e.g. to validate a
+//! reindexed version of an index before switching an alias to it.
+
+use crate::{
+    aliases, error::ServerError, index_storage_dir, reader_cache, request_id, validate_index_name,
+};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tantivy::{
+    schema::{IndexRecordOption, Value},
+    DocAddress, DocSet, Searcher, TantivyDocument, TERMINATED,
+};
+use tracing::{error, info};
+
+// field documents are matched by
+const CONTENT_HASH_FIELD: &str = "content_hash";
+
+// maximum value of the `sample_size` parameter
+const MAX_SAMPLE_SIZE: usize = 1000;
+
+/// Request body of the `POST /v1/index/compare` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct CompareRequest {
+    /// Name of the first index, or alias, e.g. the current version of an index
+    pub(crate) left: String,
+    /// Name of the second index, or alias, e.g. a reindexed version
+    pub(crate) right: String,
+    /// Maximum number of documents present in only one of the indexes returned for each index.
+    /// None are returned by default.
+    #[serde(default)]
+    pub(crate) sample_size: usize,
+}
+
+/// Document counts of one of the compared indexes
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ComparedIndex {
+    /// Name of the index
+    pub(crate) index_name: String,
+    /// Number of documents of the index
+    pub(crate) num_docs: u64,
+    /// Number of distinct contents among the documents
+    pub(crate) distinct_contents: usize,
+    /// Number of documents without a match in the other index, counting each extra copy of a
+    /// content indexed more times than in the other index
+    pub(crate) only_here: u64,
+    /// Sample of the documents without a match in the other index
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) sample: Vec<SampleDocument>,
+}
+
+/// A document present in only one of the compared indexes
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SampleDocument {
+    /// Title of the document
+    pub(crate) title: String,
+    /// Hash of the content of the document
+    pub(crate) content_hash: String,
+}
+
+/// Response body of the `POST /v1/index/compare` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CompareResponse {
+    pub(crate) left: ComparedIndex,
+    pub(crate) right: ComparedIndex,
+    /// Number of documents of the right index minus the number of documents of the left index
+    pub(crate) num_docs_difference: i64,
+    /// Whether both indexes contain the same documents, the same number of times
+    pub(crate) identical: bool,
+}
+
+// documents of an index, by content hash: number of documents and first document
+type ContentCounts = HashMap<String, (u64, DocAddress)>;
+
+// compare the documents of two indexes by content hash
+pub(crate) async fn compare_indexes_handler(
+    Json(request): Json<CompareRequest>,
+) -> Result<Json<CompareResponse>, ServerError> {
+    info!(left = %request.left, right = %request.right, sample_size = request.sample_size, "Received compare request");
+
+    if request.sample_size > MAX_SAMPLE_SIZE {
+        let err_msg = format!(
+            "Invalid sample size {}. At most {MAX_SAMPLE_SIZE} documents can be sampled",
+            request.sample_size
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let mut searchers = Vec::with_capacity(2);
+    for requested_index in [&request.left, &request.right] {
+        match open_searcher(requested_index) {
+            Ok(searcher) => searchers.push(searcher),
+            Err(e) => {
+                error!("{}", &e);
+
+                return Err(e);
+            }
+        }
+    }
+    let right = searchers.pop().unwrap();
+    let left = searchers.pop().unwrap();
+
+    // reading the postings of every document is blocking work
+    let sample_size = request.sample_size;
+    let compared = request_id::spawn_blocking(move || {
+        let left_counts = content_counts(&left.1)?;
+        let right_counts = content_counts(&right.1)?;
+        let left_index = compared_index(left, &left_counts, &right_counts, sample_size)?;
+        let right_index = compared_index(right, &right_counts, &left_counts, sample_size)?;
+        Ok::<_, tantivy::TantivyError>((left_index, right_index))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|compared| compared.map_err(|e| e.to_string()));
+    let (left, right) = match compared {
+        Ok(compared) => compared,
+        Err(e) => {
+            let err_msg = format!("Failed to compare indexes: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    let num_docs_difference = right.num_docs as i64 - left.num_docs as i64;
+    let identical = left.only_here == 0 && right.only_here == 0;
+
+    info!(
+        left = %left.index_name,
+        right = %right.index_name,
+        only_in_left = left.only_here,
+        only_in_right = right.only_here,
+        identical,
+        "Indexes compared"
+    );
+
+    Ok(Json(CompareResponse {
+        left,
+        right,
+        num_docs_difference,
+        identical,
+    }))
+}
+
+// resolve the name of an index and get a searcher of it
+fn open_searcher(requested_index: &str) -> Result<(String, Searcher), ServerError> {
+    validate_index_name(requested_index).map_err(ServerError::BadRequest)?;
+
+    let index_name = aliases::resolve(requested_index);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        return Err(ServerError::IndexNotFound(format!(
+            "Index '{requested_index}' not found"
+        )));
+    }
+
+    let (index, reader) = reader_cache::get_or_open(&index_name, &index_path)
+        .map_err(|e| ServerError::Operation(format!("Failed to open index: {e}")))?;
+    if index.schema().get_field(CONTENT_HASH_FIELD).is_err() {
+        return Err(ServerError::BadRequest(format!(
+            "Index '{requested_index}' has no `{CONTENT_HASH_FIELD}` field to match documents by"
+        )));
+    }
+
+    Ok((index_name, reader.searcher()))
+}
+
+// count the live documents of each content hash, reading the postings of the content hash terms
+fn content_counts(searcher: &Searcher) -> tantivy::Result<ContentCounts> {
+    let field = searcher.schema().get_field(CONTENT_HASH_FIELD)?;
+
+    let mut counts = ContentCounts::new();
+    for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let alive_bitset = segment_reader.alive_bitset();
+
+        let mut stream = inverted_index.terms().stream()?;
+        while stream.advance() {
+            let mut postings = inverted_index
+                .read_postings_from_terminfo(stream.value(), IndexRecordOption::Basic)?;
+            let mut doc = postings.doc();
+            while doc != TERMINATED {
+                if alive_bitset.is_none_or(|alive_bitset| alive_bitset.is_alive(doc)) {
+                    let content_hash = String::from_utf8_lossy(stream.key()).into_owned();
+                    counts
+                        .entry(content_hash)
+                        .or_insert((0, DocAddress::new(segment_ord as u32, doc)))
+                        .0 += 1;
+                }
+                doc = postings.advance();
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+// counts of an index compared to the other index, with a sample of the documents only in it
+fn compared_index(
+    (index_name, searcher): (String, Searcher),
+    counts: &ContentCounts,
+    other_counts: &ContentCounts,
+    sample_size: usize,
+) -> tantivy::Result<ComparedIndex> {
+    let mut missing: Vec<(&String, u64, DocAddress)> = counts
+        .iter()
+        .filter_map(|(content_hash, &(count, doc_address))| {
+            let other_count = other_counts
+                .get(content_hash)
+                .map_or(0, |&(count, _)| count);
+            (count > other_count).then(|| (content_hash, count - other_count, doc_address))
+        })
+        .collect();
+    let only_here = missing.iter().map(|&(_, count, _)| count).sum();
+
+    // sample in content hash order, so that repeated comparisons return the same documents
+    missing.sort_unstable_by_key(|&(content_hash, _, _)| content_hash);
+    let title = searcher.schema().get_field("title")?;
+    let sample = missing
+        .into_iter()
+        .take(sample_size)
+        .map(|(content_hash, _, doc_address)| {
+            let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+            Ok(SampleDocument {
+                title: retrieved_doc
+                    .get_first(title)
+                    .and_then(|value| value.as_str())
+                    .unwrap_or("Unknown")
+                    .to_string(),
+                content_hash: content_hash.clone(),
+            })
+        })
+        .collect::<tantivy::Result<_>>()?;
+
+    Ok(ComparedIndex {
+        index_name,
+        num_docs: searcher.num_docs(),
+        distinct_contents: counts.len(),
+        only_here,
+        sample,
+    })
+}
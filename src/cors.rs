@@ -0,0 +1,145 @@
+//! Cross-origin resource sharing, so that browser apps served from other origins can call the
+//! server. The allowed origins are set with `--cors-origin`: `*` allows any origin, and a list of
+//! origins, e.g. `https://app.example.com`, allows only those. Without it, no CORS headers are
+//! sent, and browsers block cross-origin calls.
+
+use http::{HeaderName, HeaderValue};
+use once_cell::sync::OnceCell;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use url::Url;
+
+// headers of the responses readable by browser apps, besides the CORS-safelisted ones
+const EXPOSED_HEADERS: [&str; 7] = [
+    "content-disposition",
+    "content-range",
+    "retry-after",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "x-request-id",
+];
+
+// origins allowed to call the server, if CORS is enabled
+static ALLOWED_ORIGINS: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Allow the browser apps of `origins` to call the server. `*` allows any origin, and must then
+/// be the only origin.
+pub(crate) fn init(origins: Vec<String>) -> Result<(), String> {
+    validate_origins(&origins)?;
+
+    ALLOWED_ORIGINS
+        .set(origins)
+        .map_err(|_| "The CORS origins are already set".to_string())
+}
+
+/// Origins allowed to call the server, if CORS is enabled
+pub(crate) fn allowed_origins() -> Option<&'static [String]> {
+    ALLOWED_ORIGINS.get().map(Vec::as_slice)
+}
+
+/// Layer answering the preflight requests and adding the CORS headers to the responses, if CORS
+/// is enabled
+pub(crate) fn layer() -> Option<CorsLayer> {
+    ALLOWED_ORIGINS.get().map(|origins| layer_for(origins))
+}
+
+// layer allowing the browser apps of `origins` to call the server
+fn layer_for(origins: &[String]) -> CorsLayer {
+    let allow_origin = match origins.iter().any(|origin| origin == "*") {
+        true => AllowOrigin::any(),
+        false => AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        ),
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static))
+}
+
+// check that `origins` is either `*` alone, or a list of origins
+fn validate_origins(origins: &[String]) -> Result<(), String> {
+    if origins.iter().any(|origin| origin == "*") {
+        if origins.len() > 1 {
+            return Err("`*` allows any origin, and cannot be combined with other origins".into());
+        }
+        return Ok(());
+    }
+
+    origins
+        .iter()
+        .try_for_each(|origin| validate_origin(origin))
+}
+
+// check that `origin` is an origin as sent by browsers, e.g. `https://app.example.com`, which
+// must match exactly: no path, not even a trailing slash
+fn validate_origin(origin: &str) -> Result<(), String> {
+    let url = Url::parse(origin).map_err(|e| format!("Invalid CORS origin '{origin}': {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") || url.origin().ascii_serialization() != origin {
+        return Err(format!(
+            "Invalid CORS origin '{origin}'. Expected a scheme, a host and an optional port, e.g. `https://app.example.com`"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use axum::{routing::post, Router};
+
+    // `Access-Control-Allow-Origin` header of a search from `origin` with CORS allowing `origins`
+    async fn allowed_origin(origins: &[&str], origin: &str) -> Option<String> {
+        let origins: Vec<String> = origins.iter().map(ToString::to_string).collect();
+        let app = Router::new()
+            .route("/v1/search", post(crate::query_handler))
+            .layer(layer_for(&origins));
+        let (base_url, client) = test_support::serve(app).await;
+
+        let response = client
+            .post(format!("{base_url}/v1/search"))
+            .header(http::header::ORIGIN, origin)
+            .json(&serde_json::json!({ "query": "cors", "index": "missing" }))
+            .send()
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(http::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn search_responses_allow_the_configured_origins() {
+        let app = "https://app.example.com";
+
+        assert_eq!(allowed_origin(&["*"], app).await.as_deref(), Some("*"));
+        assert_eq!(allowed_origin(&[app], app).await.as_deref(), Some(app));
+        assert_eq!(
+            allowed_origin(&[app], "https://evil.example.com").await,
+            None
+        );
+    }
+
+    #[test]
+    fn origins_must_be_bare_origins_or_a_lone_wildcard() {
+        let origins =
+            |origins: &[&str]| -> Vec<String> { origins.iter().map(ToString::to_string).collect() };
+
+        assert!(validate_origins(&origins(&["*"])).is_ok());
+        assert!(validate_origins(&origins(&[
+            "https://a.example.com",
+            "http://localhost:3000"
+        ]))
+        .is_ok());
+        assert!(validate_origins(&origins(&["*", "https://a.example.com"])).is_err());
+        assert!(validate_origins(&origins(&["https://a.example.com/"])).is_err());
+        assert!(validate_origins(&origins(&["ftp://a.example.com"])).is_err());
+    }
+}
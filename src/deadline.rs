@@ -0,0 +1,131 @@
+//! Time budget of a search. A collector wrapped in `TimeBounded` stops collecting once the budget
+//! is spent, keeping the hits collected so far, so that searches of large indexes return within a
+//! predictable time at the cost of completeness.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    query::Weight,
+    DocSet, SegmentOrdinal, SegmentReader, TERMINATED,
+};
+
+// number of documents collected between two checks of the deadline
+const DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+/// Instant a search must stop collecting at, shared by the collectors of its segments
+#[derive(Debug, Clone)]
+pub(crate) struct Deadline {
+    at: Instant,
+    expired: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// Deadline `budget` from now
+    pub(crate) fn after(budget: Duration) -> Self {
+        Self {
+            at: Instant::now() + budget,
+            expired: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Whether a collection stopped because the deadline passed
+    pub(crate) fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    // whether the deadline passed, recording it if so
+    fn check(&self) -> bool {
+        if self.expired() {
+            return true;
+        }
+
+        let expired = Instant::now() >= self.at;
+        if expired {
+            self.expired.store(true, Ordering::Relaxed);
+        }
+        expired
+    }
+}
+
+/// Collector collecting like `inner` until the deadline passes. Segments are then left
+/// uncollected, and the segment being collected is harvested as is.
+///
+/// Checking the deadline requires visiting the matching documents one by one, so the top hits
+/// are collected without the block pruning of tantivy, and every matching document is scored.
+pub(crate) struct TimeBounded<C> {
+    inner: C,
+    deadline: Deadline,
+}
+
+impl<C> TimeBounded<C> {
+    pub(crate) fn new(inner: C, deadline: Deadline) -> Self {
+        Self { inner, deadline }
+    }
+}
+
+impl<C: Collector> Collector for TimeBounded<C> {
+    type Fruit = C::Fruit;
+
+    type Child = C::Child;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        self.inner.for_segment(segment_local_id, segment)
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.inner.requires_scoring()
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<Self::Fruit> {
+        self.inner.merge_fruits(segment_fruits)
+    }
+
+    fn collect_segment(
+        &self,
+        weight: &dyn Weight,
+        segment_ord: u32,
+        reader: &SegmentReader,
+    ) -> tantivy::Result<<Self::Child as SegmentCollector>::Fruit> {
+        let mut segment_collector = self.inner.for_segment(segment_ord, reader)?;
+        if self.deadline.check() {
+            return Ok(segment_collector.harvest());
+        }
+
+        let requires_scoring = self.inner.requires_scoring();
+        let alive_bitset = reader.alive_bitset();
+        let mut scorer = weight.scorer(reader, 1.0)?;
+        let mut doc = scorer.doc();
+        let mut visited: u32 = 0;
+        while doc != TERMINATED {
+            if alive_bitset.is_none_or(|alive_bitset| alive_bitset.is_alive(doc)) {
+                let score = match requires_scoring {
+                    true => scorer.score(),
+                    false => 0.0,
+                };
+                segment_collector.collect(doc, score);
+            }
+
+            visited += 1;
+            if visited.is_multiple_of(DEADLINE_CHECK_INTERVAL) && self.deadline.check() {
+                break;
+            }
+
+            doc = scorer.advance();
+        }
+
+        Ok(segment_collector.harvest())
+    }
+}
@@ -0,0 +1,173 @@
+//! Enumeration of the distinct values of a field, e.g. to build filter dropdowns.
+
+use crate::{aliases, error::ServerError, index_storage_dir, reader_cache, validate_index_name};
+use axum::{
+    extract::{Path, Query},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tantivy::schema::FieldType;
+use tracing::{error, info};
+
+// default maximum number of distinct values returned
+const DEFAULT_DISTINCT_LIMIT: usize = 100;
+
+// maximum value of the `limit` parameter
+const MAX_DISTINCT_LIMIT: usize = 10_000;
+
+/// Query parameters of the `GET /v1/index/{index_name}/distinct` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct DistinctParams {
+    /// Name of the field whose values are enumerated
+    pub(crate) field: String,
+    /// Maximum number of values returned
+    pub(crate) limit: Option<usize>,
+}
+
+/// A distinct value of a field
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DistinctValue {
+    /// Indexed term
+    pub(crate) value: String,
+    /// Number of documents containing the term, including deleted documents not merged away yet
+    pub(crate) count: u64,
+}
+
+/// Response body of the `GET /v1/index/{index_name}/distinct` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DistinctResponse {
+    /// Name of the index
+    pub(crate) index_name: String,
+    /// Name of the field
+    pub(crate) field: String,
+    /// Distinct values of the field, in lexicographic order
+    pub(crate) values: Vec<DistinctValue>,
+    /// Whether the field has more values than returned
+    pub(crate) truncated: bool,
+}
+
+// enumerate the distinct indexed terms of a field with their document counts
+pub(crate) async fn distinct_values_handler(
+    Path(index_name): Path<String>,
+    Query(params): Query<DistinctParams>,
+) -> Result<Json<DistinctResponse>, ServerError> {
+    info!(index_name = %index_name, field = %params.field, limit = ?params.limit, "Received distinct values request");
+
+    let limit = params.limit.unwrap_or(DEFAULT_DISTINCT_LIMIT);
+    if limit == 0 || limit > MAX_DISTINCT_LIMIT {
+        let err_msg =
+            format!("Invalid limit {limit}. It must be between 1 and {MAX_DISTINCT_LIMIT}");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&index_name);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{index_name}' not found");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    // only text fields indexed as terms have values that read back as text
+    let schema = index.schema();
+    let field = match schema.get_field(&params.field) {
+        Ok(field) => field,
+        Err(_) => {
+            let err_msg = format!("Unknown field '{}'", params.field);
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+    let is_indexed_text = match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) => options.get_indexing_options().is_some(),
+        _ => false,
+    };
+    if !is_indexed_text {
+        let err_msg = format!(
+            "Field '{}' is not an indexed text field, so its distinct values cannot be enumerated",
+            params.field
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // merge the term dictionaries of the segments. A term among the `limit` first terms of the
+    // index is among the `limit` first terms of each segment containing it, so reading one more
+    // term per segment is enough to know whether the values are truncated.
+    let searcher = reader.searcher();
+    let mut counts: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let terms = match segment_reader
+            .inverted_index(field)
+            .and_then(|inverted_index| {
+                let mut stream = inverted_index.terms().stream()?;
+                let mut terms = Vec::new();
+                while terms.len() <= limit && stream.advance() {
+                    terms.push((stream.key().to_vec(), stream.value().doc_freq as u64));
+                }
+                Ok(terms)
+            }) {
+            Ok(terms) => terms,
+            Err(e) => {
+                let err_msg = format!("Failed to read the terms of field '{}': {e}", params.field);
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        };
+
+        for (term, count) in terms {
+            *counts.entry(term).or_default() += count;
+        }
+        while counts.len() > limit + 1 {
+            counts.pop_last();
+        }
+    }
+
+    let truncated = counts.len() > limit;
+    let values: Vec<DistinctValue> = counts
+        .into_iter()
+        .take(limit)
+        .map(|(term, count)| DistinctValue {
+            value: String::from_utf8_lossy(&term).into_owned(),
+            count,
+        })
+        .collect();
+
+    info!(index_name = %index_name, field = %params.field, values = values.len(), truncated, "Distinct values enumerated");
+
+    Ok(Json(DistinctResponse {
+        index_name,
+        field: params.field,
+        values,
+        truncated,
+    }))
+}
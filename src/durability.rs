@@ -0,0 +1,142 @@
+//! Durability of the commits of new indexes.
+//!
+//! In `strict` mode, the files of a commit are synced to disk before the commit returns. In
+//! `relaxed` mode, they are left to the page cache of the operating system, which writes them back
+//! in the background, typically within 30 seconds on Linux. A crash of the server loses nothing,
+//! but a crash of the operating system or a power loss may lose, or even corrupt, the indexes
+//! committed during that window.
+
+use crate::DURABILITY;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tantivy::{
+    directory::{
+        error::{DeleteError, LockError, OpenReadError, OpenWriteError},
+        AntiCallToken, Directory, DirectoryLock, FileHandle, Lock, MmapDirectory, TerminatingWrite,
+        WatchCallback, WatchHandle, WritePtr,
+    },
+    schema::Schema,
+    Index, IndexSettings, TantivyError,
+};
+
+/// Durability of the commits of new indexes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Durability {
+    /// Sync the files of each commit to disk before the commit returns
+    #[default]
+    Strict,
+    /// Leave the files of each commit to the operating system, trading durability for speed
+    Relaxed,
+}
+
+/// Create an index at `index_path`, committing with the configured durability.
+pub(crate) fn create_index(index_path: &Path, schema: Schema) -> tantivy::Result<Index> {
+    match DURABILITY.get().copied().unwrap_or_default() {
+        Durability::Strict => Index::create_in_dir(index_path, schema),
+        Durability::Relaxed => {
+            let directory = RelaxedDirectory::open(index_path)?;
+            if Index::exists(&directory)? {
+                return Err(TantivyError::IndexAlreadyExists);
+            }
+            Index::create(directory, schema, IndexSettings::default())
+        }
+    }
+}
+
+// Directory writing files like `MmapDirectory`, but without syncing them to disk
+#[derive(Debug, Clone)]
+struct RelaxedDirectory {
+    root_path: PathBuf,
+    inner: MmapDirectory,
+}
+
+impl RelaxedDirectory {
+    fn open(root_path: &Path) -> tantivy::Result<Self> {
+        Ok(Self {
+            root_path: root_path.to_path_buf(),
+            inner: MmapDirectory::open(root_path)?,
+        })
+    }
+}
+
+impl Directory for RelaxedDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        self.inner.get_file_handle(path)
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+        self.inner.delete(path)
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        self.inner.exists(path)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.root_path.join(path))
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => {
+                    OpenWriteError::FileAlreadyExists(path.to_path_buf())
+                }
+                _ => OpenWriteError::wrap_io_error(e, path.to_path_buf()),
+            })?;
+
+        Ok(BufWriter::new(Box::new(UnsyncedFileWriter(file))))
+    }
+
+    fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        self.inner.atomic_read(path)
+    }
+
+    fn atomic_write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        // the rename keeps the write atomic, even though it is not durable
+        let full_path = self.root_path.join(path);
+        let tmp_path = full_path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&tmp_path, data)
+            .and_then(|_| std::fs::rename(&tmp_path, &full_path))
+            .inspect_err(|_| {
+                let _ = std::fs::remove_file(&tmp_path);
+            })
+    }
+
+    fn acquire_lock(&self, lock: &Lock) -> Result<DirectoryLock, LockError> {
+        self.inner.acquire_lock(lock)
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn watch(&self, watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        self.inner.watch(watch_callback)
+    }
+}
+
+// File writer flushing its content to the operating system, but not syncing it to disk
+struct UnsyncedFileWriter(File);
+
+impl Write for UnsyncedFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl TerminatingWrite for UnsyncedFileWriter {
+    fn terminate_ref(&mut self, _: AntiCallToken) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -1,3 +1,9 @@
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::StatusCode;
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Error, Clone, Debug, PartialEq, Eq)]
@@ -8,4 +14,103 @@ pub enum ServerError {
     /// Generic error returned while performing an operation
     #[error("{0}")]
     Operation(String),
+    /// Error returned when a request is malformed or has invalid values
+    #[error("{0}")]
+    BadRequest(String),
+    /// Error returned when the requested index, or alias, does not exist
+    #[error("{0}")]
+    IndexNotFound(String),
+    /// Error returned when another requested resource does not exist, e.g. a job or an upload
+    #[error("{0}")]
+    NotFound(String),
+    /// Error returned when a request conflicts with the state of the server, e.g. with an
+    /// existing index
+    #[error("{0}")]
+    Conflict(String),
+    /// Error returned when a request, or a part of it, exceeds a size limit
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    /// Error returned when the content type of a request is not supported
+    #[error("{0}")]
+    Unsupported(String),
+    /// Error returned when a request lacks a valid API key
+    #[error("{0}")]
+    Unauthorized(String),
+    /// Error returned when a client exceeds its rate limit
+    #[error("{0}")]
+    RateLimited(String),
+    /// Error returned when the server cannot serve a request for now, e.g. too many downloads
+    #[error("{0}")]
+    Unavailable(String),
+    /// Error returned when the requested byte range of a download starts past the end of the file
+    #[error("{0}")]
+    RangeNotSatisfiable(String),
+}
+
+impl ServerError {
+    /// HTTP status of the error
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            ServerError::ArgumentError(_) | ServerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServerError::Operation(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ServerError::IndexNotFound(_) | ServerError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServerError::Conflict(_) => StatusCode::CONFLICT,
+            ServerError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServerError::Unsupported(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ServerError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ServerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ServerError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServerError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+        }
+    }
+
+    /// Machine-readable code of the error
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            ServerError::ArgumentError(_) => "INVALID_ARGUMENT",
+            ServerError::Operation(_) => "INTERNAL_ERROR",
+            ServerError::BadRequest(_) => "BAD_REQUEST",
+            ServerError::IndexNotFound(_) => "INDEX_NOT_FOUND",
+            ServerError::NotFound(_) => "NOT_FOUND",
+            ServerError::Conflict(_) => "CONFLICT",
+            ServerError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            ServerError::Unsupported(_) => "UNSUPPORTED_MEDIA_TYPE",
+            ServerError::Unauthorized(_) => "UNAUTHORIZED",
+            ServerError::RateLimited(_) => "RATE_LIMITED",
+            ServerError::Unavailable(_) => "SERVICE_UNAVAILABLE",
+            ServerError::RangeNotSatisfiable(_) => "RANGE_NOT_SATISFIABLE",
+        }
+    }
+}
+
+/// Response body of the failed requests
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorResponse {
+    pub(crate) error: ErrorBody,
+}
+
+/// Error of a failed request
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorBody {
+    /// Machine-readable code of the error, e.g. `INDEX_NOT_FOUND`
+    pub(crate) code: &'static str,
+    /// Human-readable description of the error
+    pub(crate) message: String,
+    /// HTTP status of the response
+    pub(crate) status: u16,
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = ErrorResponse {
+            error: ErrorBody {
+                code: self.code(),
+                message: self.to_string(),
+                status: status.as_u16(),
+            },
+        };
+
+        (status, Json(body)).into_response()
+    }
 }
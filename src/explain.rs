@@ -0,0 +1,84 @@
+//! Explanation of the relevance score of a hit, as a documented JSON tree that clients can walk,
+//! rather than the serialization of tantivy's `Explanation`, which omits empty fields and may
+//! change between tantivy versions.
+
+use crate::keyword_search::ScoreExplanation;
+use tantivy::{query::Query, DocAddress, Searcher};
+
+/// Explain the relevance score of the document at `doc_address` for `query`.
+pub(crate) fn explain(
+    searcher: &Searcher,
+    query: &dyn Query,
+    doc_address: DocAddress,
+) -> Result<ScoreExplanation, String> {
+    let explanation = query
+        .explain(searcher, doc_address)
+        .map_err(|e| format!("Failed to explain the score: {e}"))?;
+
+    // tantivy only exposes the tree through its serialization, whose optional fields are read
+    // back as empty
+    serde_json::to_value(&explanation)
+        .and_then(serde_json::from_value)
+        .map_err(|e| format!("Failed to convert the explanation: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::{
+        collector::TopDocs,
+        doc,
+        query::TermQuery,
+        schema::{IndexRecordOption, Schema, TEXT},
+        Index, Term,
+    };
+
+    // check that `node` and its descendants have exactly the documented fields
+    fn assert_shape(node: &serde_json::Value) {
+        let object = node.as_object().unwrap();
+        let mut keys: Vec<_> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["context", "description", "details", "value"]);
+        assert!(object["value"].is_number());
+        assert!(object["description"].is_string());
+        assert!(object["context"].is_array());
+        for detail in object["details"].as_array().unwrap() {
+            assert_shape(detail);
+        }
+    }
+
+    #[test]
+    fn term_query_explanation_is_a_documented_tree() {
+        let mut schema = Schema::builder();
+        let body = schema.add_text_field("body", TEXT);
+        let index = Index::create_in_ram(schema.build());
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer
+            .add_document(doc!(body => "ferris the crab"))
+            .unwrap();
+        writer.add_document(doc!(body => "the mascot")).unwrap();
+        writer.commit().unwrap();
+
+        let searcher = index.reader().unwrap().searcher();
+        let query = TermQuery::new(
+            Term::from_field_text(body, "crab"),
+            IndexRecordOption::WithFreqs,
+        );
+        let (score, doc_address) = searcher.search(&query, &TopDocs::with_limit(1)).unwrap()[0];
+
+        let explanation = explain(&searcher, &query, doc_address).unwrap();
+        assert_eq!(explanation.value, score);
+        assert!(explanation.description.starts_with("TermQuery"));
+        assert!(!explanation.details.is_empty());
+
+        let json = serde_json::to_value(&explanation).unwrap();
+        assert_shape(&json);
+        // the fields omitted by tantivy are still there on the leaves
+        let mut leaf = &json;
+        while let Some(detail) = leaf["details"].get(0) {
+            leaf = detail;
+        }
+        assert_eq!(leaf["details"], serde_json::json!([]));
+        assert_eq!(leaf["context"], serde_json::json!([]));
+    }
+}
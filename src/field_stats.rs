@@ -0,0 +1,168 @@
+//! Aggregate statistics over the values of numeric fast fields, computed on all the documents
+//! matching a search rather than only on the returned hits.
+
+use crate::keyword_search::FieldStats;
+use tantivy::{
+    collector::{Collector, SegmentCollector},
+    columnar::Column,
+    schema::{FieldType, Schema},
+    DocId, Score, SegmentOrdinal, SegmentReader,
+};
+
+impl FieldStats {
+    // add a value to the statistics, except the average computed at the end
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    /// Merge the statistics of another set of documents, e.g. of another index, into these.
+    pub(crate) fn merge(&mut self, other: FieldStats) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.avg = (self.count > 0).then(|| self.sum / self.count as f64);
+    }
+}
+
+/// Numeric type of a field statistics are computed for
+#[derive(Debug, Clone, Copy)]
+enum NumericType {
+    U64,
+    I64,
+    F64,
+}
+
+// numeric type of `field_name`, if statistics can be computed for it, i.e. it is a numeric field
+// declared `FAST` in the schema
+fn numeric_type(schema: &Schema, field_name: &str) -> Result<NumericType, String> {
+    let field = schema
+        .get_field(field_name)
+        .map_err(|_| format!("Unknown stats field `{field_name}`"))?;
+    let entry = schema.get_field_entry(field);
+
+    let numeric_type = match entry.field_type() {
+        FieldType::U64(_) => Some(NumericType::U64),
+        FieldType::I64(_) => Some(NumericType::I64),
+        FieldType::F64(_) => Some(NumericType::F64),
+        _ => None,
+    };
+    match numeric_type {
+        Some(numeric_type) if entry.is_fast() => Ok(numeric_type),
+        _ => Err(format!(
+            "Statistics cannot be computed for field `{field_name}`. Only numeric fields declared `FAST` are supported"
+        )),
+    }
+}
+
+/// Collector computing the statistics of the given fields over all the matching documents
+#[derive(Debug, Clone)]
+pub(crate) struct FieldStatsCollector {
+    fields: Vec<(String, NumericType)>,
+}
+
+impl FieldStatsCollector {
+    /// Create a collector for `field_names`, checking that each is a numeric field declared `FAST`
+    /// in the schema.
+    pub(crate) fn new(schema: &Schema, field_names: &[String]) -> Result<Self, String> {
+        let fields = field_names
+            .iter()
+            .map(|field_name| Ok((field_name.clone(), numeric_type(schema, field_name)?)))
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { fields })
+    }
+}
+
+impl Collector for FieldStatsCollector {
+    type Fruit = Vec<FieldStats>;
+    type Child = FieldStatsSegmentCollector;
+
+    fn for_segment(
+        &self,
+        _segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        let fast_fields = segment.fast_fields();
+
+        // a segment where no document has a value for a field has no column for it
+        let columns = self
+            .fields
+            .iter()
+            .map(|(field_name, numeric_type)| {
+                Ok(match numeric_type {
+                    NumericType::U64 => fast_fields.column_opt(field_name)?.map(StatsColumn::U64),
+                    NumericType::I64 => fast_fields.column_opt(field_name)?.map(StatsColumn::I64),
+                    NumericType::F64 => fast_fields.column_opt(field_name)?.map(StatsColumn::F64),
+                })
+            })
+            .collect::<tantivy::Result<Vec<_>>>()?;
+
+        Ok(FieldStatsSegmentCollector {
+            stats: vec![FieldStats::default(); columns.len()],
+            columns,
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        false
+    }
+
+    fn merge_fruits(&self, segment_fruits: Vec<Vec<FieldStats>>) -> tantivy::Result<Self::Fruit> {
+        let mut merged = vec![FieldStats::default(); self.fields.len()];
+        for segment_stats in segment_fruits {
+            for (stats, segment_stats) in merged.iter_mut().zip(segment_stats) {
+                stats.merge(segment_stats);
+            }
+        }
+
+        Ok(merged)
+    }
+}
+
+/// Column of a field in a segment
+enum StatsColumn {
+    U64(Column<u64>),
+    I64(Column<i64>),
+    F64(Column<f64>),
+}
+
+/// Segment-level collector of [`FieldStatsCollector`]
+pub(crate) struct FieldStatsSegmentCollector {
+    columns: Vec<Option<StatsColumn>>,
+    stats: Vec<FieldStats>,
+}
+
+impl SegmentCollector for FieldStatsSegmentCollector {
+    type Fruit = Vec<FieldStats>;
+
+    fn collect(&mut self, doc: DocId, _score: Score) {
+        for (column, stats) in self.columns.iter().zip(self.stats.iter_mut()) {
+            match column {
+                Some(StatsColumn::U64(column)) => {
+                    column.values_for_doc(doc).for_each(|v| stats.add(v as f64))
+                }
+                Some(StatsColumn::I64(column)) => {
+                    column.values_for_doc(doc).for_each(|v| stats.add(v as f64))
+                }
+                Some(StatsColumn::F64(column)) => {
+                    column.values_for_doc(doc).for_each(|v| stats.add(v))
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        self.stats
+    }
+}
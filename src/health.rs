@@ -0,0 +1,88 @@
+//! Liveness and readiness probes, for orchestrators and load balancers. The probes are routed
+//! outside of the rate limiting, so that frequent probing never gets a healthy server rejected.
+//!
+//! - `GET /v1/health`: the server is running
+//! - `GET /v1/ready`: the server can serve requests, i.e. the index storage directory is writable
+//!   and the download URL prefix is set
+
+use crate::{index_storage_dir, request_id, DOWNLOAD_URL_PREFIX};
+use axum::{response::IntoResponse, Json};
+use http::status::StatusCode;
+use serde::Serialize;
+use std::{fs, path::Path};
+use tracing::warn;
+
+/// Response body of the `GET /v1/ready` endpoint
+#[derive(Debug, Serialize)]
+struct ReadinessResponse {
+    /// `ready`, or `unavailable` if any check failed
+    status: &'static str,
+    /// Version of the server
+    version: &'static str,
+    /// Failed checks, empty if the server is ready
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+}
+
+// tell that the server is running
+pub(crate) async fn health_handler() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+// tell whether the server can serve requests, with `503 Service Unavailable` if it cannot
+pub(crate) async fn ready_handler() -> impl IntoResponse {
+    let mut errors = Vec::new();
+
+    if DOWNLOAD_URL_PREFIX.get().is_none() {
+        errors.push("The download URL prefix is not set".to_string());
+    }
+
+    // probing the storage directory is blocking work
+    let index_storage_dir = index_storage_dir();
+    match request_id::spawn_blocking(move || check_writable(&index_storage_dir)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err_msg)) => errors.push(err_msg),
+        Err(e) => errors.push(format!("Failed to check the index storage directory: {e}")),
+    }
+
+    let status = match errors.is_empty() {
+        true => StatusCode::OK,
+        false => {
+            warn!(errors = ?errors, "Server is not ready");
+
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            status: match status {
+                StatusCode::OK => "ready",
+                _ => "unavailable",
+            },
+            version: env!("CARGO_PKG_VERSION"),
+            errors,
+        }),
+    )
+}
+
+// check that files can be created in `dir`, by creating and removing a probe file
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe_path = dir.join(format!(".ready-{}", uuid::Uuid::new_v4()));
+    fs::write(&probe_path, b"").map_err(|e| {
+        format!(
+            "The index storage directory {} is not writable: {e}",
+            dir.display()
+        )
+    })?;
+    fs::remove_file(&probe_path).map_err(|e| {
+        format!(
+            "Failed to remove the probe file {}: {e}",
+            probe_path.display()
+        )
+    })
+}
@@ -0,0 +1,160 @@
+//! Extraction of the readable text of uploaded HTML files, e.g. scraped web pages, so that their
+//! markup, scripts and styles are not indexed.
+
+use scraper::{ElementRef, Html, Node, Selector};
+
+// elements whose content is not readable text
+const SKIPPED_ELEMENTS: [&str; 7] = [
+    "head", "script", "style", "noscript", "template", "svg", "iframe",
+];
+
+// elements laid out as blocks, whose text is kept on separate lines
+const BLOCK_ELEMENTS: [&str; 30] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "tr",
+];
+
+// elements of a table row, whose texts are separated by a space
+const CELL_ELEMENTS: [&str; 2] = ["td", "th"];
+
+/// Readable text of an HTML document
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HtmlText {
+    /// Text of the `<title>` element, if any
+    pub(crate) title: Option<String>,
+    /// Text of the document, one line per block, without the content of scripts and styles
+    pub(crate) body: String,
+}
+
+/// Whether an uploaded file is an HTML file, from its content type or, for a generic content
+/// type, from the extension of its name.
+pub(crate) fn is_html(content_type: &str, filename: Option<&str>) -> bool {
+    match content_type {
+        "text/html" => true,
+        "application/octet-stream" => filename
+            .and_then(|filename| filename.rsplit_once('.'))
+            .is_some_and(|(_, extension)| {
+                extension.eq_ignore_ascii_case("html") || extension.eq_ignore_ascii_case("htm")
+            }),
+        _ => false,
+    }
+}
+
+/// Extract the readable text of an HTML document.
+pub(crate) fn extract_text(html: &str) -> HtmlText {
+    let document = Html::parse_document(html);
+
+    let title_selector = Selector::parse("title").expect("valid selector");
+    let title = document
+        .select(&title_selector)
+        .next()
+        .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        .filter(|title| !title.is_empty());
+
+    let mut text = String::new();
+    append_text(document.root_element(), &mut text);
+    let body = text
+        .lines()
+        .map(collapse_whitespace)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    HtmlText { title, body }
+}
+
+// append the readable text of the descendants of `element` to `text`
+fn append_text(element: ElementRef<'_>, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(child_text) => text.push_str(child_text),
+            Node::Element(child_element) if SKIPPED_ELEMENTS.contains(&child_element.name()) => {}
+            Node::Element(child_element) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                let block = BLOCK_ELEMENTS.contains(&child_element.name());
+                if block {
+                    text.push('\n');
+                }
+                append_text(child, text);
+                if block {
+                    text.push('\n');
+                } else if CELL_ELEMENTS.contains(&child_element.name()) {
+                    text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup_scripts_and_styles_are_dropped() {
+        let html = r#"<!DOCTYPE html>
+            <html>
+              <head>
+                <title> Paris   guide </title>
+                <style>body { color: red; }</style>
+                <script>var tracking = "secret";</script>
+              </head>
+              <body>
+                <h1>Visiting <em>Paris</em></h1>
+                <p>The Louvre &amp; the <a href="/eiffel">Eiffel tower</a>.</p>
+                <table><tr><td>Open</td><td>daily</td></tr></table>
+              </body>
+            </html>"#;
+
+        let text = extract_text(html);
+
+        assert_eq!(text.title.as_deref(), Some("Paris guide"));
+        assert_eq!(
+            text.body,
+            "Visiting Paris\nThe Louvre & the Eiffel tower.\nOpen daily"
+        );
+    }
+
+    #[test]
+    fn html_files_are_detected_by_type_or_extension() {
+        assert!(is_html("text/html", None));
+        assert!(is_html("application/octet-stream", Some("page.HTM")));
+        assert!(!is_html("application/octet-stream", Some("page.txt")));
+        assert!(!is_html("text/plain", Some("page.html")));
+    }
+}
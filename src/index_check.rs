@@ -0,0 +1,210 @@
+//! Integrity check and repair of an index, meant as a recovery tool after an ungraceful shutdown.
+//!
+//! The check can repair:
+//! - orphaned files, e.g. segments written by a commit that never completed. They are garbage
+//!   collected, since no commit references them.
+//! - a stale `.tantivy-writer.lock` file. The lock is an advisory lock of the operating system,
+//!   released when the process holding it dies, so a leftover file does not block indexing. It is
+//!   removed anyway once the lock is known not to be held. Since writers always leave this file
+//!   behind, its removal is reported separately and does not make the index count as repaired.
+//!
+//! Repairs are skipped if a writer currently holds the index.
+//!
+//! It cannot repair a missing or unparsable `meta.json`, nor segment files whose checksum does not
+//! match. Such indexes are reported as `corrupted` and must be re-created.
+
+use crate::{
+    aliases, error::ServerError, index_storage_dir, memory_budget::MIN_WRITER_MEMORY_BYTES,
+    reader_cache, token_length, validate_index_name,
+};
+use axum::{extract::Path, Json};
+use serde::Serialize;
+use tantivy::{
+    directory::{error::LockError, Directory, Lock, INDEX_WRITER_LOCK},
+    Index, IndexReader, ReloadPolicy, TantivyDocument, TantivyError,
+};
+use tracing::{error, info, warn};
+
+/// Health of an index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IndexHealth {
+    /// The index loads and no repair was needed
+    Healthy,
+    /// The index loads after the repairs listed in the report
+    Repaired,
+    /// A writer currently holds the index, so only the read-only checks were performed
+    Busy,
+    /// The index cannot be loaded, or some of its files are damaged
+    Corrupted,
+}
+
+/// Response body of the `POST /v1/index/{index_name}/check` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexCheckReport {
+    /// Name of the checked index
+    pub(crate) index_name: String,
+    /// Health of the index
+    pub(crate) status: IndexHealth,
+    /// Number of searchable segments
+    pub(crate) segments: usize,
+    /// Number of searchable documents
+    pub(crate) num_docs: u64,
+    /// Files of the searchable segments whose checksum does not match
+    pub(crate) damaged_files: Vec<String>,
+    /// Whether a leftover writer lock file, not held by any writer, was removed
+    pub(crate) stale_lock_removed: bool,
+    /// Repairs performed by the check, besides removing a stale lock file
+    pub(crate) repairs: Vec<String>,
+    /// Error preventing the index from being loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+// check the integrity of an index and repair what can be repaired
+pub(crate) async fn check_index_handler(
+    Path(index_name): Path<String>,
+) -> Result<Json<IndexCheckReport>, ServerError> {
+    info!(index_name = %index_name, "Received index check request");
+
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&index_name);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{index_name}' not found");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let report = check_index(&index_name, &index_path);
+    info!(
+        index_name = %index_name,
+        status = ?report.status,
+        repairs = report.repairs.len(),
+        "Index check completed"
+    );
+
+    Ok(Json(report))
+}
+
+// check the index located at `index_path`
+fn check_index(index_name: &str, index_path: &std::path::Path) -> IndexCheckReport {
+    let mut report = IndexCheckReport {
+        index_name: index_name.to_string(),
+        status: IndexHealth::Healthy,
+        segments: 0,
+        num_docs: 0,
+        damaged_files: Vec::new(),
+        stale_lock_removed: false,
+        repairs: Vec::new(),
+        error: None,
+    };
+
+    // open the index and verify that its segments load
+    let index = match Index::open_in_dir(index_path) {
+        Ok(index) => index,
+        Err(e) => return corrupted(report, format!("Failed to open index: {e}")),
+    };
+    token_length::register_from_metadata(&index);
+    let reader: IndexReader = match index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()
+    {
+        Ok(reader) => reader,
+        Err(e) => return corrupted(report, format!("Failed to load segments: {e}")),
+    };
+    let searcher = reader.searcher();
+    report.segments = searcher.segment_readers().len();
+    report.num_docs = searcher.num_docs();
+
+    match index.validate_checksum() {
+        Ok(damaged_files) => {
+            report.damaged_files = damaged_files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            report.damaged_files.sort();
+        }
+        Err(e) => return corrupted(report, format!("Failed to validate checksums: {e}")),
+    }
+    if !report.damaged_files.is_empty() {
+        return corrupted(report, "Some segment files are damaged".to_string());
+    }
+
+    // a writer holding the lock may be writing files, so they must not be touched
+    let lock = Lock {
+        filepath: INDEX_WRITER_LOCK.filepath.clone(),
+        is_blocking: false,
+    };
+    let lock_path = index_path.join(&lock.filepath);
+    let lock_file_existed = lock_path.exists();
+    match index.directory().acquire_lock(&lock) {
+        Ok(directory_lock) => {
+            // the lock is not held, so a leftover lock file is stale. It is removed while the
+            // lock is held, so that no writer acquires it in the meantime. Writers leave the
+            // file behind when they are dropped, so this is not reported as a repair.
+            if lock_file_existed {
+                match std::fs::remove_file(&lock_path) {
+                    Ok(()) => report.stale_lock_removed = true,
+                    Err(e) => {
+                        warn!(index_name = %index_name, error = %e, "Failed to remove stale lock file")
+                    }
+                }
+            }
+            drop(directory_lock);
+        }
+        Err(LockError::LockBusy) => return busy(report),
+        Err(e) => return corrupted(report, format!("Failed to acquire writer lock: {e}")),
+    }
+
+    // remove orphaned files, i.e. files of the index that no commit references. No document is
+    // added, so the writer gets the minimum budget, outside of the arbitration of the writers.
+    match index.writer_with_num_threads::<TantivyDocument>(1, MIN_WRITER_MEMORY_BYTES) {
+        Ok(index_writer) => match index_writer.garbage_collect_files().wait() {
+            Ok(result) => {
+                for path in result.deleted_files {
+                    report
+                        .repairs
+                        .push(format!("Removed orphaned file {}", path.display()));
+                }
+            }
+            Err(e) => {
+                warn!(index_name = %index_name, error = %e, "Failed to remove orphaned files")
+            }
+        },
+        Err(TantivyError::LockFailure(LockError::LockBusy, _)) => return busy(report),
+        Err(e) => warn!(index_name = %index_name, error = %e, "Failed to create index writer"),
+    }
+
+    if !report.repairs.is_empty() {
+        report.status = IndexHealth::Repaired;
+        reader_cache::invalidate(index_name);
+    }
+
+    report
+}
+
+// mark the report as busy, skipping the repairs
+fn busy(mut report: IndexCheckReport) -> IndexCheckReport {
+    warn!(index_name = %report.index_name, "Index is locked by a writer, skipping repairs");
+
+    report.status = IndexHealth::Busy;
+    report
+}
+
+// mark the report as corrupted
+fn corrupted(mut report: IndexCheckReport, err_msg: String) -> IndexCheckReport {
+    error!(index_name = %report.index_name, "{}", &err_msg);
+
+    report.status = IndexHealth::Corrupted;
+    report.error = Some(err_msg);
+    report
+}
@@ -0,0 +1,105 @@
+//! Listing of the indexes of the server, so that clients can discover them, e.g. after a restart.
+
+use crate::{
+    error::ServerError,
+    index_meta::{self, IndexProvenance},
+    index_storage_dir, request_id,
+};
+use axum::Json;
+use serde::Serialize;
+use std::path::Path;
+use tantivy::Index;
+use tracing::{error, info, warn};
+
+/// An index of the server
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexSummary {
+    /// Name of the index
+    pub(crate) index_name: String,
+    /// Number of documents of the index
+    pub(crate) num_docs: u64,
+    /// Total size in bytes of the files of the index
+    pub(crate) size_bytes: u64,
+    /// Provenance of the index, e.g. its creation time, if recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) provenance: Option<IndexProvenance>,
+}
+
+// list the indexes of the index storage directory
+pub(crate) async fn list_indexes_handler() -> Result<Json<Vec<IndexSummary>>, ServerError> {
+    info!("Received list indexes request");
+
+    // opening every index is blocking work
+    let index_storage_dir = index_storage_dir();
+    let listed = request_id::spawn_blocking(move || list_indexes(&index_storage_dir)).await;
+    match listed {
+        Ok(indexes) => {
+            info!(count = indexes.len(), "Listed indexes");
+
+            Ok(Json(indexes))
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to list indexes: {e}");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::Operation(err_msg))
+        }
+    }
+}
+
+// summaries of the indexes of `index_storage_dir`, sorted by name. Directories that cannot be
+// opened as an index are skipped.
+fn list_indexes(index_storage_dir: &Path) -> Vec<IndexSummary> {
+    let Ok(entries) = std::fs::read_dir(index_storage_dir) else {
+        return Vec::new();
+    };
+
+    // staging directories of uploads and replaced indexes are hidden
+    let mut indexes: Vec<IndexSummary> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let index_name = entry.file_name().into_string().ok()?;
+            if index_name.starts_with('.') {
+                return None;
+            }
+
+            let index_path = entry.path();
+            let opened = Index::open_in_dir(&index_path).and_then(|index| {
+                let num_docs = index.reader()?.searcher().num_docs();
+                Ok((index, num_docs))
+            });
+            match opened {
+                Ok((index, num_docs)) => Some(IndexSummary {
+                    size_bytes: dir_size(&index_path),
+                    index_name,
+                    num_docs,
+                    provenance: index_meta::load(&index).provenance,
+                }),
+                Err(e) => {
+                    warn!(index_name = %index_name, error = %e, "Skipping directory that cannot be opened as an index");
+                    None
+                }
+            }
+        })
+        .collect();
+
+    indexes.sort_by(|a, b| a.index_name.cmp(&b.index_name));
+    indexes
+}
+
+/// Total size of the files under `path`
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            _ => entry.metadata().map_or(0, |metadata| metadata.len()),
+        })
+        .sum()
+}
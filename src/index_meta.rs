@@ -0,0 +1,125 @@
+//! Metadata of an index, stored as JSON in the payload of its commit in `meta.json`.
+
+use crate::keyword_search::{SearchDefaults, TextTokenizer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tantivy::{schema::Schema, Index, IndexWriter, Opstamp};
+use tracing::warn;
+
+/// Metadata of an index
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(crate) struct IndexMetadata {
+    /// Defaults of the search requests omitting the corresponding parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) search_defaults: Option<SearchDefaults>,
+    /// Boosts of the default search fields, by field name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) field_boosts: BTreeMap<String, f32>,
+    /// Minimum length in characters of the tokens of the `title` and `body` fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) min_token_length: Option<usize>,
+    /// Tokenizer of the `title` and `body` fields, if not the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tokenizer: Option<TextTokenizer>,
+    /// Stop words removed from the `title` and `body` fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) stopwords: Vec<String>,
+    /// Field identifying the documents, which is a keyword field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) unique_key: Option<String>,
+    /// Provenance of the index. Indexes created by earlier versions of the server have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) provenance: Option<IndexProvenance>,
+}
+
+/// Provenance of an index, recorded when it is created
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct IndexProvenance {
+    /// Creation time of the index, as a Unix timestamp in seconds
+    pub(crate) created_at: u64,
+    /// Number of documents indexed when the index was created
+    pub(crate) num_documents: u64,
+    /// Names of the uploaded files the documents were read from, without duplicates
+    pub(crate) source_filenames: Vec<String>,
+    /// Names of the fields of the schema of the index
+    pub(crate) fields: Vec<String>,
+}
+
+impl IndexProvenance {
+    /// Provenance of an index of `schema` created now with `num_documents` documents, read from
+    /// the files `source_filenames`
+    pub(crate) fn new<'a>(
+        schema: &Schema,
+        num_documents: u64,
+        source_filenames: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        let mut filenames: Vec<String> = Vec::new();
+        for filename in source_filenames {
+            if !filenames.iter().any(|known| known == filename) {
+                filenames.push(filename.to_string());
+            }
+        }
+
+        Self {
+            created_at,
+            num_documents,
+            source_filenames: filenames,
+            fields: schema
+                .fields()
+                .map(|(_, entry)| entry.name().to_string())
+                .collect(),
+        }
+    }
+}
+
+impl IndexMetadata {
+    // whether there is nothing to store
+    fn is_empty(&self) -> bool {
+        self.search_defaults.is_none()
+            && self.field_boosts.is_empty()
+            && self.min_token_length.is_none()
+            && self.tokenizer.is_none()
+            && self.stopwords.is_empty()
+            && self.unique_key.is_none()
+            && self.provenance.is_none()
+    }
+}
+
+/// Commit the pending documents of `index_writer`, storing `metadata` along with the commit.
+pub(crate) fn commit_with_metadata(
+    index_writer: &mut IndexWriter,
+    metadata: &IndexMetadata,
+) -> tantivy::Result<Opstamp> {
+    let mut prepared_commit = index_writer.prepare_commit()?;
+    if !metadata.is_empty() {
+        prepared_commit.set_payload(&serde_json::to_string(metadata)?);
+    }
+    prepared_commit.commit()
+}
+
+/// Load the metadata stored with the last commit of `index`. Indexes created without metadata,
+/// or whose metadata cannot be read, get the default metadata.
+pub(crate) fn load(index: &Index) -> IndexMetadata {
+    let payload = match index.load_metas() {
+        Ok(metas) => metas.payload,
+        Err(e) => {
+            warn!(error = %e, "Failed to load index metas");
+            return IndexMetadata::default();
+        }
+    };
+
+    match payload {
+        Some(payload) => serde_json::from_str(&payload).unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to parse index metadata");
+            IndexMetadata::default()
+        }),
+        None => IndexMetadata::default(),
+    }
+}
@@ -0,0 +1,105 @@
+//! Statistics of an index, e.g. to spot an index fragmented into many small segments, or holding
+//! many deleted documents, which a merge would compact.
+
+use crate::{
+    aliases, error::ServerError, index_list, index_storage_dir, reader_cache, request_id,
+    validate_index_name,
+};
+use axum::{extract::Path, Json};
+use serde::Serialize;
+use tracing::{error, info};
+
+/// Response body of the `GET /v1/index/{index_name}/stats` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexStats {
+    /// Name of the index
+    pub(crate) index_name: String,
+    /// Number of documents of the index, deleted documents excluded
+    pub(crate) num_docs: u64,
+    /// Number of deleted documents not merged away yet
+    pub(crate) num_deleted: u64,
+    /// Number of segments of the index
+    pub(crate) num_segments: usize,
+    /// Total size in bytes of the files of the index
+    pub(crate) size_bytes: u64,
+    /// Names of the fields of the schema of the index
+    pub(crate) schema_fields: Vec<String>,
+}
+
+// statistics of an index
+pub(crate) async fn index_stats_handler(
+    Path(index_name): Path<String>,
+) -> Result<Json<IndexStats>, ServerError> {
+    info!(index_name = %index_name, "Received index stats request");
+
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&index_name);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{index_name}' not found");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    let searcher = reader.searcher();
+    let segment_readers = searcher.segment_readers();
+    let num_deleted = segment_readers
+        .iter()
+        .map(|segment_reader| segment_reader.num_deleted_docs() as u64)
+        .sum();
+    let schema_fields = index
+        .schema()
+        .fields()
+        .map(|(_, entry)| entry.name().to_string())
+        .collect();
+
+    // walking the index directory is blocking work
+    let size_bytes =
+        match request_id::spawn_blocking(move || index_list::dir_size(&index_path)).await {
+            Ok(size_bytes) => size_bytes,
+            Err(e) => {
+                let err_msg = format!("Failed to compute the size of the index: {e}");
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        };
+
+    let stats = IndexStats {
+        num_docs: searcher.num_docs(),
+        num_deleted,
+        num_segments: segment_readers.len(),
+        size_bytes,
+        schema_fields,
+        index_name,
+    };
+
+    info!(
+        index_name = %stats.index_name,
+        num_docs = stats.num_docs,
+        num_deleted = stats.num_deleted,
+        num_segments = stats.num_segments,
+        "Index stats computed"
+    );
+
+    Ok(Json(stats))
+}
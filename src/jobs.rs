@@ -0,0 +1,237 @@
+//! Background indexing jobs created for requests carrying a `callback_url`.
+
+use crate::{error::ServerError, keyword_search::IndexResponse, CALLBACK_ALLOWED_HOSTS};
+use axum::{extract::Path, Json};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+use url::Url;
+
+// how long the status of a finished job can still be polled
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+// maximum number of finished jobs kept, the oldest ones being evicted first
+const MAX_FINISHED_JOBS: usize = 1000;
+// time allowed to connect to a callback URL, and to post a result to it
+const CALLBACK_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// registry of indexing jobs, keyed by job id
+static INDEX_JOBS: Lazy<RwLock<HashMap<String, IndexJob>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+// client posting the results to the callback URLs, shared so that its connections are reused
+static CALLBACK_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .connect_timeout(CALLBACK_CONNECT_TIMEOUT)
+        .timeout(CALLBACK_TIMEOUT)
+        .build()
+        .expect("Failed to build the callback HTTP client")
+});
+
+/// Status of a background indexing job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobStatus {
+    /// The documents are being indexed
+    Pending,
+    /// Indexing finished and the index was created
+    Completed,
+    /// Indexing finished without creating an index
+    Failed,
+}
+
+/// A background indexing job
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct IndexJob {
+    /// Id of the job
+    pub(crate) job_id: String,
+    /// Status of the job
+    pub(crate) status: JobStatus,
+    /// URL the final `IndexResponse` is posted to
+    pub(crate) callback_url: String,
+    /// Final response of the indexing, available once the job is no longer pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) result: Option<IndexResponse>,
+    /// Error returned while posting the result to the callback URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) callback_error: Option<String>,
+    /// When the job finished, to evict it once its status has been kept long enough
+    #[serde(skip)]
+    finished_at: Option<Instant>,
+}
+
+/// Check that the callback URL uses `http(s)` and points to one of the hosts allowed by the
+/// `--callback-allowed-hosts` CLI option.
+pub(crate) fn validate_callback_url(callback_url: &str) -> Result<Url, ServerError> {
+    let url = Url::parse(callback_url)
+        .map_err(|e| ServerError::BadRequest(format!("Invalid callback URL: {e}")))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ServerError::BadRequest(format!(
+            "Unsupported callback URL scheme: {}",
+            url.scheme()
+        )));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| ServerError::BadRequest("Callback URL has no host".to_string()))?;
+    let allowed = CALLBACK_ALLOWED_HOSTS
+        .get()
+        .map(|hosts| hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+        .unwrap_or(false);
+    if !allowed {
+        return Err(ServerError::BadRequest(format!(
+            "Callback host '{host}' is not allowed"
+        )));
+    }
+
+    Ok(url)
+}
+
+/// Register a new pending job and return its id.
+pub(crate) fn create_job(callback_url: &Url) -> String {
+    let job_id = format!("job-{}", uuid::Uuid::new_v4());
+    let job = IndexJob {
+        job_id: job_id.clone(),
+        status: JobStatus::Pending,
+        callback_url: callback_url.to_string(),
+        result: None,
+        callback_error: None,
+        finished_at: None,
+    };
+
+    let mut jobs = INDEX_JOBS.write().unwrap();
+    evict_finished_jobs(&mut jobs, Instant::now());
+    jobs.insert(job_id.clone(), job);
+
+    job_id
+}
+
+/// Record the final response of a job and post it to the callback URL.
+pub(crate) async fn complete_job(job_id: String, callback_url: Url, response: IndexResponse) {
+    let status = match response.index_name {
+        Some(_) => JobStatus::Completed,
+        None => JobStatus::Failed,
+    };
+
+    info!(job_id = %job_id, callback_url = %callback_url, "Posting indexing result to callback URL");
+    let callback_error = match CALLBACK_CLIENT
+        .post(callback_url)
+        .json(&response)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+    {
+        Ok(_) => None,
+        Err(e) => {
+            warn!(job_id = %job_id, error = %e, "Failed to post indexing result to callback URL");
+            Some(e.to_string())
+        }
+    };
+
+    if let Some(job) = INDEX_JOBS.write().unwrap().get_mut(&job_id) {
+        job.status = status;
+        job.result = Some(response);
+        job.callback_error = callback_error;
+        job.finished_at = Some(Instant::now());
+    }
+}
+
+// drop the finished jobs older than `FINISHED_JOB_TTL`, then the oldest ones beyond
+// `MAX_FINISHED_JOBS`. Pending jobs are always kept, as their result is still to come.
+fn evict_finished_jobs(jobs: &mut HashMap<String, IndexJob>, now: Instant) {
+    jobs.retain(|_, job| {
+        job.finished_at
+            .is_none_or(|finished_at| now.duration_since(finished_at) < FINISHED_JOB_TTL)
+    });
+
+    let mut finished: Vec<(Instant, String)> = jobs
+        .values()
+        .filter_map(|job| job.finished_at.map(|at| (at, job.job_id.clone())))
+        .collect();
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort_unstable();
+        for (_, job_id) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+            jobs.remove(job_id);
+        }
+    }
+}
+
+// get the status of an indexing job
+pub(crate) async fn index_job_handler(
+    Path(job_id): Path<String>,
+) -> Result<Json<IndexJob>, ServerError> {
+    info!(job_id = %job_id, "Received index job status request");
+
+    match INDEX_JOBS.read().unwrap().get(&job_id) {
+        Some(job) => Ok(Json(job.clone())),
+        None => {
+            let err_msg = format!("Job '{job_id}' not found");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::NotFound(err_msg))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(job_id: &str, finished_at: Option<Instant>) -> (String, IndexJob) {
+        let job = IndexJob {
+            job_id: job_id.to_string(),
+            status: JobStatus::Pending,
+            callback_url: "http://localhost/callback".to_string(),
+            result: None,
+            callback_error: None,
+            finished_at,
+        };
+
+        (job_id.to_string(), job)
+    }
+
+    #[test]
+    fn expired_finished_jobs_are_evicted() {
+        let now = Instant::now() + FINISHED_JOB_TTL * 2;
+        let mut jobs = HashMap::from([
+            job("pending", None),
+            job("expired", Some(now - FINISHED_JOB_TTL)),
+            job("recent", Some(now - Duration::from_secs(1))),
+        ]);
+
+        evict_finished_jobs(&mut jobs, now);
+
+        let mut kept: Vec<_> = jobs.keys().map(String::as_str).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, ["pending", "recent"]);
+    }
+
+    #[test]
+    fn oldest_finished_jobs_are_evicted_beyond_the_cap() {
+        let now = Instant::now() + Duration::from_secs(MAX_FINISHED_JOBS as u64 + 10);
+        let mut jobs: HashMap<_, _> = (0..MAX_FINISHED_JOBS + 5)
+            .map(|i| {
+                job(
+                    &format!("job-{i}"),
+                    Some(now - Duration::from_secs(i as u64 + 1)),
+                )
+            })
+            .chain([job("pending", None)])
+            .collect();
+
+        evict_finished_jobs(&mut jobs, now);
+
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert!(jobs.contains_key("pending"));
+        assert!(jobs.contains_key("job-0"));
+        assert!(!jobs.contains_key(&format!("job-{}", MAX_FINISHED_JOBS + 4)));
+    }
+}
@@ -0,0 +1,499 @@
+//! Request and response types of the keyword search endpoints.
+
+use crate::index_meta::IndexProvenance;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Request body of the `/v1/index/create` endpoint
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IndexRequest {
+    /// Documents to index
+    pub documents: Vec<DocumentInput>,
+    /// Name of the index, made of 1 to 64 letters, digits, `_` and `-`, e.g. `docs-v2`. Also
+    /// accepted as `index_name`. If not provided, a name is generated.
+    #[serde(alias = "index_name", skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    /// URL the final `IndexResponse` is posted to. If provided, the documents are indexed in
+    /// background and the request returns immediately with a job id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
+    /// Make the documents searchable as soon as the response is returned. They always are, since
+    /// each request commits its index before responding, so this is accepted but has no effect.
+    #[serde(default)]
+    pub commit_immediately: bool,
+    /// Also index the title of each document into its body, so that searching the body matches
+    /// title words too. The title is then indexed and stored twice, which grows the index.
+    #[serde(default)]
+    pub title_in_body: bool,
+    /// Create the index even if `documents` is empty. Otherwise, such requests are rejected.
+    #[serde(default)]
+    pub allow_empty: bool,
+    /// Defaults of the search requests against this index, overriding the global defaults
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_defaults: Option<SearchDefaults>,
+    /// Boosts of the default search fields `title` and `body`, stored with the index and applied
+    /// to every search against it, e.g. `{"title": 2.0}` to weigh title matches twice as much
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_boosts: BTreeMap<String, f32>,
+    /// Transformations of the content of the documents before indexing, replacing those set with
+    /// the `--preprocess`, `--redact` and `--redact-regex` CLI options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preprocessing: Option<Preprocessing>,
+    /// Field identifying the documents, stored with the index: `content_hash`, or the name of a
+    /// value of the `fields` of the documents, e.g. `url`. A document whose key was already
+    /// indexed by the request replaces the earlier one, and searches can deduplicate their hits
+    /// by key. Keys are not compared with the documents of other indexes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unique_key: Option<String>,
+    /// Skip the documents whose content, once preprocessed, is identical to the content of a
+    /// document indexed before them by the request. They are reported with the `skipped` status.
+    #[serde(default)]
+    pub dedup: bool,
+    /// Tokenizer of the `title` and `body` fields, stored with the index so that queries are
+    /// tokenized the same way
+    #[serde(default)]
+    pub tokenizer: TextTokenizer,
+    /// Remove the stop words from the `title` and `body` fields, when indexing and when parsing
+    /// queries: `stopwords` if provided, else common English words like `the` and `and`
+    #[serde(default)]
+    pub remove_stopwords: bool,
+    /// Stop words removed with `remove_stopwords`, replacing the English stop words
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stopwords: Vec<String>,
+}
+
+/// Tokenizer of the `title` and `body` fields of an index
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextTokenizer {
+    /// Words split on non-alphanumeric characters, in lowercase
+    #[default]
+    Default,
+    /// Words of `default` reduced to their English stem, so that `running` matches `run`
+    EnStem,
+    /// Words split on whitespace, kept as is
+    Whitespace,
+    /// The whole text as a single token, matched exactly
+    Raw,
+}
+
+impl TextTokenizer {
+    /// Whether the tokenizer lowercases the tokens
+    pub fn lowercases(self) -> bool {
+        matches!(self, TextTokenizer::Default | TextTokenizer::EnStem)
+    }
+
+    /// Name of the tokenizer, as registered by default in every index
+    pub fn name(self) -> &'static str {
+        match self {
+            TextTokenizer::Default => "default",
+            TextTokenizer::EnStem => "en_stem",
+            TextTokenizer::Whitespace => "whitespace",
+            TextTokenizer::Raw => "raw",
+        }
+    }
+}
+
+/// Transformations of the content of a document before indexing. The enabled steps are applied
+/// in the order of the fields: cleanup, HTML stripping, Markdown stripping, redaction, and
+/// whitespace normalization.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Preprocessing {
+    /// Remove control and zero-width characters, and replace non-breaking spaces with spaces.
+    /// Letter case is preserved.
+    #[serde(default)]
+    pub cleanup: bool,
+    /// Remove HTML tags, comments, scripts and styles, and decode character references
+    #[serde(default)]
+    pub strip_html: bool,
+    /// Remove Markdown syntax, keeping the text of headings, lists, emphasis, links and code
+    #[serde(default)]
+    pub strip_markdown: bool,
+    /// Built-in patterns of personal data replaced with `[REDACTED]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<PiiPattern>,
+    /// Regular expressions whose matches are replaced with `[REDACTED]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact_regex: Vec<String>,
+    /// Collapse runs of spaces and tabs into one space, trim the lines, and collapse runs of blank
+    /// lines into one
+    #[serde(default)]
+    pub normalize_whitespace: bool,
+}
+
+/// Built-in pattern of personal data to redact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum PiiPattern {
+    /// Email addresses, e.g. `jane.doe@example.com`
+    Email,
+    /// Phone numbers, e.g. `+1 (555) 123-4567`
+    Phone,
+    /// IPv4 addresses, e.g. `192.168.1.1`
+    IpAddress,
+    /// Payment card numbers of 13 to 19 digits, e.g. `4111 1111 1111 1111`
+    CreditCard,
+}
+
+/// Defaults of the search requests against an index, used when a request omits them
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SearchDefaults {
+    /// Number of hits to return
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Minimum score of the returned hits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f32>,
+}
+
+/// A document to index
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DocumentInput {
+    /// Content of the document
+    pub content: String,
+    /// Title of the document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Precomputed quality or popularity score of the document, usable to rank search results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
+    /// Named values of a structured document, e.g. the columns of a CSV row, searchable with
+    /// queries like `fields.city:paris`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+    /// Tags of the document, e.g. a category or a source, matched exactly by the `filter` of
+    /// search requests
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Creation date of the document in RFC 3339 format, e.g. `2024-05-01T12:00:00Z`, usable to
+    /// sort the hits with `"sort_by": "created_at"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Response body of the `/v1/index/create` endpoint
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IndexResponse {
+    /// Indexing result of each document
+    pub results: Vec<DocumentResult>,
+    /// Name of the index
+    pub index_name: Option<String>,
+    /// URL to download the index file
+    pub download_url: Option<String>,
+    /// Provenance of the created index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<IndexProvenance>,
+}
+
+/// Indexing result of a single document
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DocumentResult {
+    /// Name of the uploaded file or title of the document
+    pub filename: Option<String>,
+    /// Status of the document, e.g. `indexed`, `skipped` or `failed`
+    pub status: String,
+    /// Error message if the document failed to index
+    pub error: Option<String>,
+}
+
+/// Request body of the `/v1/search` endpoint
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QueryRequest {
+    /// Query string
+    pub query: String,
+    /// Number of hits to return. Defaults to the default of the index, if any, or to
+    /// `DEFAULT_TOP_K`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Minimum score of the returned hits. Defaults to the default of the index, if any. Scores
+    /// are BM25 scores, which are not normalized to [0, 1] and depend on the index and the query.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f32>,
+    /// Name of the index to search. If empty or not provided, the `--default-index` CLI option,
+    /// or the only index of the server, is searched.
+    #[serde(default)]
+    pub index: String,
+    /// Names of several indexes to search instead of `index`. Their hits are merged by score, and
+    /// the indexes that cannot be searched are reported in the `warnings` of the response.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<String>,
+    /// Maximum number of characters of the generated snippet. Defaults to 150.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet_max_chars: Option<usize>,
+    /// Maximum number of characters of the document body scanned while generating the snippet.
+    /// Defaults to the `--snippet-max-scan-chars` CLI option.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet_max_scan_chars: Option<usize>,
+    /// How the snippet is built from the best matching fragment
+    #[serde(default)]
+    pub snippet_mode: SnippetMode,
+    /// Number of sentences of context added on each side of the best matching sentences, in
+    /// `sentences` snippet mode
+    #[serde(default = "default_snippet_context_sentences")]
+    pub snippet_context_sentences: usize,
+    /// Return the offsets of the snippet within the content of each hit, for precise citations
+    #[serde(default)]
+    pub snippet_offsets: bool,
+    /// Tag inserted before each highlighted term of the snippet. Defaults to `<b>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_pre_tag: Option<String>,
+    /// Tag inserted after each highlighted term of the snippet. Defaults to `</b>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_post_tag: Option<String>,
+    /// Strategy used to retry the search with a relaxed query if the query yields no hits
+    #[serde(default)]
+    pub fallback: FallbackStrategy,
+    /// Match each word of the query allowing typos, e.g. `kubernettes` for `kubernetes`, instead
+    /// of parsing the query. Its syntax, e.g. phrases and operators, is then ignored.
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Maximum number of typos per word of a fuzzy search, or of the `fuzzy` fallback, at most 2.
+    /// Defaults to 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fuzzy_distance: Option<u8>,
+    /// Numeric or date fast field to sort the hits by, instead of relevance, e.g. `quality`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    /// Order of the `sort_by` field
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Weight of the document quality added to the relevance score of each hit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_weight: Option<f32>,
+    /// Length of the documents preferred among hits with similar scores
+    #[serde(default)]
+    pub prefer_length: PreferLength,
+    /// Return the explanation of the relevance score of each hit
+    #[serde(default)]
+    pub explain: bool,
+    /// Collapse hits sharing the same value of the given key into the highest-scoring one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dedup_by: Option<DedupBy>,
+    /// Number of decimal places the scores of the hits are rounded to. Scores are returned with
+    /// full precision if not provided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_precision: Option<u32>,
+    /// Numeric fast fields to compute aggregate statistics for, over all the matching documents
+    /// rather than only the returned hits, e.g. `quality`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stats: Vec<String>,
+    /// Time budget of the search in milliseconds, at most the `--max-search-timeout-ms` of the
+    /// server. Once spent, the hits collected so far are returned with `timed_out` set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// Number of hits skipped, to page through the hits `top_k` at a time. Defaults to 0.
+    #[serde(default)]
+    pub offset: usize,
+    /// Fields searched by the terms of the query without a field reference, e.g. `["title",
+    /// "body"]`. The fallback query searches them as well. Defaults to `body`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    /// Filter of the hits, e.g. `{"tags": ["finance"]}`, applied to the query and the fallback
+    /// query without affecting the scores
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<SearchFilter>,
+    /// Fields of `RETURNABLE_FIELDS` returned in each hit, e.g. `["title", "score"]` to leave out
+    /// the content of the documents. All of them are returned if not provided.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub return_fields: Vec<String>,
+}
+
+/// Filter of the hits of a search
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SearchFilter {
+    /// Tags the hits must all have, matched exactly
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl QueryRequest {
+    /// Number of hits returned if `top_k` is not provided
+    pub const DEFAULT_TOP_K: usize = 5;
+
+    /// Fields of the hits that can be selected with `return_fields`
+    pub const RETURNABLE_FIELDS: [&'static str; 3] = ["title", "content", "score"];
+}
+
+fn default_snippet_context_sentences() -> usize {
+    1
+}
+
+/// How the snippet of a hit is built
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetMode {
+    /// The best matching fragment of at most `snippet_max_chars` characters
+    #[default]
+    Fragment,
+    /// The sentences overlapping the best matching fragment, plus `snippet_context_sentences`
+    /// sentences on each side
+    Sentences,
+}
+
+/// Strategy used to relax a query that yields no hits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackStrategy {
+    /// Match any of the query terms, allowing `fuzzy_distance` typos per term
+    Fuzzy,
+    /// Match any of the query terms
+    Or,
+    /// Do not retry
+    #[default]
+    None,
+}
+
+/// Sort order of search hits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Ascending order
+    Asc,
+    /// Descending order
+    #[default]
+    Desc,
+}
+
+/// Length of the documents preferred among hits with similar scores
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreferLength {
+    /// Shorter, more focused documents
+    Short,
+    /// Longer, more comprehensive documents
+    Long,
+    /// Rank by score only
+    #[default]
+    None,
+}
+
+/// Key used to deduplicate search hits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupBy {
+    /// SHA-256 hash of the document content, computed at indexing time
+    ContentHash,
+    /// Unique key of the index, declared when it was created
+    UniqueKey,
+}
+
+/// Response body of the `/v1/search` endpoint
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueryResponse {
+    /// Search hits
+    pub hits: Vec<SearchHit>,
+    /// Error message if the search failed. Failed searches are now answered with an error body
+    /// and the status of the failure instead, so the server never sets it. It is kept so that the
+    /// response keeps the shape of the shared type.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Whether the hits come from the fallback query
+    #[serde(default)]
+    pub fallback_used: bool,
+    /// Statistics of each field listed in the `stats` parameter of the request, over all the
+    /// matching documents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_stats: Option<BTreeMap<String, FieldStats>>,
+    /// Whether the search ran out of time, so the hits and statistics cover only the documents
+    /// collected before the timeout
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// Number of documents matching the query, whatever the `offset`, `top_k`, `min_score` and
+    /// deduplication of the request. Always serialized, as `null` if the search failed.
+    pub total_hits: Option<usize>,
+    /// Time taken by the search, in milliseconds
+    #[serde(default)]
+    pub took_ms: u64,
+    /// Indexes of a multi-index search that could not be searched, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Statistics of the values of a field over the matching documents
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FieldStats {
+    /// Number of values. Documents without a value are not counted.
+    pub count: u64,
+    /// Smallest value, if any
+    pub min: Option<f64>,
+    /// Largest value, if any
+    pub max: Option<f64>,
+    /// Mean of the values, if any
+    pub avg: Option<f64>,
+    /// Sum of the values
+    pub sum: f64,
+}
+
+/// A single search hit
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchHit {
+    /// Title of the document
+    pub title: String,
+    /// Content of the document
+    pub content: String,
+    /// Relevance score of the document
+    pub score: f64,
+    /// Name of the index the document comes from
+    pub index: String,
+    /// Highlighted fragment of the content that best matches the query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+    /// `[start, end)` offsets of the snippet passage within `content`, in characters (Unicode
+    /// scalar values), if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet_offsets: Option<[usize; 2]>,
+    /// Number of lower-scoring hits collapsed into this one by deduplication
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_count: Option<usize>,
+    /// Quality of the document, if provided at indexing time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
+    /// Tags of the document, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Creation date of the document in RFC 3339 format, if provided at indexing time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// Explanation of the relevance score of the document, if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<ScoreExplanation>,
+}
+
+impl QueryResponse {
+    /// The response as JSON, leaving out the title, content, and score of the hits not listed in
+    /// `fields`, if any. The hits keep the shape of the shared type otherwise.
+    pub fn to_json(&self, fields: &[String]) -> serde_json::Result<serde_json::Value> {
+        let mut response = serde_json::to_value(self)?;
+        if fields.is_empty() {
+            return Ok(response);
+        }
+
+        let hits = response["hits"].as_array_mut().into_iter().flatten();
+        for hit in hits.filter_map(serde_json::Value::as_object_mut) {
+            for field in QueryRequest::RETURNABLE_FIELDS {
+                if !fields.iter().any(|returned| returned == field) {
+                    hit.remove(field);
+                }
+            }
+        }
+
+        Ok(response)
+    }
+}
+
+/// A node of the explanation of a score: the value, how it is computed, and the values it is
+/// computed from
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScoreExplanation {
+    /// Value of this node, e.g. the score of a term
+    pub value: f32,
+    /// What the value is, e.g. `TermQuery, product of...`
+    pub description: String,
+    /// Nodes the value is computed from. Empty for leaves.
+    #[serde(default)]
+    pub details: Vec<ScoreExplanation>,
+    /// Additional information, e.g. the clauses of a boolean query that did not match
+    #[serde(default)]
+    pub context: Vec<String>,
+}
@@ -0,0 +1,352 @@
+//! Live search, streaming the hits of a query as Server-Sent Events while the segments of the index
+//! are collected, for interactive search UIs. The segments are collected one after the other, and
+//! the hits of each segment entering the top `top_k` hits so far are sent as soon as the segment
+//! is collected. A client disconnecting aborts the collection, so that abandoned searches stop
+//! using the server.
+//!
+//! The stream is made of the following events:
+//!
+//! - `hits`: the hits of a segment entering the top hits so far, in descending score order. Hits
+//!   sent before may drop out of the top hits, so clients keep the `top_k` best hits received.
+//! - `done`: the search is complete, with the number of matching documents
+//! - `error`: the search failed, with the error message
+
+use crate::{
+    aliases, clamp_top_k, default_top_k, error::ServerError, index_meta, index_storage_dir,
+    keyword_search::SearchHit, only_index, query_fields, reader_cache, request_id,
+    validate_index_name, DEFAULT_INDEX,
+};
+use axum::{
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, time::Instant};
+use tantivy::{
+    collector::{Collector, SegmentCollector, TopDocs},
+    query::{EnableScoring, Query, QueryParser},
+    schema::Value,
+    DocAddress, DocSet, Score, Searcher, TantivyDocument, TERMINATED,
+};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+// number of documents scanned between two checks of whether the client is still connected
+const DISCONNECT_CHECK_INTERVAL: u64 = 4096;
+
+// number of events buffered before the collection waits for the client to receive them
+const EVENT_BUFFER_SIZE: usize = 16;
+
+/// Request body of the `POST /v1/search/live` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct LiveSearchRequest {
+    /// Query to search
+    pub(crate) query: String,
+    /// Name of the index, or alias, to search. Defaults to the default index of the server.
+    #[serde(default)]
+    pub(crate) index: Option<String>,
+    /// Number of hits to return. Defaults to the default of the index, or 5.
+    #[serde(default)]
+    pub(crate) top_k: Option<usize>,
+    /// Fields searched by the terms of the query without a field reference. Defaults to `body`.
+    #[serde(default)]
+    pub(crate) fields: Vec<String>,
+}
+
+/// Data of the `done` event
+#[derive(Debug, Clone, Serialize)]
+struct LiveSearchDone {
+    /// Number of documents matching the query
+    total_hits: u64,
+    /// Number of segments collected
+    segments: usize,
+    /// Duration of the search in milliseconds
+    elapsed_ms: u128,
+}
+
+/// Data of the `error` event
+#[derive(Debug, Clone, Serialize)]
+struct LiveSearchError {
+    error: String,
+}
+
+// how a collection ended
+enum Collection {
+    Done(LiveSearchDone),
+    Disconnected { scanned_docs: u64 },
+}
+
+// stream the hits of a query as they are collected
+pub(crate) async fn live_search_handler(
+    Json(request): Json<LiveSearchRequest>,
+) -> Result<Response, ServerError> {
+    info!(query = %request.query, top_k = ?request.top_k, "Received live search request");
+
+    // resolve the index to search if the request does not name one
+    let requested_index = match request
+        .index
+        .clone()
+        .or_else(|| DEFAULT_INDEX.get().cloned())
+    {
+        Some(index) => index,
+        None => match only_index() {
+            Ok(index) => index,
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+    };
+
+    if let Err(err_msg) = validate_index_name(&requested_index) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&requested_index);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.exists() {
+        let err_msg = format!("Index '{requested_index}' does not exist");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    let schema = index.schema();
+    let index_metadata = index_meta::load(&index);
+
+    let top_k = request
+        .top_k
+        .or(index_metadata
+            .search_defaults
+            .and_then(|defaults| defaults.top_k))
+        .unwrap_or_else(default_top_k);
+    if top_k == 0 {
+        let err_msg = "`top_k` must be at least 1".to_string();
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+    let top_k = clamp_top_k(top_k);
+
+    // parse the query like the batch search, with the field boosts of the index
+    let default_fields = match query_fields::default_fields(&request.fields, &schema) {
+        Ok(fields) => fields,
+        Err(err_msg) => {
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+    let (title, body) = match (schema.get_field("title"), schema.get_field("body")) {
+        (Ok(title), Ok(body)) => (title, body),
+        (Err(e), _) | (_, Err(e)) => {
+            let err_msg = format!("Invalid index '{index_name}': {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    let mut query_parser = QueryParser::for_index(&index, default_fields);
+    for field in [title, body] {
+        if let Some(boost) = index_metadata
+            .field_boosts
+            .get(schema.get_field_name(field))
+        {
+            query_parser.set_field_boost(field, *boost);
+        }
+    }
+
+    let unknown_fields = query_fields::unknown_field_references(&request.query, &schema);
+    if let Some(unknown_field) = unknown_fields.first() {
+        let err_msg = format!(
+            "Unknown field '{}' in query. Valid fields are: {}",
+            unknown_field.name,
+            query_fields::queryable_fields(&schema).join(", ")
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let query = match query_parser.parse_query(&request.query) {
+        Ok(query) => query,
+        Err(e) => {
+            let err_msg = format!("Failed to parse query: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+
+    // collect on a blocking thread, which sends the events to the response stream
+    let (sender, receiver) = mpsc::channel(EVENT_BUFFER_SIZE);
+    let searcher = reader.searcher();
+    request_id::spawn_blocking(move || {
+        let start = Instant::now();
+        match collect(
+            &searcher,
+            &index_name,
+            query.as_ref(),
+            top_k,
+            &sender,
+            start,
+        ) {
+            Ok(Collection::Done(done)) => {
+                info!(
+                    index_name = %index_name,
+                    total_hits = done.total_hits,
+                    elapsed_ms = done.elapsed_ms,
+                    "Live search completed"
+                );
+                let _ = sender.blocking_send(json_event("done", &done));
+            }
+            Ok(Collection::Disconnected { scanned_docs }) => {
+                warn!(
+                    index_name = %index_name,
+                    scanned_docs,
+                    elapsed_ms = start.elapsed().as_millis(),
+                    "Live search aborted: the client disconnected"
+                );
+            }
+            Err(e) => {
+                let err_msg = format!("Failed to execute search: {e}");
+
+                error!("{}", &err_msg);
+
+                let _ =
+                    sender.blocking_send(json_event("error", &LiveSearchError { error: err_msg }));
+            }
+        }
+    });
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        let event = receiver.recv().await?;
+        Some((Ok::<Event, Infallible>(event), receiver))
+    });
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+// collect the segments one after the other, sending the hits of each segment entering the top
+// hits so far, and stop as soon as the client disconnects
+fn collect(
+    searcher: &Searcher,
+    index_name: &str,
+    query: &dyn Query,
+    top_k: usize,
+    sender: &mpsc::Sender<Event>,
+    start: Instant,
+) -> tantivy::Result<Collection> {
+    let weight = query.weight(EnableScoring::enabled_from_searcher(searcher))?;
+    let top_docs = TopDocs::with_limit(top_k);
+
+    let mut top_hits: Vec<(Score, DocAddress)> = Vec::new();
+    let mut total_hits = 0;
+    let mut scanned_docs = 0;
+    for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+        let mut segment_collector = top_docs.for_segment(segment_ord as u32, segment_reader)?;
+        let alive_bitset = segment_reader.alive_bitset();
+
+        let mut scorer = weight.scorer(segment_reader, 1.0)?;
+        let mut doc = scorer.doc();
+        while doc != TERMINATED {
+            if alive_bitset.is_none_or(|alive_bitset| alive_bitset.is_alive(doc)) {
+                segment_collector.collect(doc, scorer.score());
+                total_hits += 1;
+            }
+
+            scanned_docs += 1;
+            if scanned_docs % DISCONNECT_CHECK_INTERVAL == 0 && sender.is_closed() {
+                return Ok(Collection::Disconnected { scanned_docs });
+            }
+
+            doc = scorer.advance();
+        }
+
+        // keep the hits of the segment entering the top hits so far
+        let segment_hits = segment_collector.harvest();
+        top_hits.extend(segment_hits.iter().copied());
+        top_hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        top_hits.truncate(top_k);
+        let new_hits: Vec<SearchHit> = segment_hits
+            .into_iter()
+            .filter(|hit| top_hits.contains(hit))
+            .map(|(score, doc_address)| search_hit(searcher, index_name, score, doc_address))
+            .collect::<tantivy::Result<_>>()?;
+
+        if !new_hits.is_empty() && sender.blocking_send(json_event("hits", &new_hits)).is_err() {
+            return Ok(Collection::Disconnected { scanned_docs });
+        }
+    }
+
+    Ok(Collection::Done(LiveSearchDone {
+        total_hits,
+        segments: searcher.segment_readers().len(),
+        elapsed_ms: start.elapsed().as_millis(),
+    }))
+}
+
+// hit of a document, without snippet
+fn search_hit(
+    searcher: &Searcher,
+    index_name: &str,
+    score: Score,
+    doc_address: DocAddress,
+) -> tantivy::Result<SearchHit> {
+    let schema = searcher.schema();
+    let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+    let text = |field_name: &str| {
+        schema
+            .get_field(field_name)
+            .ok()
+            .and_then(|field| retrieved_doc.get_first(field))
+            .and_then(|value| value.as_str())
+            .unwrap_or("Unknown")
+            .to_string()
+    };
+
+    Ok(SearchHit {
+        title: text("title"),
+        content: text("body"),
+        score: score as f64,
+        index: index_name.to_string(),
+        snippet: None,
+        snippet_offsets: None,
+        duplicate_count: None,
+        quality: None,
+        tags: Vec::new(),
+        created_at: None,
+        explanation: None,
+    })
+}
+
+// event named `name` with `data` as JSON
+fn json_event<T: Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+}
@@ -0,0 +1,57 @@
+//! Logging of the server, either as human-readable text or as one JSON object per line, for log
+//! aggregators.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::Level;
+
+/// Format of the log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the events as keys
+    Json,
+}
+
+/// Minimum level of the logged events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<LogLevel> for Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => Level::TRACE,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Error => Level::ERROR,
+        }
+    }
+}
+
+/// Install the global subscriber, logging the events of `level` and above in `format`.
+pub(crate) fn init(format: LogFormat, level: LogLevel) {
+    let builder = tracing_subscriber::fmt()
+        .with_target(false)
+        .with_level(true)
+        .with_file(true)
+        .with_line_number(true)
+        .with_thread_ids(true)
+        .with_max_level(Level::from(level));
+
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
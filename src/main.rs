@@ -1,39 +1,245 @@
+mod access_log;
+mod aliases;
+mod analyze;
+mod archive;
+mod auth;
+mod byte_range;
+mod compare;
+mod cors;
+mod deadline;
+mod distinct;
+mod durability;
 mod error;
+mod explain;
+mod field_stats;
+mod health;
+mod html;
+mod index_check;
+mod index_list;
+mod index_meta;
+mod index_stats;
+mod jobs;
+mod keyword_search;
+mod live_search;
+mod logging;
+mod memory_budget;
+mod merge;
+mod multi_index;
+mod ndjson;
+mod originals;
+mod pdf;
+mod plain_text;
+mod preprocess;
+mod query_fields;
+mod ranking;
+mod rate_limit;
+mod reader_cache;
+mod request_id;
+mod restore;
+mod resumable_upload;
+mod search_executor;
+mod shutdown;
+mod similar;
+mod snippet;
+mod suggest;
+mod tabular;
+mod tags;
+#[cfg(test)]
+mod test_support;
+mod token_length;
+mod unique_key;
+mod upload;
 
+use access_log::RequestOutcome;
 use axum::extract::Path;
 use axum::response::IntoResponse;
 use axum::{
-    extract::{FromRequest, Multipart},
-    routing::{get, post},
+    extract::{DefaultBodyLimit, FromRequest, Multipart},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use clap::{ArgGroup, Parser};
-use endpoints::keyword_search::{
-    DocumentInput, DocumentResult, IndexRequest, IndexResponse, QueryRequest, QueryResponse,
-    SearchHit,
-};
+use deadline::Deadline;
+use durability::Durability;
 use error::ServerError;
+use field_stats::FieldStatsCollector;
+use futures_util::StreamExt;
 use http::status::StatusCode;
+use index_meta::{IndexMetadata, IndexProvenance};
+use keyword_search::{
+    DedupBy, DocumentInput, DocumentResult, FallbackStrategy, IndexRequest, IndexResponse,
+    PiiPattern, Preprocessing, QueryRequest, QueryResponse, SearchHit, SnippetMode, TextTokenizer,
+};
+use logging::{LogFormat, LogLevel};
 use once_cell::sync::OnceCell;
+use ranking::Ranking;
+use sha2::{Digest, Sha256};
 use std::{
-    fs::File,
-    io::Read,
+    collections::{BTreeMap, HashMap, HashSet},
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
 };
-use tantivy::{collector::TopDocs, doc, query::QueryParser, schema::*, Index, ReloadPolicy};
-use tracing::{debug, error, info, warn, Level};
+use tantivy::{
+    collector::Count,
+    doc,
+    query::{BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, Query, QueryParser, TermQuery},
+    schema::*,
+    snippet::SnippetGenerator,
+    Index, Score, Term,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Semaphore;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, info, warn};
 use url::Url;
 
 // default port of Keyword Search Server
 const DEFAULT_PORT: &str = "12306";
 
-const MEMORY_BUDGET_IN_BYTES: usize = 100_000_000;
+// default directory storing the indexes, relative to the working directory
+const DEFAULT_INDEX_STORAGE_DIR: &str = "index_storage";
+
+// default maximum number of characters scanned while generating a snippet
+const DEFAULT_SNIPPET_MAX_SCAN_CHARS: &str = "10000";
+
+// default maximum number of indexes kept open by the reader cache
+const DEFAULT_READER_CACHE_SIZE: &str = "64";
+
+// default maximum number of hits of a search
+const DEFAULT_MAX_TOP_K: &str = "1000";
+
+// default number of threads collecting the segments of a search
+const DEFAULT_SEARCH_EXECUTOR_THREADS: &str = "1";
+
+// default duration above which a search is logged as slow, in milliseconds
+const DEFAULT_SLOW_QUERY_MS: &str = "1000";
+
+// default maximum number of concurrent index downloads
+const DEFAULT_MAX_CONCURRENT_DOWNLOADS: &str = "4";
+
+// number of seconds clients are asked to wait when too many downloads are in progress
+const DOWNLOAD_RETRY_AFTER_SECS: u64 = 5;
+
+// maximum number of decimal places scores can be rounded to
+const MAX_SCORE_PRECISION: u32 = 10;
+
+// fields searched by queries that do not reference a field
+const DEFAULT_SEARCH_FIELDS: [&str; 2] = ["title", "body"];
+
+// maximum number of characters of a snippet highlight tag
+const MAX_HIGHLIGHT_TAG_CHARS: usize = 32;
+
+// number of candidates collected per requested hit when deduplicating search results
+const DEDUP_CANDIDATES_FACTOR: usize = 4;
+
+// maximum number of hits skipped by a search, as all of them are collected
+const MAX_SEARCH_OFFSET: usize = 10_000;
+
+// maximum number of characters of the name given to a new index
+const MAX_INDEX_NAME_CHARS: usize = 64;
 
-const INDEX_STORAGE_DIR: &str = "index_storage";
+// names of the `/v1/index/...` routes, which shadow `DELETE /v1/index/{index_name}` for an index
+// named after them
+const RESERVED_INDEX_NAMES: [&str; 3] = ["create", "upload", "compare"];
+
+// maximum number of typos per word of a fuzzy search
+const MAX_FUZZY_DISTANCE: u8 = 2;
+
+// default maximum number of fields in a multipart request
+const DEFAULT_MAX_MULTIPART_FIELDS: &str = "1000";
+
+// default maximum number of fields of a document
+const DEFAULT_MAX_FIELDS_PER_DOCUMENT: &str = "64";
+
+// default maximum size in bytes of the body of an indexing request
+const DEFAULT_MAX_UPLOAD_BYTES: &str = "50000000";
 
 // socket address
 pub(crate) static DOWNLOAD_URL_PREFIX: OnceCell<Url> = OnceCell::new();
 
+// scheme of the download URLs, overriding the one of the download URL prefix
+pub(crate) static DOWNLOAD_URL_SCHEME: OnceCell<String> = OnceCell::new();
+
+// socket address the server is bound to
+pub(crate) static SOCKET_ADDRESS: OnceCell<SocketAddr> = OnceCell::new();
+
+// maximum number of characters scanned while generating a snippet
+pub(crate) static SNIPPET_MAX_SCAN_CHARS: OnceCell<usize> = OnceCell::new();
+
+// hosts allowed as targets of indexing callbacks
+pub(crate) static CALLBACK_ALLOWED_HOSTS: OnceCell<Vec<String>> = OnceCell::new();
+
+// maximum number of fields in a multipart request
+pub(crate) static MAX_MULTIPART_FIELDS: OnceCell<usize> = OnceCell::new();
+
+// maximum size in bytes of the body of an indexing request
+pub(crate) static MAX_UPLOAD_BYTES: OnceCell<usize> = OnceCell::new();
+
+// maximum number of indexes kept open by the reader cache
+pub(crate) static READER_CACHE_SIZE: OnceCell<usize> = OnceCell::new();
+
+// number of hits of the searches without `top_k`, if the index has no default
+pub(crate) static DEFAULT_TOP_K: OnceCell<usize> = OnceCell::new();
+
+// maximum number of hits of a search
+pub(crate) static MAX_TOP_K: OnceCell<usize> = OnceCell::new();
+
+// maximum number of concurrent index downloads
+pub(crate) static MAX_CONCURRENT_DOWNLOADS: OnceCell<usize> = OnceCell::new();
+
+// semaphore limiting the number of concurrent index downloads
+pub(crate) static DOWNLOAD_SEMAPHORE: OnceCell<Arc<Semaphore>> = OnceCell::new();
+
+// whether to keep the directories of indexes that failed to be created
+pub(crate) static KEEP_FAILED_INDEXES: OnceCell<bool> = OnceCell::new();
+
+// whether to normalize line endings of documents before indexing
+pub(crate) static NORMALIZE_LINE_ENDINGS: OnceCell<bool> = OnceCell::new();
+
+// maximum time budget of a search
+pub(crate) static MAX_SEARCH_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+
+// duration above which a search is logged as slow
+pub(crate) static SLOW_QUERY_THRESHOLD: OnceCell<Duration> = OnceCell::new();
+
+// format of the log lines
+pub(crate) static LOG_FORMAT: OnceCell<LogFormat> = OnceCell::new();
+
+// minimum level of the logged events
+pub(crate) static LOG_LEVEL: OnceCell<LogLevel> = OnceCell::new();
+
+// durability of the commits of new indexes
+pub(crate) static DURABILITY: OnceCell<Durability> = OnceCell::new();
+
+// whether references to unknown fields in queries are searched as plain words
+pub(crate) static UNKNOWN_FIELDS_AS_TEXT: OnceCell<bool> = OnceCell::new();
+
+// whether a multipart request whose fields cannot all be read is aborted without creating an index
+pub(crate) static ATOMIC_MULTIPART: OnceCell<bool> = OnceCell::new();
+
+// maximum number of fields of a document
+pub(crate) static MAX_FIELDS_PER_DOCUMENT: OnceCell<usize> = OnceCell::new();
+
+// maximum number of characters of the content of a document, if any
+pub(crate) static MAX_CONTENT_CHARS: OnceCell<usize> = OnceCell::new();
+
+// preprocessing of the documents of the requests that do not set their own
+pub(crate) static PREPROCESSING: OnceCell<Preprocessing> = OnceCell::new();
+
+// minimum length of the tokens of new indexes
+pub(crate) static MIN_TOKEN_LENGTH: OnceCell<usize> = OnceCell::new();
+
+// whether to keep the original uploaded files with the index
+pub(crate) static STORE_ORIGINALS: OnceCell<bool> = OnceCell::new();
+
+// index searched by the requests that do not name one
+pub(crate) static DEFAULT_INDEX: OnceCell<String> = OnceCell::new();
+
+// absolute path of the directory storing the indexes
+pub(crate) static INDEX_STORAGE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
 /// Command line arguments configuration
 #[derive(Debug, Parser)]
 #[command(name = "Keyword Search Server", version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"), about = "Keyword Search Server")]
@@ -42,916 +248,4879 @@ struct Cli {
     /// Download URL prefix, format: `http(s)://{IPv4_address}:{port}` or `http(s)://{domain}:{port}`
     #[arg(long)]
     download_url_prefix: Option<String>,
+    /// Scheme of the generated download URLs, e.g. `https` behind a TLS-terminating proxy while the server itself serves HTTP. Defaults to the scheme of the download URL prefix.
+    #[arg(long, value_parser = ["http", "https"])]
+    download_url_scheme: Option<String>,
     /// Socket address of llama-proxy-server instance. For example, `0.0.0.0:12306`.
     #[arg(long, default_value = None, value_parser = clap::value_parser!(SocketAddr), group = "socket_address_group")]
     socket_addr: Option<SocketAddr>,
     /// Socket address of llama-proxy-server instance
     #[arg(long, default_value = DEFAULT_PORT, value_parser = clap::value_parser!(u16), group = "socket_address_group")]
     port: u16,
+    /// Maximum number of characters of a document body scanned while generating a snippet. Matches beyond this length are not considered for the snippet, which trades completeness for speed on huge documents.
+    #[arg(long, default_value = DEFAULT_SNIPPET_MAX_SCAN_CHARS, value_parser = clap::value_parser!(usize))]
+    snippet_max_scan_chars: usize,
+    /// Comma-separated list of hosts allowed as `callback_url` targets of indexing requests. Callbacks are rejected if not set.
+    #[arg(long, value_delimiter = ',')]
+    callback_allowed_hosts: Vec<String>,
+    /// Origins of the browser apps allowed to call the server: `*` for any origin, or a comma-separated list of origins, e.g. `https://app.example.com`. Without it, no CORS headers are sent.
+    #[arg(long, value_delimiter = ',')]
+    cors_origin: Vec<String>,
+    /// API key required by the `/v1/*` endpoints, except the health and readiness probes, sent in an `Authorization: Bearer <key>` or an `X-API-Key` header. Without it, the server is open.
+    #[arg(long)]
+    api_key: Option<String>,
+    /// Maximum number of fields in a multipart indexing request. Fields beyond this limit are not processed.
+    #[arg(long, default_value = DEFAULT_MAX_MULTIPART_FIELDS, value_parser = clap::value_parser!(usize))]
+    max_multipart_fields: usize,
+    /// Maximum size in bytes of the body of an indexing request, JSON or multipart. Larger requests are rejected with `413 Payload Too Large`.
+    #[arg(long, default_value = DEFAULT_MAX_UPLOAD_BYTES, value_parser = clap::value_parser!(usize))]
+    max_upload_bytes: usize,
+    /// Normalize `\r\n` and `\r` line endings of documents to `\n` before indexing
+    #[arg(long)]
+    normalize_line_endings: bool,
+    /// Maximum number of indexes kept open for searching. The least-recently-used index is closed when the limit is reached. `0` disables caching.
+    #[arg(long, default_value = DEFAULT_READER_CACHE_SIZE, value_parser = clap::value_parser!(usize))]
+    reader_cache_size: usize,
+    /// Keep the directory of an index whose creation failed before the first commit, for debugging. By default, such directories are removed.
+    #[arg(long)]
+    keep_failed_indexes: bool,
+    /// Maximum number of concurrent index downloads, at least 1. Further download requests are rejected with `503 Service Unavailable`.
+    #[arg(long, default_value = DEFAULT_MAX_CONCURRENT_DOWNLOADS, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_concurrent_downloads: usize,
+    /// Compression level of the downloaded index archives, from 1 (fastest) to 9 (smallest), for both gzip and zstd. By default, each codec uses its default level.
+    #[arg(long, value_parser = clap::value_parser!(u32))]
+    compression_level: Option<u32>,
+    /// Maximum number of fields of a document, counting its content, title, quality, tags and creation date, and each of its named `fields`. Documents exceeding it are rejected with a per-document error before being added to the index.
+    #[arg(long, default_value = DEFAULT_MAX_FIELDS_PER_DOCUMENT, value_parser = clap::value_parser!(usize))]
+    max_fields_per_document: usize,
+    /// Maximum number of characters of the content of a document, after preprocessing. Longer documents are rejected with a per-document error before being added to the index. Unbounded by default.
+    #[arg(long, value_parser = clap::value_parser!(usize))]
+    max_content_chars: Option<usize>,
+    /// Search references to fields missing from the index, e.g. `author:smith`, as plain words in the default fields instead of rejecting the query with `400 Bad Request`
+    #[arg(long)]
+    unknown_fields_as_text: bool,
+    /// Durability of the commits of new indexes. `relaxed` does not sync the files of a commit to disk, so the indexes committed in the last seconds before an operating system crash or a power loss may be lost.
+    #[arg(long, value_enum, default_value_t = Durability::Strict)]
+    durability: Durability,
+    /// Abort a multipart indexing request without creating an index if any of its fields cannot be read, e.g. because the connection dropped mid-upload. By default, the fields read successfully are indexed.
+    #[arg(long)]
+    atomic_multipart: bool,
+    /// Keep the raw bytes of each file uploaded to create an index in the `originals` directory of the index, to retrieve them via `GET /v1/index/{index_name}/originals/{filename}`
+    #[arg(long)]
+    store_originals: bool,
+    /// Maximum number of requests per minute of each client, identified by its IP address, in bursts of at most as many requests. Requests beyond it are rejected with `429 Too Many Requests`, and every response carries the `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers. Requests are not limited if not set.
+    #[arg(long, value_parser = clap::value_parser!(u32))]
+    rate_limit_per_minute: Option<u32>,
+    /// Number of threads collecting the segments of large indexes in parallel, to reduce the latency of searches on indexes with many segments. Indexes with fewer than 100000 documents or a single segment are searched on a single thread. `1` disables parallel collection.
+    #[arg(long, default_value = DEFAULT_SEARCH_EXECUTOR_THREADS, value_parser = clap::value_parser!(usize))]
+    search_executor_threads: usize,
+    /// Index, or alias, searched by the search requests that do not name one. Without it, such requests search the only index of the server, and are rejected if there are several.
+    #[arg(long)]
+    default_index: Option<String>,
+    /// Memory budget in bytes of each index writer. A larger budget speeds up large indexing requests, a smaller one reduces the footprint of concurrent ones. It must be at least 15000000 bytes, the minimum required by tantivy.
+    #[arg(long, default_value_t = memory_budget::DEFAULT_WRITER_MEMORY_BYTES, value_parser = clap::value_parser!(usize))]
+    writer_memory_bytes: usize,
+    /// Total memory budget in bytes of the index writers, divided among the writers running concurrently. Each writer gets an equal share of it, at most `--writer-memory-bytes` and at least 15000000 bytes, and waits for other writers to finish if less than that is left. By default, each writer gets `--writer-memory-bytes`.
+    #[arg(long, value_parser = clap::value_parser!(usize))]
+    total_index_memory: Option<usize>,
+    /// Comma-separated preprocessing steps applied to the content of the documents before indexing, unless a request sets its own `preprocessing`. `cleanup` removes control and zero-width characters, `strip_html` removes HTML markup, `strip_markdown` removes Markdown syntax, and `normalize_whitespace` collapses runs of whitespace.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    preprocess: Vec<preprocess::PreprocessStep>,
+    /// Comma-separated built-in patterns of personal data replaced with `[REDACTED]` before indexing, unless a request sets its own `preprocessing`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    redact: Vec<PiiPattern>,
+    /// Regular expression whose matches are replaced with `[REDACTED]` before indexing, unless a request sets its own `preprocessing`. Can be repeated.
+    #[arg(long)]
+    redact_regex: Vec<String>,
+    /// Minimum length in characters of the tokens of the `title` and `body` fields of new indexes. Shorter tokens, e.g. `a` or `I`, are neither indexed nor matched by queries, which reduces the size of the index. The length is stored with each index, so it keeps applying to existing indexes if changed. `1` keeps all tokens.
+    #[arg(long, default_value_t = token_length::DEFAULT_MIN_TOKEN_LENGTH, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    min_token_length: usize,
+    /// Maximum time budget of a search in milliseconds. Searches running out of time return the hits collected so far with `timed_out: true`. It applies to the searches without a `timeout_ms`, and bounds the `timeout_ms` of the others. Searches are not bounded if not set.
+    #[arg(long, value_parser = clap::value_parser!(u64))]
+    max_search_timeout_ms: Option<u64>,
+    /// Number of hits returned by the searches without `top_k`, unless the searched index has a default of its own
+    #[arg(long, default_value_t = QueryRequest::DEFAULT_TOP_K, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    default_top_k: usize,
+    /// Maximum number of hits of a search. Larger `top_k` values are lowered to it, with a warning in the response.
+    #[arg(long, default_value = DEFAULT_MAX_TOP_K, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_top_k: usize,
+    /// Duration in milliseconds above which a search is logged as slow, with its query and total number of matching documents. `0` disables the slow-query log.
+    #[arg(long, default_value = DEFAULT_SLOW_QUERY_MS, value_parser = clap::value_parser!(u64))]
+    slow_query_ms: u64,
+    /// Number of seconds after its last chunk an unfinished resumable upload is removed, along with the bytes received so far
+    #[arg(long, default_value = resumable_upload::DEFAULT_UPLOAD_EXPIRY_SECS, value_parser = clap::value_parser!(u64).range(1..))]
+    upload_expiry_secs: u64,
+    /// Directory storing the indexes, along with their archives and the alias registry. A relative path is resolved against the working directory. The directory is created at startup if missing.
+    #[arg(long, default_value = DEFAULT_INDEX_STORAGE_DIR)]
+    index_storage_dir: PathBuf,
+    /// Format of the log lines. `json` writes one JSON object per line, for log aggregators.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Minimum level of the logged events
+    #[arg(long, value_enum, default_value_t = LogLevel::Info)]
+    log_level: LogLevel,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), ServerError> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_level(true)
-        .with_file(true)
-        .with_line_number(true)
-        .with_thread_ids(true)
-        .with_max_level(Level::INFO)
-        .init();
-
     // Parse command line arguments
     let cli = Cli::parse();
 
-    // Build application routes
-    let app = Router::new()
-        .route("/v1/index/create", post(index_document_handler))
-        .route("/v1/search", post(query_handler))
-        .route(
-            "/v1/index/download/{index_name}",
-            get(download_index_file_handler),
-        );
+    // Initialize logging
+    logging::init(cli.log_format, cli.log_level);
 
-    // Run the server
-    let addr = match cli.socket_addr {
-        Some(addr) => addr,
-        None => SocketAddr::from(([0, 0, 0, 0], cli.port)),
-    };
+    // set LOG_FORMAT and LOG_LEVEL
+    info!(target: "stdout", "log_format: {:?}", cli.log_format);
+    if let Err(e) = LOG_FORMAT.set(cli.log_format) {
+        let err_msg = format!("Failed to set LOG_FORMAT: {e:?}");
 
-    // set DOWNLOAD_URL_PREFIX
-    match cli.download_url_prefix {
-        Some(download_url_prefix) => {
-            info!(target: "stdout", "download_url_prefix: {}", &download_url_prefix);
+        error!(target: "stdout", "{}", &err_msg);
 
-            // download url prefix
-            info!(target: "stdout", "download_url_prefix: {}", &download_url_prefix);
-            let download_url_prefix = Url::parse(&download_url_prefix).map_err(|e| {
-                ServerError::ArgumentError(format!(
-                    "Failed to parse `download_url_prefix` CLI option. Reason: {e}",
-                ))
-            })?;
-            if let Err(e) = DOWNLOAD_URL_PREFIX.set(download_url_prefix) {
-                let err_msg = format!("Failed to set DOWNLOAD_URL_PREFIX: {e}");
+        return Err(ServerError::Operation(err_msg));
+    }
+    info!(target: "stdout", "log_level: {:?}", cli.log_level);
+    if let Err(e) = LOG_LEVEL.set(cli.log_level) {
+        let err_msg = format!("Failed to set LOG_LEVEL: {e:?}");
 
-                error!(target: "stdout", "{}", &err_msg);
+        error!(target: "stdout", "{}", &err_msg);
 
-                return Err(ServerError::Operation(err_msg));
-            }
-        }
-        None => {
-            match addr.ip() {
-                IpAddr::V4(ip) => match ip.to_string().as_str() {
-                    "0.0.0.0" => {
-                        let ipv4_addr_str = format!("http://localhost:{}", addr.port());
+        return Err(ServerError::Operation(err_msg));
+    }
 
-                        info!(target: "stdout", "download_url_prefix: {}", ipv4_addr_str);
+    // set INDEX_STORAGE_DIR, resolved once against the working directory, creating the
+    // directory if missing
+    let index_storage_dir = match std::path::absolute(&cli.index_storage_dir) {
+        Ok(index_storage_dir) => index_storage_dir,
+        Err(e) => {
+            let err_msg = format!(
+                "Failed to resolve the index storage directory {}: {e}",
+                cli.index_storage_dir.display()
+            );
 
-                        let download_url_prefix = Url::parse(&ipv4_addr_str).map_err(|e| {
-                            ServerError::Operation(format!(
-                                "Failed to parse `download_url_prefix` CLI option: {e}",
-                            ))
-                        })?;
-                        if let Err(e) = DOWNLOAD_URL_PREFIX.set(download_url_prefix) {
-                            let err_msg = format!("Failed to set SOCKET_ADDRESS: {e}");
+            error!(target: "stdout", "{}", &err_msg);
 
-                            error!(target: "stdout", "{}", &err_msg);
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    info!(target: "stdout", "index_storage_dir: {}", index_storage_dir.display());
+    if let Err(e) = std::fs::create_dir_all(&index_storage_dir) {
+        let err_msg = format!(
+            "Failed to create the index storage directory {}: {e}",
+            index_storage_dir.display()
+        );
 
-                            return Err(ServerError::Operation(err_msg));
-                        }
-                    }
-                    _ => {
-                        let ipv4_addr_str = format!("http://{}:{}", addr.ip(), addr.port());
+        error!(target: "stdout", "{}", &err_msg);
 
-                        info!(target: "stdout", "download_url_prefix: {}", ipv4_addr_str);
+        return Err(ServerError::Operation(err_msg));
+    }
+    if let Err(e) = INDEX_STORAGE_DIR.set(index_storage_dir) {
+        let err_msg = format!("Failed to set INDEX_STORAGE_DIR: {e:?}");
 
-                        let download_url_prefix = Url::parse(&ipv4_addr_str).map_err(|e| {
-                            ServerError::Operation(format!(
-                                "Failed to parse `download_url_prefix` CLI option: {e}",
-                            ))
-                        })?;
-                        if let Err(e) = DOWNLOAD_URL_PREFIX.set(download_url_prefix) {
-                            let err_msg = format!("Failed to set SOCKET_ADDRESS: {e}");
+        error!(target: "stdout", "{}", &err_msg);
 
-                            error!(target: "stdout", "{}", &err_msg);
+        return Err(ServerError::Operation(err_msg));
+    }
 
-                            return Err(ServerError::Operation(err_msg));
-                        }
-                    }
-                },
-                IpAddr::V6(_) => {
-                    let err_msg = "ipv6 is not supported";
+    // set SNIPPET_MAX_SCAN_CHARS
+    info!(target: "stdout", "snippet_max_scan_chars: {}", cli.snippet_max_scan_chars);
+    if let Err(e) = SNIPPET_MAX_SCAN_CHARS.set(cli.snippet_max_scan_chars) {
+        let err_msg = format!("Failed to set SNIPPET_MAX_SCAN_CHARS: {e}");
 
-                    // log error
-                    error!(target: "stdout", "{}", err_msg);
+        error!(target: "stdout", "{}", &err_msg);
 
-                    // return error
-                    return Err(ServerError::Operation(err_msg.into()));
-                }
-            }
-        }
+        return Err(ServerError::Operation(err_msg));
     }
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    info!("Server running at http://{}", addr);
+    // set CALLBACK_ALLOWED_HOSTS
+    info!(target: "stdout", "callback_allowed_hosts: {:?}", &cli.callback_allowed_hosts);
+    if let Err(e) = CALLBACK_ALLOWED_HOSTS.set(cli.callback_allowed_hosts) {
+        let err_msg = format!("Failed to set CALLBACK_ALLOWED_HOSTS: {e:?}");
 
-    info!("Starting to accept connections...");
-    match axum::serve(listener, app).await {
-        Ok(_) => Ok(()),
-        Err(e) => Err(ServerError::Operation(e.to_string())),
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
     }
-}
 
-// Main handler that routes to appropriate processing function based on content type
-async fn index_document_handler(
-    content_type: axum::http::header::HeaderMap,
-    request: axum::extract::Request,
-) -> Json<IndexResponse> {
-    let content_type = content_type
-        .get(axum::http::header::CONTENT_TYPE)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
+    // set the origins allowed to call the server
+    if !cli.cors_origin.is_empty() {
+        info!(target: "stdout", "cors_origin: {:?}", &cli.cors_origin);
+        if let Err(e) = cors::init(cli.cors_origin) {
+            let err_msg = format!("Invalid `cors_origin` CLI option: {e}");
 
-    info!("Received document indexing request");
+            error!(target: "stdout", "{}", &err_msg);
 
-    let response = match content_type {
-        t if t.starts_with("multipart/form-data") => {
-            info!("Processing as multipart/form-data");
-            let multipart = match Multipart::from_request(request, &()).await {
-                Ok(m) => m,
-                Err(e) => {
-                    error!(error = %e, "Failed to parse multipart request");
-                    return Json(IndexResponse {
-                        results: vec![DocumentResult {
-                            filename: None,
-                            status: "failed".to_string(),
-                            error: Some("Failed to parse multipart request".to_string()),
-                        }],
-                        index_name: None,
-                        download_url: None,
-                    });
-                }
-            };
-            process_multipart(multipart).await
-        }
-        "application/json" => {
-            info!("Processing as JSON request");
-            let index_request = match axum::Json::<IndexRequest>::from_request(request, &()).await {
-                Ok(Json(payload)) => payload,
-                Err(e) => {
-                    error!(error = %e, "Failed to parse JSON request");
-                    return Json(IndexResponse {
-                        results: vec![DocumentResult {
-                            filename: None,
-                            status: "failed".to_string(),
-                            error: Some("Failed to parse JSON request".to_string()),
-                        }],
-                        index_name: None,
-                        download_url: None,
-                    });
-                }
-            };
-            process_json(index_request).await
-        }
-        _ => {
-            warn!(content_type = content_type, "Unsupported content type");
-            Json(IndexResponse {
-                results: vec![DocumentResult {
-                    filename: None,
-                    status: "failed".to_string(),
-                    error: Some("Unsupported content type".to_string()),
-                }],
-                index_name: None,
-                download_url: None,
-            })
+            return Err(ServerError::ArgumentError(err_msg));
         }
-    };
+    }
 
-    info!(
-        successful = response
-            .results
-            .iter()
-            .filter(|r| r.status == "indexed")
-            .count(),
-        failed = response
-            .results
-            .iter()
-            .filter(|r| r.status == "failed")
-            .count(),
-        "Request processing completed"
-    );
+    // require an API key
+    if let Some(api_key) = &cli.api_key {
+        info!(target: "stdout", "api_key: enabled");
+        if let Err(e) = auth::init(api_key) {
+            let err_msg = format!("Invalid `api_key` CLI option: {e}");
 
-    response
-}
+            error!(target: "stdout", "{}", &err_msg);
 
-// Process multipart form data
-async fn process_multipart(mut multipart: Multipart) -> Json<IndexResponse> {
-    info!("Starting multipart form data processing");
-    let mut results = Vec::new();
-    let mut field_count = 0;
-    let mut documents = Vec::new();
-    let mut index_name: Option<String> = None;
+            return Err(ServerError::ArgumentError(err_msg));
+        }
+    }
 
-    while let Ok(Some(field)) = multipart.next_field().await {
-        field_count += 1;
-        let field_name = field.name().unwrap_or("unknown").to_string();
-        let filename = field.file_name().map(ToString::to_string);
+    // set MAX_MULTIPART_FIELDS
+    info!(target: "stdout", "max_multipart_fields: {}", cli.max_multipart_fields);
+    if let Err(e) = MAX_MULTIPART_FIELDS.set(cli.max_multipart_fields) {
+        let err_msg = format!("Failed to set MAX_MULTIPART_FIELDS: {e}");
 
-        let content_type = field
-            .content_type()
-            .map(|ct| ct.to_string())
-            .unwrap_or_else(|| "application/octet-stream".to_string());
+        error!(target: "stdout", "{}", &err_msg);
 
-        info!(
-            field_number = field_count,
-            field_name = %field_name,
-            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-            content_type = %content_type,
-            "Processing field"
-        );
+        return Err(ServerError::Operation(err_msg));
+    }
 
-        // Handle index field (text type)
-        if field_name == "index" {
-            match field.text().await {
-                Ok(text) => {
-                    info!("Processing index field");
-                    // Store index name for later use
-                    index_name = Some(text.trim_matches('"').to_string());
-                    continue;
-                }
-                Err(e) => {
-                    error!(error = %e, "Failed to read index field");
-                    results.push(DocumentResult {
-                        filename: None,
-                        status: "failed".to_string(),
-                        error: Some(format!("Failed to read index field: {e}")),
-                    });
-                    continue;
-                }
-            }
-        }
+    // set MAX_UPLOAD_BYTES
+    info!(target: "stdout", "max_upload_bytes: {}", cli.max_upload_bytes);
+    if let Err(e) = MAX_UPLOAD_BYTES.set(cli.max_upload_bytes) {
+        let err_msg = format!("Failed to set MAX_UPLOAD_BYTES: {e}");
 
-        let is_file = is_file_field(&field);
-        if is_file {
-            // Handle file fields
-            if !is_valid_content_type(&content_type) {
-                warn!(
-                    field_number = field_count,
-                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                    content_type = %content_type,
-                    "Unsupported file type"
-                );
-                results.push(DocumentResult {
-                    filename,
-                    status: "failed".to_string(),
-                    error: Some(
-                        "Unsupported file type. Only .txt and .md files are allowed".to_string(),
-                    ),
-                });
-                continue;
-            }
-        }
+        error!(target: "stdout", "{}", &err_msg);
 
-        process_field_content(&mut results, &mut documents, field, filename).await;
+        return Err(ServerError::Operation(err_msg));
     }
 
-    info!(
-        total_fields = field_count,
-        successful = results.iter().filter(|r| r.status == "indexed").count(),
-        failed = results.iter().filter(|r| r.status == "failed").count(),
-        "Field processing completed"
-    );
+    // set NORMALIZE_LINE_ENDINGS
+    info!(target: "stdout", "normalize_line_endings: {}", cli.normalize_line_endings);
+    if let Err(e) = NORMALIZE_LINE_ENDINGS.set(cli.normalize_line_endings) {
+        let err_msg = format!("Failed to set NORMALIZE_LINE_ENDINGS: {e}");
 
-    // Create index directory
-    info!("Starting index creation");
-    let index_storage_dir = std::env::current_dir().unwrap().join(INDEX_STORAGE_DIR);
-    let index_name = index_name.unwrap_or_else(|| format!("index-{}", uuid::Uuid::new_v4()));
-    let index_path = index_storage_dir.as_path().join(&index_name);
-    if !index_path.exists() {
-        debug!(path = %index_path.display(), "Creating index directory");
-        std::fs::create_dir_all(&index_path).unwrap();
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
     }
 
-    // Define schema
-    info!("Defining index schema");
-    let mut schema_builder = Schema::builder();
-    let title = schema_builder.add_text_field("title", TEXT | STORED);
-    let body = schema_builder.add_text_field("body", TEXT | STORED);
-    let schema = schema_builder.build();
+    // set READER_CACHE_SIZE
+    info!(target: "stdout", "reader_cache_size: {}", cli.reader_cache_size);
+    if let Err(e) = READER_CACHE_SIZE.set(cli.reader_cache_size) {
+        let err_msg = format!("Failed to set READER_CACHE_SIZE: {e}");
 
-    // Create index
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set KEEP_FAILED_INDEXES
+    info!(target: "stdout", "keep_failed_indexes: {}", cli.keep_failed_indexes);
+    if let Err(e) = KEEP_FAILED_INDEXES.set(cli.keep_failed_indexes) {
+        let err_msg = format!("Failed to set KEEP_FAILED_INDEXES: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set MAX_CONCURRENT_DOWNLOADS and DOWNLOAD_SEMAPHORE
+    info!(target: "stdout", "max_concurrent_downloads: {}", cli.max_concurrent_downloads);
+    if let Err(e) = MAX_CONCURRENT_DOWNLOADS.set(cli.max_concurrent_downloads) {
+        let err_msg = format!("Failed to set MAX_CONCURRENT_DOWNLOADS: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+    if DOWNLOAD_SEMAPHORE
+        .set(Arc::new(Semaphore::new(cli.max_concurrent_downloads)))
+        .is_err()
+    {
+        let err_msg = "Failed to set DOWNLOAD_SEMAPHORE";
+
+        error!(target: "stdout", "{}", err_msg);
+
+        return Err(ServerError::Operation(err_msg.into()));
+    }
+
+    // set the compression level of the index archives
+    if let Some(compression_level) = cli.compression_level {
+        info!(target: "stdout", "compression_level: {}", compression_level);
+        if let Err(e) = archive::init_compression_level(compression_level) {
+            let err_msg = format!("Invalid `compression_level` CLI option: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::ArgumentError(err_msg));
+        }
+    }
+
+    // set MAX_FIELDS_PER_DOCUMENT
+    info!(target: "stdout", "max_fields_per_document: {}", cli.max_fields_per_document);
+    if let Err(e) = MAX_FIELDS_PER_DOCUMENT.set(cli.max_fields_per_document) {
+        let err_msg = format!("Failed to set MAX_FIELDS_PER_DOCUMENT: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set MAX_CONTENT_CHARS
+    if let Some(max_content_chars) = cli.max_content_chars {
+        info!(target: "stdout", "max_content_chars: {}", max_content_chars);
+
+        if max_content_chars == 0 {
+            let err_msg = "The maximum content length must be greater than 0".to_string();
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::ArgumentError(err_msg));
+        }
+
+        if let Err(e) = MAX_CONTENT_CHARS.set(max_content_chars) {
+            let err_msg = format!("Failed to set MAX_CONTENT_CHARS: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    // set UNKNOWN_FIELDS_AS_TEXT
+    info!(target: "stdout", "unknown_fields_as_text: {}", cli.unknown_fields_as_text);
+    if let Err(e) = UNKNOWN_FIELDS_AS_TEXT.set(cli.unknown_fields_as_text) {
+        let err_msg = format!("Failed to set UNKNOWN_FIELDS_AS_TEXT: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set ATOMIC_MULTIPART
+    info!(target: "stdout", "atomic_multipart: {}", cli.atomic_multipart);
+    if let Err(e) = ATOMIC_MULTIPART.set(cli.atomic_multipart) {
+        let err_msg = format!("Failed to set ATOMIC_MULTIPART: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set SLOW_QUERY_THRESHOLD
+    info!(target: "stdout", "slow_query_ms: {}", cli.slow_query_ms);
+    if SLOW_QUERY_THRESHOLD
+        .set(Duration::from_millis(cli.slow_query_ms))
+        .is_err()
+    {
+        let err_msg = "Failed to set SLOW_QUERY_THRESHOLD";
+
+        error!(target: "stdout", "{}", err_msg);
+
+        return Err(ServerError::Operation(err_msg.into()));
+    }
+
+    // set PREPROCESSING
+    let preprocessing = preprocess::from_options(&cli.preprocess, &cli.redact, &cli.redact_regex);
+    info!(target: "stdout", "preprocessing: {:?}", preprocessing);
+    if let Err(err_msg) = preprocess::Pipeline::new(&preprocessing) {
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+    if let Err(e) = PREPROCESSING.set(preprocessing) {
+        let err_msg = format!("Failed to set PREPROCESSING: {e:?}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set MIN_TOKEN_LENGTH
+    info!(target: "stdout", "min_token_length: {}", cli.min_token_length);
+    if let Err(e) = MIN_TOKEN_LENGTH.set(cli.min_token_length) {
+        let err_msg = format!("Failed to set MIN_TOKEN_LENGTH: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set DEFAULT_TOP_K and MAX_TOP_K
+    info!(target: "stdout", "default_top_k: {}", cli.default_top_k);
+    info!(target: "stdout", "max_top_k: {}", cli.max_top_k);
+    if cli.default_top_k > cli.max_top_k {
+        let err_msg = format!(
+            "The default top_k {} exceeds the maximum top_k {}",
+            cli.default_top_k, cli.max_top_k
+        );
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::ArgumentError(err_msg));
+    }
+    if let Err(e) = DEFAULT_TOP_K.set(cli.default_top_k) {
+        let err_msg = format!("Failed to set DEFAULT_TOP_K: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+    if let Err(e) = MAX_TOP_K.set(cli.max_top_k) {
+        let err_msg = format!("Failed to set MAX_TOP_K: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set MAX_SEARCH_TIMEOUT
+    if let Some(max_search_timeout_ms) = cli.max_search_timeout_ms {
+        info!(target: "stdout", "max_search_timeout_ms: {}", max_search_timeout_ms);
+        if MAX_SEARCH_TIMEOUT
+            .set(Duration::from_millis(max_search_timeout_ms))
+            .is_err()
+        {
+            let err_msg = "Failed to set MAX_SEARCH_TIMEOUT";
+
+            error!(target: "stdout", "{}", err_msg);
+
+            return Err(ServerError::Operation(err_msg.into()));
+        }
+    }
+
+    // set STORE_ORIGINALS
+    info!(target: "stdout", "store_originals: {}", cli.store_originals);
+    if let Err(e) = STORE_ORIGINALS.set(cli.store_originals) {
+        let err_msg = format!("Failed to set STORE_ORIGINALS: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set the rate limit of the clients
+    if let Some(rate_limit_per_minute) = cli.rate_limit_per_minute {
+        info!(target: "stdout", "rate_limit_per_minute: {}", rate_limit_per_minute);
+        if let Err(e) = rate_limit::init(rate_limit_per_minute) {
+            let err_msg = format!("Failed to set the rate limit: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    // create the thread pool of the searches
+    info!(target: "stdout", "search_executor_threads: {}", cli.search_executor_threads);
+    if let Err(e) = search_executor::init(cli.search_executor_threads) {
+        let err_msg = format!("Failed to set the search executor threads: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // remove the abandoned resumable uploads periodically
+    info!(target: "stdout", "upload_expiry_secs: {}", cli.upload_expiry_secs);
+    if let Err(e) = resumable_upload::init(Duration::from_secs(cli.upload_expiry_secs)) {
+        let err_msg = format!("Failed to set the upload expiry: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set DEFAULT_INDEX
+    if let Some(default_index) = cli.default_index {
+        info!(target: "stdout", "default_index: {}", default_index);
+        if let Err(e) = DEFAULT_INDEX.set(default_index) {
+            let err_msg = format!("Failed to set DEFAULT_INDEX: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    // set the memory budget of an index writer
+    info!(target: "stdout", "writer_memory_bytes: {}", cli.writer_memory_bytes);
+    if let Err(e) = memory_budget::init_writer_bytes(cli.writer_memory_bytes) {
+        let err_msg = format!("Invalid `writer_memory_bytes` CLI option: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::ArgumentError(err_msg));
+    }
+
+    // set the total memory budget of the index writers
+    if let Some(total_index_memory) = cli.total_index_memory {
+        info!(target: "stdout", "total_index_memory: {}", total_index_memory);
+        if let Err(e) = memory_budget::init(total_index_memory) {
+            let err_msg = format!("Failed to set the total index memory: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    // set DURABILITY
+    info!(target: "stdout", "durability: {:?}", cli.durability);
+    if let Err(e) = DURABILITY.set(cli.durability) {
+        let err_msg = format!("Failed to set DURABILITY: {e:?}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // Build application routes
+    let mut app = Router::new()
+        .route("/", get(root_handler))
+        .route("/v1/indexes", get(index_list::list_indexes_handler))
+        .route(
+            "/v1/index/create",
+            post(index_document_handler).layer(DefaultBodyLimit::max(cli.max_upload_bytes)),
+        )
+        .route(
+            "/v1/index/upload",
+            post(restore::upload_index_handler)
+                .layer(DefaultBodyLimit::max(restore::MAX_INDEX_UPLOAD_BYTES)),
+        )
+        .route(
+            "/v1/files/upload/{index_name}",
+            post(restore::restore_named_index_handler)
+                .layer(DefaultBodyLimit::max(restore::MAX_INDEX_UPLOAD_BYTES)),
+        )
+        .route("/v1/index/jobs/{job_id}", get(jobs::index_job_handler))
+        .route("/v1/index/compare", post(compare::compare_indexes_handler))
+        .route(
+            "/v1/index/{index_name}/check",
+            post(index_check::check_index_handler),
+        )
+        .route(
+            "/v1/index/{index_name}/merge",
+            post(merge::merge_index_handler),
+        )
+        .route(
+            "/v1/index/{index_name}/stats",
+            get(index_stats::index_stats_handler),
+        )
+        .route(
+            "/v1/index/{index_name}/distinct",
+            get(distinct::distinct_values_handler),
+        )
+        .route(
+            "/v1/index/{index_name}/originals/{filename}",
+            get(originals::original_file_handler),
+        )
+        .route(
+            "/v1/upload/init",
+            post(resumable_upload::init_upload_handler),
+        )
+        .route(
+            "/v1/upload/{upload_id}",
+            get(resumable_upload::upload_status_handler)
+                .patch(resumable_upload::append_chunk_handler)
+                .layer(DefaultBodyLimit::max(resumable_upload::MAX_CHUNK_BYTES)),
+        )
+        .route(
+            "/v1/upload/{upload_id}/finalize",
+            post(resumable_upload::finalize_upload_handler),
+        )
+        .route("/v1/aliases", get(aliases::list_aliases_handler))
+        .route("/v1/aliases/{alias}", put(aliases::put_alias_handler))
+        .route("/v1/search", post(query_handler))
+        .route("/v1/search/live", post(live_search::live_search_handler))
+        .route("/v1/search/similar", post(similar::similar_handler))
+        .route("/v1/suggest", get(suggest::suggest_handler))
+        .route("/v1/analyze", post(analyze::analyze_handler))
+        .route("/v1/config", get(config_handler))
+        .route("/v1/metrics", get(metrics_handler))
+        .route(
+            "/v1/index/download/{index_name}",
+            get(download_index_file_handler),
+        )
+        .route("/v1/index/{index_name}", delete(delete_index_handler))
+        .layer(axum::middleware::from_fn(auth::require_api_key))
+        // unauthorized requests are rate limited too
+        .layer(axum::middleware::from_fn(rate_limit::limit_rate))
+        // probes are neither authenticated nor rate limited
+        .route("/v1/health", get(health::health_handler))
+        .route("/v1/ready", get(health::ready_handler));
+    // allow the configured origins to call every route, and answer their preflight requests
+    if let Some(cors) = cors::layer() {
+        app = app.layer(cors);
+    }
+    // the access log line of a request carries its id too
+    let app = app
+        .layer(axum::middleware::from_fn(access_log::log_request))
+        .layer(axum::middleware::from_fn(request_id::set_request_id));
+
+    // Run the server
+    let addr = match cli.socket_addr {
+        Some(addr) => addr,
+        None => SocketAddr::from(([0, 0, 0, 0], cli.port)),
+    };
+
+    // set SOCKET_ADDRESS
+    if let Err(e) = SOCKET_ADDRESS.set(addr) {
+        let err_msg = format!("Failed to set SOCKET_ADDRESS: {e}");
+
+        error!(target: "stdout", "{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // set DOWNLOAD_URL_PREFIX
+    match cli.download_url_prefix {
+        Some(download_url_prefix) => {
+            info!(target: "stdout", "download_url_prefix: {}", &download_url_prefix);
+
+            // download url prefix
+            info!(target: "stdout", "download_url_prefix: {}", &download_url_prefix);
+            let download_url_prefix = Url::parse(&download_url_prefix).map_err(|e| {
+                ServerError::ArgumentError(format!(
+                    "Failed to parse `download_url_prefix` CLI option. Reason: {e}",
+                ))
+            })?;
+            if let Err(e) = DOWNLOAD_URL_PREFIX.set(download_url_prefix) {
+                let err_msg = format!("Failed to set DOWNLOAD_URL_PREFIX: {e}");
+
+                error!(target: "stdout", "{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        }
+        None => {
+            let default_prefix = default_download_url_prefix(addr);
+
+            info!(target: "stdout", "download_url_prefix: {}", default_prefix);
+
+            let download_url_prefix = Url::parse(&default_prefix).map_err(|e| {
+                ServerError::Operation(format!(
+                    "Failed to parse `download_url_prefix` CLI option: {e}",
+                ))
+            })?;
+            if let Err(e) = DOWNLOAD_URL_PREFIX.set(download_url_prefix) {
+                let err_msg = format!("Failed to set DOWNLOAD_URL_PREFIX: {e}");
+
+                error!(target: "stdout", "{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        }
+    }
+
+    // set DOWNLOAD_URL_SCHEME
+    if let Some(download_url_scheme) = cli.download_url_scheme {
+        info!(target: "stdout", "download_url_scheme: {}", &download_url_scheme);
+
+        if let Err(e) = DOWNLOAD_URL_SCHEME.set(download_url_scheme) {
+            let err_msg = format!("Failed to set DOWNLOAD_URL_SCHEME: {e}");
+
+            error!(target: "stdout", "{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    info!("Server running at http://{}", addr);
+
+    info!("Starting to accept connections...");
+    // the address of the peer identifies the client for rate limiting
+    let served = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown::signal())
+    .await;
+
+    // the indexing running off the async runtime is waited for when the runtime is dropped
+    shutdown::drain().await;
+
+    served.map_err(|e| ServerError::Operation(e.to_string()))
+}
+
+// Return basic information about the service, so that a browser pointed at the server shows it
+// is alive
+async fn root_handler() -> impl IntoResponse {
+    Json(serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "endpoints": [
+            "GET /v1/indexes",
+            "POST /v1/index/create",
+            "POST /v1/index/upload",
+            "POST /v1/files/upload/{index_name}",
+            "GET /v1/index/jobs/{job_id}",
+            "POST /v1/index/compare",
+            "POST /v1/index/{index_name}/check",
+            "POST /v1/index/{index_name}/merge",
+            "GET /v1/index/{index_name}/stats",
+            "GET /v1/index/{index_name}/distinct",
+            "GET /v1/index/{index_name}/originals/{filename}",
+            "GET /v1/index/download/{index_name}",
+            "DELETE /v1/index/{index_name}",
+            "POST /v1/upload/init",
+            "GET /v1/upload/{upload_id}",
+            "PATCH /v1/upload/{upload_id}",
+            "POST /v1/upload/{upload_id}/finalize",
+            "GET /v1/aliases",
+            "PUT /v1/aliases/{alias}",
+            "POST /v1/search",
+            "POST /v1/search/live",
+            "POST /v1/search/similar",
+            "GET /v1/suggest",
+            "POST /v1/analyze",
+            "GET /v1/config",
+            "GET /v1/metrics",
+            "GET /v1/health",
+            "GET /v1/ready",
+        ],
+    }))
+}
+
+// Return the effective configuration of the server. Secrets must never be included here.
+async fn config_handler() -> impl IntoResponse {
+    info!("Received config request");
+
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "socket_addr": SOCKET_ADDRESS.get().map(|addr| addr.to_string()),
+        "download_url_prefix": DOWNLOAD_URL_PREFIX.get().map(|url| url.to_string()),
+        "download_url_scheme": DOWNLOAD_URL_SCHEME.get(),
+        "index_storage_dir": index_storage_dir(),
+        "memory_budget_in_bytes": memory_budget::writer_bytes(),
+        "total_index_memory": memory_budget::total_bytes(),
+        "default_index": DEFAULT_INDEX.get(),
+        "search_executor_threads": search_executor::num_threads(),
+        "rate_limit_per_minute": rate_limit::requests_per_minute(),
+        "store_originals": STORE_ORIGINALS.get(),
+        "min_token_length": MIN_TOKEN_LENGTH.get(),
+        "preprocessing": PREPROCESSING.get(),
+        "default_top_k": default_top_k(),
+        "max_top_k": MAX_TOP_K.get(),
+        "snippet_max_scan_chars": SNIPPET_MAX_SCAN_CHARS.get(),
+        "max_multipart_fields": MAX_MULTIPART_FIELDS.get(),
+        "max_upload_bytes": MAX_UPLOAD_BYTES.get(),
+        "callback_allowed_hosts": CALLBACK_ALLOWED_HOSTS.get(),
+        "cors_origins": cors::allowed_origins(),
+        "api_key_required": auth::enabled(),
+        "normalize_line_endings": NORMALIZE_LINE_ENDINGS.get(),
+        "reader_cache_size": READER_CACHE_SIZE.get(),
+        "keep_failed_indexes": KEEP_FAILED_INDEXES.get(),
+        "max_concurrent_downloads": MAX_CONCURRENT_DOWNLOADS.get(),
+        "compression_level": archive::compression_level(),
+        "max_fields_per_document": MAX_FIELDS_PER_DOCUMENT.get(),
+        "max_content_chars": MAX_CONTENT_CHARS.get(),
+        "unknown_fields_as_text": UNKNOWN_FIELDS_AS_TEXT.get(),
+        "durability": DURABILITY.get(),
+        "log_format": LOG_FORMAT.get(),
+        "log_level": LOG_LEVEL.get(),
+        "atomic_multipart": ATOMIC_MULTIPART.get(),
+        "max_search_timeout_ms": MAX_SEARCH_TIMEOUT.get().map(|timeout| timeout.as_millis()),
+        "slow_query_ms": SLOW_QUERY_THRESHOLD.get().map(|threshold| threshold.as_millis()),
+        "upload_expiry_secs": resumable_upload::expiry().map(|expiry| expiry.as_secs()),
+        "features": {
+            "callbacks": CALLBACK_ALLOWED_HOSTS.get().is_some_and(|hosts| !hosts.is_empty()),
+        },
+    }))
+}
+
+// Return runtime metrics of the server
+async fn metrics_handler() -> impl IntoResponse {
+    info!("Received metrics request");
+
+    Json(serde_json::json!({
+        "reader_cache": reader_cache::stats(),
+    }))
+}
+
+// Main handler that routes to appropriate processing function based on content type
+async fn index_document_handler(
+    content_type: axum::http::header::HeaderMap,
+    request: axum::extract::Request,
+) -> Result<axum::response::Response, ServerError> {
+    let content_type = content_type
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    info!("Received document indexing request");
+
+    let (status, response) = match content_type {
+        t if t.starts_with("multipart/form-data") => {
+            info!("Processing as multipart/form-data");
+            let multipart = match Multipart::from_request(request, &()).await {
+                Ok(m) => m,
+                Err(e) => {
+                    error!(error = %e, "Failed to parse multipart request");
+                    return Err(ServerError::BadRequest(
+                        "Failed to parse multipart request".to_string(),
+                    ));
+                }
+            };
+            let atomic = ATOMIC_MULTIPART.get().copied().unwrap_or(false);
+            process_multipart(multipart, atomic).await
+        }
+        t if t.starts_with("application/x-ndjson") || t.starts_with("application/jsonl") => {
+            info!("Processing as NDJSON request");
+            let params =
+                match axum::extract::Query::<ndjson::NdjsonParams>::try_from_uri(request.uri()) {
+                    Ok(axum::extract::Query(params)) => params,
+                    Err(e) => {
+                        let err_msg = format!("Invalid query parameters: {e}");
+
+                        error!("{}", &err_msg);
+
+                        return Err(ServerError::BadRequest(err_msg));
+                    }
+                };
+            ndjson::process_ndjson(params, request.into_body()).await?
+        }
+        t if t.starts_with("text/plain") => {
+            info!("Processing as plain-text request");
+            let params = match axum::extract::Query::<plain_text::PlainTextParams>::try_from_uri(
+                request.uri(),
+            ) {
+                Ok(axum::extract::Query(params)) => params,
+                Err(e) => {
+                    let err_msg = format!("Invalid query parameters: {e}");
+
+                    error!("{}", &err_msg);
+
+                    return Err(ServerError::BadRequest(err_msg));
+                }
+            };
+            plain_text::process_plain_text(params, request).await?
+        }
+        "application/json" => {
+            info!("Processing as JSON request");
+            let index_request = match axum::Json::<IndexRequest>::from_request(request, &()).await {
+                Ok(Json(payload)) => payload,
+                Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                    let err_msg = format!(
+                        "The request exceeds the maximum upload size of {} bytes",
+                        MAX_UPLOAD_BYTES.get().copied().unwrap_or_default()
+                    );
+
+                    error!("{}", &err_msg);
+
+                    return Err(ServerError::PayloadTooLarge(err_msg));
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to parse JSON request");
+                    return Err(ServerError::BadRequest(
+                        "Failed to parse JSON request".to_string(),
+                    ));
+                }
+            };
+
+            // an empty batch would only leave an empty index behind, unless explicitly wanted
+            if index_request.documents.is_empty() && !index_request.allow_empty {
+                let err_msg = "No documents to index. Set `allow_empty` to create an empty index";
+
+                error!("{}", err_msg);
+
+                return Err(ServerError::BadRequest(err_msg.to_string()));
+            }
+
+            // boosts apply to the default search fields, and must keep scores positive
+            if let Err(err_msg) = validate_field_boosts(&index_request.field_boosts) {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+
+            // custom stop words are only removed on request
+            if let Err(err_msg) = token_length::validate_stopwords(
+                index_request.remove_stopwords,
+                &index_request.stopwords,
+            ) {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+
+            // the unique key must be a keyword field of the schema of new indexes
+            if let Some(unique_key) = &index_request.unique_key {
+                if let Err(err_msg) = unique_key::validate(unique_key) {
+                    error!("{}", &err_msg);
+
+                    return Err(ServerError::BadRequest(err_msg));
+                }
+            }
+
+            // reject invalid redaction regular expressions before indexing anything
+            if let Some(preprocessing) = &index_request.preprocessing {
+                if let Err(err_msg) = preprocess::Pipeline::new(preprocessing) {
+                    error!("{}", &err_msg);
+
+                    return Err(ServerError::BadRequest(err_msg));
+                }
+            }
+
+            // index in background and notify the callback URL on completion
+            if let Some(callback_url) = index_request.callback_url.clone() {
+                // the name of the index is checked before answering, and again when indexing
+                if let Err(e) = check_new_index_name(index_request.index.as_deref()) {
+                    error!("{}", &e);
+
+                    return Err(e);
+                }
+
+                let callback_url = match jobs::validate_callback_url(&callback_url) {
+                    Ok(url) => url,
+                    Err(e) => {
+                        error!(error = %e, "Invalid callback URL");
+                        return Err(e);
+                    }
+                };
+
+                let job_id = jobs::create_job(&callback_url);
+                info!(job_id = %job_id, "Processing JSON request in background");
+
+                let task_job_id = job_id.clone();
+                shutdown::spawn_tracked(async move {
+                    let (_, Json(response)) = process_json(index_request).await;
+                    jobs::complete_job(task_job_id, callback_url, response).await;
+                });
+
+                return Ok((
+                    StatusCode::ACCEPTED,
+                    Json(serde_json::json!({ "job_id": job_id, "status": "pending" })),
+                )
+                    .into_response());
+            }
+
+            process_json(index_request).await
+        }
+        _ => {
+            warn!(content_type = content_type, "Unsupported content type");
+            return Err(ServerError::Unsupported(format!(
+                "Unsupported content type '{content_type}'"
+            )));
+        }
+    };
+
+    info!(
+        successful = response
+            .results
+            .iter()
+            .filter(|r| r.status == "indexed")
+            .count(),
+        failed = response
+            .results
+            .iter()
+            .filter(|r| r.status == "failed")
+            .count(),
+        "Request processing completed"
+    );
+
+    let outcome = RequestOutcome {
+        index_name: response.index_name.clone(),
+        successful: response
+            .results
+            .iter()
+            .filter(|r| r.status == "indexed")
+            .count(),
+        failed: response
+            .results
+            .iter()
+            .filter(|r| r.status == "failed")
+            .count(),
+    };
+    let mut response = (status, response).into_response();
+    response.extensions_mut().insert(outcome);
+    Ok(response)
+}
+
+// Process multipart form data
+async fn process_multipart(
+    mut multipart: Multipart,
+    atomic: bool,
+) -> (StatusCode, Json<IndexResponse>) {
+    info!("Starting multipart form data processing");
+    let mut results = Vec::new();
+    let mut field_count = 0;
+    let mut documents = Vec::new();
+    let mut index_name: Option<String> = None;
+    // uploaded files kept with the index, if requested
+    let mut uploads = Vec::new();
+    // parsing of the CSV and TSV files, set by the `delimiter` and `has_header` fields
+    let mut tabular_options = tabular::TabularOptions::default();
+    // title of the document of the next field, set by a `title` field
+    let mut next_title: Option<String> = None;
+    // whether the documents with the same content as an earlier one are skipped, set by a
+    // `dedup` field
+    let mut dedup = false;
+
+    // preprocessing of the documents, validated at startup
+    let pipeline =
+        match preprocess::Pipeline::new(&PREPROCESSING.get().cloned().unwrap_or_default()) {
+            Ok(pipeline) => pipeline,
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(IndexResponse {
+                        results: vec![DocumentResult {
+                            filename: None,
+                            status: "failed".to_string(),
+                            error: Some(err_msg),
+                        }],
+                        index_name: None,
+                        download_url: None,
+                        provenance: None,
+                    }),
+                );
+            }
+        };
+
+    let max_fields = MAX_MULTIPART_FIELDS.get().copied().unwrap_or(usize::MAX);
+    // whether reading a field failed, e.g. because the connection dropped mid-stream
+    let mut read_failed = false;
+    // bytes of the files left to the remaining fields, and whether the request exceeds the limit
+    let mut remaining_bytes = MAX_UPLOAD_BYTES.get().map_or(u64::MAX, |max| *max as u64);
+    let mut too_large = false;
+
+    loop {
+        if (atomic && read_failed) || too_large {
+            break;
+        }
+
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                error!(error = %e, "Failed to read multipart field");
+                results.push(DocumentResult {
+                    filename: None,
+                    status: "failed".to_string(),
+                    error: Some(format!("Failed to read multipart field: {e}")),
+                });
+                read_failed = true;
+                too_large = e.status() == StatusCode::PAYLOAD_TOO_LARGE;
+                break;
+            }
+        };
+        field_count += 1;
+
+        // stop processing once the number of fields exceeds the limit
+        if field_count > max_fields {
+            warn!(
+                max_fields = max_fields,
+                "Too many multipart fields, skipping the remaining fields"
+            );
+            results.push(DocumentResult {
+                filename: field.file_name().map(ToString::to_string),
+                status: "failed".to_string(),
+                error: Some(format!(
+                    "Too many multipart fields. At most {max_fields} fields are allowed, the remaining fields are ignored"
+                )),
+            });
+            break;
+        }
+
+        let field_name = field.name().unwrap_or("unknown").to_string();
+        let filename = field.file_name().map(ToString::to_string);
+
+        let content_type = field
+            .content_type()
+            .map(|ct| ct.to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+
+        info!(
+            field_number = field_count,
+            field_name = %field_name,
+            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+            content_type = %content_type,
+            "Processing field"
+        );
+
+        // Handle index field (text type)
+        if field_name == "index" || field_name == "index_name" {
+            match field.text().await {
+                Ok(text) => {
+                    info!("Processing index field");
+                    // Store index name for later use
+                    index_name = Some(text.trim_matches('"').to_string());
+                    continue;
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to read index field");
+                    results.push(DocumentResult {
+                        filename: None,
+                        status: "failed".to_string(),
+                        error: Some(format!("Failed to read index field: {e}")),
+                    });
+                    read_failed = true;
+                    too_large = e.status() == StatusCode::PAYLOAD_TOO_LARGE;
+                    continue;
+                }
+            }
+        }
+
+        // Handle the options of the CSV and TSV files following them
+        if field_name == "delimiter" || field_name == "has_header" {
+            let option = match field.text().await {
+                Ok(text) => parse_tabular_option(&mut tabular_options, &field_name, &text),
+                Err(e) => {
+                    read_failed = true;
+                    too_large = e.status() == StatusCode::PAYLOAD_TOO_LARGE;
+                    Err(format!("Failed to read {field_name} field: {e}"))
+                }
+            };
+            if let Err(e) = option {
+                error!(error = %e, "Invalid tabular option");
+                results.push(DocumentResult {
+                    filename: None,
+                    status: "failed".to_string(),
+                    error: Some(e),
+                });
+            }
+            continue;
+        }
+
+        // Handle the deduplication of the documents, whatever the position of the field
+        if field_name == "dedup" {
+            let option = match field.text().await {
+                Ok(text) => text.trim_matches('"').parse().map_err(|_| {
+                    format!("Invalid dedup value '{text}'. It must be `true` or `false`")
+                }),
+                Err(e) => {
+                    read_failed = true;
+                    too_large = e.status() == StatusCode::PAYLOAD_TOO_LARGE;
+                    Err(format!("Failed to read dedup field: {e}"))
+                }
+            };
+            match option {
+                Ok(value) => dedup = value,
+                Err(e) => {
+                    error!(error = %e, "Invalid dedup option");
+                    results.push(DocumentResult {
+                        filename: None,
+                        status: "failed".to_string(),
+                        error: Some(e),
+                    });
+                }
+            }
+            continue;
+        }
+
+        // Handle the title of the document of the next field
+        if field_name == "title" {
+            match field.text().await {
+                Ok(text) => {
+                    let title = text.trim();
+                    next_title = (!title.is_empty()).then(|| title.to_string());
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to read title field");
+                    results.push(DocumentResult {
+                        filename: None,
+                        status: "failed".to_string(),
+                        error: Some(format!("Failed to read title field: {e}")),
+                    });
+                    read_failed = true;
+                    too_large = e.status() == StatusCode::PAYLOAD_TOO_LARGE;
+                }
+            }
+            continue;
+        }
+        let title = next_title.take();
+
+        let is_file = is_file_field(&field);
+        if is_file {
+            // Handle file fields
+            if !is_valid_content_type(&content_type) {
+                warn!(
+                    field_number = field_count,
+                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                    content_type = %content_type,
+                    "Unsupported file type"
+                );
+                results.push(DocumentResult {
+                    filename,
+                    status: "failed".to_string(),
+                    error: Some(
+                        "Unsupported file type. Only .txt, .md, .csv, .tsv, .html and .pdf files are allowed"
+                            .to_string(),
+                    ),
+                });
+                continue;
+            }
+        }
+
+        let tabular = tabular::TabularFormat::detect(&content_type, filename.as_deref())
+            .map(|format| (format, tabular_options));
+        let text_format = TextFormat::detect(&content_type, filename.as_deref());
+        match process_field_content(
+            &mut results,
+            &mut documents,
+            &mut uploads,
+            &pipeline,
+            field,
+            filename,
+            title,
+            tabular,
+            text_format,
+            remaining_bytes,
+        )
+        .await
+        {
+            Ok(size_bytes) => remaining_bytes = remaining_bytes.saturating_sub(size_bytes),
+            Err(e) => {
+                read_failed = true;
+                too_large = matches!(e, upload::UploadError::TooLarge(_));
+            }
+        }
+    }
+
+    info!(
+        total_fields = field_count,
+        successful = results.iter().filter(|r| r.status == "indexed").count(),
+        failed = results.iter().filter(|r| r.status == "failed").count(),
+        "Field processing completed"
+    );
+
+    // a request exceeding the maximum upload size never creates an index
+    if too_large {
+        warn!("The request exceeds the maximum upload size, aborting without creating an index");
+        for result in results
+            .iter_mut()
+            .filter(|result| result.status == "indexed")
+        {
+            result.status = "failed".to_string();
+            result.error =
+                Some("Not indexed, as the request exceeds the maximum upload size".to_string());
+        }
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // in atomic mode, a partial upload must not create an index
+    if atomic && read_failed {
+        warn!("Failed to read the multipart request, aborting without creating an index");
+        for result in results
+            .iter_mut()
+            .filter(|result| result.status == "indexed")
+        {
+            result.status = "failed".to_string();
+            result.error =
+                Some("Not indexed, as another field of the request could not be read".to_string());
+        }
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // the index is created under the given name only if it is valid and free
+    if let Err(e) = check_new_index_name(index_name.as_deref()) {
+        error!("{}", &e);
+        for result in results
+            .iter_mut()
+            .filter(|result| result.status == "indexed")
+        {
+            result.status = "failed".to_string();
+            result.error = Some("Not indexed, as the index cannot be created".to_string());
+        }
+        results.push(DocumentResult {
+            filename: None,
+            status: "failed".to_string(),
+            error: Some(e.to_string()),
+        });
+        return (
+            e.status(),
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // waiting for the memory of the index writer is async, so it is acquired beforehand
+    let writer_budget = memory_budget::acquire().await;
+
+    // indexing is blocking work, kept off the async runtime along with the index writer
+    let indexed = request_id::spawn_blocking(move || {
+        index_uploads(
+            index_name,
+            documents,
+            uploads,
+            results,
+            dedup,
+            writer_budget,
+        )
+    })
+    .await;
+    match indexed {
+        Ok(response) => response,
+        Err(e) => indexing_task_failed(e),
+    }
+}
+
+// Index the documents read from a multipart request, keeping the uploaded files with the index if
+// requested, with the memory budget of the index writer. With `dedup`, the documents with the
+// same content as an earlier one are skipped.
+fn index_uploads(
+    index_name: Option<String>,
+    documents: Vec<DocumentInput>,
+    uploads: Vec<(String, upload::TempUpload)>,
+    mut results: Vec<DocumentResult>,
+    dedup: bool,
+    writer_budget: memory_budget::WriterBudget,
+) -> (StatusCode, Json<IndexResponse>) {
+    // Create index directory
+    info!("Starting index creation");
+    let index_storage_dir = index_storage_dir();
+    let index_name = index_name.unwrap_or_else(|| format!("index-{}", uuid::Uuid::new_v4()));
+    let index_path = index_storage_dir.as_path().join(&index_name);
+    let created_index_dir = !index_path.exists();
+    if created_index_dir {
+        debug!(path = %index_path.display(), "Creating index directory");
+        if let Err(e) = std::fs::create_dir_all(&index_path) {
+            let e = ServerError::Operation(format!("Failed to create the index directory: {e}"));
+
+            error!("{}", &e);
+
+            results.push(DocumentResult {
+                filename: None,
+                status: "failed".to_string(),
+                error: Some(e.to_string()),
+            });
+            return (
+                e.status(),
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    }
+
+    // Define schema
+    info!("Defining index schema");
+    let mut schema_builder = Schema::builder();
+    let min_token_length = MIN_TOKEN_LENGTH
+        .get()
+        .copied()
+        .unwrap_or(token_length::DEFAULT_MIN_TOKEN_LENGTH);
+    // the documents of multipart requests are tokenized by the default tokenizer
+    let tokenization = token_length::Tokenization {
+        min_token_length,
+        ..Default::default()
+    };
+    let text_options = tokenization.text_options();
+    let title = schema_builder.add_text_field("title", text_options.clone());
+    let body = schema_builder.add_text_field("body", text_options.clone());
+    let content_hash = schema_builder.add_text_field("content_hash", STRING | STORED);
+    let quality = schema_builder.add_f64_field(ranking::QUALITY_FIELD, FAST | STORED);
+    let body_length = schema_builder.add_u64_field(ranking::BODY_LENGTH_FIELD, FAST);
+    // the documents of multipart requests have no creation date
+    schema_builder.add_date_field(ranking::CREATED_AT_FIELD, INDEXED | STORED | FAST);
+    let fields = schema_builder.add_json_field(tabular::FIELDS_FIELD, text_options);
+    let tags_field = tags::add_field(&mut schema_builder);
+    let schema = schema_builder.build();
+
+    // Create index
+    info!("Creating new index");
+    let index = match durability::create_index(&index_path, schema.clone()) {
+        Ok(index) => index,
+        Err(e) => {
+            error!(error = %e, "Failed to create index");
+            if created_index_dir {
+                remove_failed_index(&index_path);
+            }
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    };
+
+    tokenization.register(&index);
+
+    // Create index writer, with its share of the total indexing memory
+    info!("Initializing index writer");
+    let mut index_writer = match index.writer(writer_budget.bytes()) {
+        Ok(writer) => writer,
+        Err(e) => {
+            error!(error = %e, "Failed to create index writer");
+            if created_index_dir {
+                remove_failed_index(&index_path);
+            }
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    };
+
+    // Add documents to index
+    info!(
+        document_count = documents.len(),
+        "Starting document indexing"
+    );
+    // each document was reported as indexed once read, in the same order as the documents
+    let positions: Vec<usize> = results
+        .iter()
+        .enumerate()
+        .filter(|(_, result)| result.status == "indexed")
+        .map(|(position, _)| position)
+        .collect();
+    let mut num_documents = 0;
+    // content hashes of the documents indexed so far, to skip duplicates
+    let mut indexed_hashes: HashSet<String> = HashSet::new();
+    let max_fields = MAX_FIELDS_PER_DOCUMENT.get().copied().unwrap_or(usize::MAX);
+    for (i, (document, position)) in documents.iter().zip(positions).enumerate() {
+        if let Err(e) = check_field_count(document, max_fields) {
+            error!(
+                document_number = i + 1,
+                error = %e,
+                "Rejected document"
+            );
+            results[position].status = "failed".to_string();
+            results[position].error = Some(e);
+            continue;
+        }
+        let content_hash_value = compute_content_hash(&document.content);
+        if dedup && indexed_hashes.contains(&content_hash_value) {
+            info!(
+                document_number = i + 1,
+                "Skipped document with the same content as an earlier document"
+            );
+            results[position].status = "skipped".to_string();
+            continue;
+        }
+        let mut doc = doc!(
+            title => document.title.clone().unwrap_or("Unknown".to_string()),
+            body => document.content.clone(),
+            content_hash => content_hash_value.clone(),
+            body_length => document.content.chars().count() as u64,
+        );
+        if let Some(quality_value) = document.quality {
+            doc.add_f64(quality, quality_value);
+        }
+        tabular::add_fields(&mut doc, fields, &document.fields);
+        tags::add_tags(&mut doc, tags_field, &document.tags);
+        if let Err(e) = index_writer.add_document(doc) {
+            error!(
+                document_number = i + 1,
+                error = %e,
+                "Failed to add document to index"
+            );
+            results[position].status = "failed".to_string();
+            results[position].error = Some(format!("Failed to add to index: {e}"));
+            continue;
+        }
+        if dedup {
+            indexed_hashes.insert(content_hash_value);
+        }
+        num_documents += 1;
+        info!(
+            document_number = i + 1,
+            total = documents.len(),
+            "Document added to index"
+        );
+    }
+
+    // Commit index, along with the metadata of the index
+    info!("Committing index");
+    let source_filenames = results
+        .iter()
+        .filter(|result| result.status == "indexed")
+        .filter_map(|result| result.filename.as_deref());
+    let metadata = IndexMetadata {
+        min_token_length: (min_token_length > token_length::DEFAULT_MIN_TOKEN_LENGTH)
+            .then_some(min_token_length),
+        provenance: Some(IndexProvenance::new(
+            &schema,
+            num_documents,
+            source_filenames,
+        )),
+        ..Default::default()
+    };
+    if let Err(e) = index_meta::commit_with_metadata(&mut index_writer, &metadata) {
+        error!(error = %e, "Failed to commit index");
+        drop(index_writer);
+        if created_index_dir {
+            remove_failed_index(&index_path);
+        }
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // drop any stale reader cached under the same name
+    reader_cache::invalidate(&index_name);
+
+    // keep the uploaded files along with the index
+    originals::store(&index_path, uploads);
+
+    // generate download url for index file
+    let url = download_url(
+        DOWNLOAD_URL_PREFIX.get().unwrap(),
+        DOWNLOAD_URL_SCHEME.get().map(String::as_str),
+        &index_name,
+    );
+    info!(url = %url, "Download URL generated");
+
+    (
+        StatusCode::OK,
+        Json(IndexResponse {
+            results,
+            index_name: Some(index_name),
+            download_url: Some(url),
+            provenance: metadata.provenance,
+        }),
+    )
+}
+
+// Helper function to process field content. CSV and TSV files, given with the options to parse
+// them, are indexed as one document per row, and HTML and PDF files by their extracted text. The
+// document is titled `title` if given, else by the `<title>` of an HTML file, else by the name of
+// the file without its extension. Fields larger than `max_bytes` are rejected. Returns the size of
+// the field, or why its content could not be read.
+#[allow(clippy::too_many_arguments)]
+async fn process_field_content(
+    results: &mut Vec<DocumentResult>,
+    documents: &mut Vec<DocumentInput>,
+    uploads: &mut Vec<(String, upload::TempUpload)>,
+    pipeline: &preprocess::Pipeline,
+    field: axum::extract::multipart::Field<'_>,
+    filename: Option<String>,
+    title: Option<String>,
+    tabular: Option<(tabular::TabularFormat, tabular::TabularOptions)>,
+    text_format: TextFormat,
+    max_bytes: u64,
+) -> Result<u64, upload::UploadError> {
+    // stream the field to disk, so that large uploads are not buffered in memory
+    let upload = match upload::stream_to_temp_file(field, Some(max_bytes)).await {
+        Ok(upload) => upload,
+        Err(e) => {
+            error!(
+                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                error = %e,
+                "Failed to read field content"
+            );
+            results.push(DocumentResult {
+                filename,
+                status: "failed".to_string(),
+                error: Some(e.to_string()),
+            });
+            return Err(e);
+        }
+    };
+    let size_bytes = upload.size_bytes();
+    info!(
+        size_bytes = upload.size_bytes(),
+        "Content read successfully"
+    );
+
+    // the title of a document is lifted from the `<title>` of an HTML file
+    let content = match text_format {
+        TextFormat::Plain => read_text_file(upload.path())
+            .await
+            .map(|content| (None, content)),
+        TextFormat::Html => read_text_file(upload.path()).await.map(|content| {
+            let text = html::extract_text(&content);
+            (text.title, text.body)
+        }),
+        TextFormat::Pdf => pdf::extract_text(upload.path())
+            .await
+            .map(|content| (None, content)),
+    };
+    match content {
+        Ok((html_title, content)) => match tabular {
+            Some((format, options)) => {
+                let indexed = process_rows(
+                    results,
+                    documents,
+                    pipeline,
+                    filename.as_deref(),
+                    &normalize_text(content),
+                    format,
+                    options,
+                );
+
+                // keep the upload, which is otherwise removed once read
+                if indexed && STORE_ORIGINALS.get().copied().unwrap_or(false) {
+                    if let Some(filename) = filename {
+                        uploads.push((filename, upload));
+                    }
+                }
+            }
+            None => {
+                let title = title
+                    .or(html_title)
+                    .or_else(|| filename.as_deref().and_then(title_from_filename));
+                match process_content(normalize_text(content), pipeline) {
+                    Ok(content) => {
+                        info!("Content processed successfully");
+                        documents.push(DocumentInput {
+                            content,
+                            title,
+                            quality: None,
+                            fields: BTreeMap::new(),
+                            tags: Vec::new(),
+                            created_at: None,
+                        });
+                        results.push(DocumentResult {
+                            filename: filename.clone(),
+                            status: "indexed".to_string(),
+                            error: None,
+                        });
+
+                        // keep the upload, which is otherwise removed once read
+                        if STORE_ORIGINALS.get().copied().unwrap_or(false) {
+                            if let Some(filename) = filename {
+                                uploads.push((filename, upload));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!(
+                            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                            error = %e,
+                            "Content processing failed"
+                        );
+                        results.push(DocumentResult {
+                            filename,
+                            status: "failed".to_string(),
+                            error: Some(e.to_string()),
+                        });
+                    }
+                }
+            }
+        },
+        Err(e) => {
+            error!(
+                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                error = %e,
+                "Failed to read file content"
+            );
+            results.push(DocumentResult {
+                filename,
+                status: "failed".to_string(),
+                error: Some(e),
+            });
+        }
+    }
+
+    Ok(size_bytes)
+}
+
+// Title of a document read from the file `filename`: its name without the extension
+pub(crate) fn title_from_filename(filename: &str) -> Option<String> {
+    std::path::Path::new(filename)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::trim)
+        .filter(|stem| !stem.is_empty())
+        .map(str::to_string)
+}
+
+// Read an uploaded text file, which must be valid UTF-8
+async fn read_text_file(path: &std::path::Path) -> Result<String, String> {
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => "Invalid UTF-8 content".to_string(),
+            _ => format!("Failed to read file: {e}"),
+        })
+}
+
+// Index each data row of a CSV or TSV file as a document, reporting the result of each row under
+// `{filename}:{line}`. Returns whether a row was indexed.
+pub(crate) fn process_rows(
+    results: &mut Vec<DocumentResult>,
+    documents: &mut Vec<DocumentInput>,
+    pipeline: &preprocess::Pipeline,
+    filename: Option<&str>,
+    content: &str,
+    format: tabular::TabularFormat,
+    options: tabular::TabularOptions,
+) -> bool {
+    let filename = filename.unwrap_or("Unknown");
+    let rows = match tabular::parse(content, format, options) {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!(filename = %filename, error = %e, "Failed to parse tabular file");
+            results.push(DocumentResult {
+                filename: Some(filename.to_string()),
+                status: "failed".to_string(),
+                error: Some(e),
+            });
+            return false;
+        }
+    };
+    info!(filename = %filename, rows = rows.len(), "Tabular file parsed");
+
+    let mut indexed = false;
+    for row in rows {
+        let row_name = format!("{filename}:{}", row.line);
+
+        // preprocess each value, so that e.g. redacted data is not kept in the fields either
+        let row_fields = row.fields.and_then(|fields| {
+            let fields: BTreeMap<String, String> = fields
+                .into_iter()
+                .map(|(name, value)| (name, pipeline.apply(value)))
+                .collect();
+            let content = tabular::Row::body(&fields);
+            match content.trim().is_empty() {
+                true => Err("Empty row is not allowed".to_string()),
+                false => check_content_length(&content, MAX_CONTENT_CHARS.get().copied())
+                    .map(|_| (content, fields)),
+            }
+        });
+        match row_fields {
+            Ok((content, fields)) => {
+                documents.push(DocumentInput {
+                    content,
+                    title: Some(row_name.clone()),
+                    quality: None,
+                    fields,
+                    tags: Vec::new(),
+                    created_at: None,
+                });
+                results.push(DocumentResult {
+                    filename: Some(row_name),
+                    status: "indexed".to_string(),
+                    error: None,
+                });
+                indexed = true;
+            }
+            Err(e) => {
+                warn!(row = %row_name, error = %e, "Row rejected");
+                results.push(DocumentResult {
+                    filename: Some(row_name),
+                    status: "failed".to_string(),
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    indexed
+}
+
+// Set the CSV and TSV parsing option of a `delimiter` or `has_header` multipart field
+fn parse_tabular_option(
+    options: &mut tabular::TabularOptions,
+    field_name: &str,
+    text: &str,
+) -> Result<(), String> {
+    let text = text.trim_matches('"');
+    match field_name {
+        "delimiter" => {
+            let delimiter = match text {
+                "\\t" | "tab" => '\t',
+                _ => {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if !matches!(c, '"' | '\n' | '\r') => c,
+                        _ => {
+                            return Err(format!(
+                                "Invalid delimiter '{text}'. It must be a single character other than a quote or a line break, or `tab`"
+                            ))
+                        }
+                    }
+                }
+            };
+            options.delimiter = Some(delimiter);
+        }
+        _ => {
+            options.has_header = text.parse().map_err(|_| {
+                format!("Invalid has_header value '{text}'. It must be `true` or `false`")
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+// Prefix of the download URLs of a server bound to `addr`, if `--download-url-prefix` is not set.
+// An unspecified address, `0.0.0.0` or `::`, listens on all interfaces and maps to `localhost`,
+// and IPv6 hosts are bracketed in URLs.
+fn default_download_url_prefix(addr: SocketAddr) -> String {
+    match addr.ip() {
+        ip if ip.is_unspecified() => format!("http://localhost:{}", addr.port()),
+        IpAddr::V4(ip) => format!("http://{}:{}", ip, addr.port()),
+        IpAddr::V6(ip) => format!("http://[{}]:{}", ip, addr.port()),
+    }
+}
+
+// Generate the URL to download the archive of an index under `download_url_prefix`, with
+// `scheme`, set by `--download-url-scheme`, if any, rather than the one of the prefix
+fn download_url(download_url_prefix: &Url, scheme: Option<&str>, index_name: &str) -> String {
+    let host = match download_url_prefix.port() {
+        Some(port) => {
+            format!("{}:{}", download_url_prefix.host_str().unwrap(), port)
+        }
+        None => download_url_prefix.host_str().unwrap().to_string(),
+    };
+    let scheme = scheme.unwrap_or(download_url_prefix.scheme());
+
+    format!("{scheme}://{host}/v1/index/download/{index_name}")
+}
+
+// Check that `name` can name a new index: 1 to 64 ASCII letters, digits, `_` or `-`, other than
+// the names of the routes
+pub(crate) fn validate_new_index_name(name: &str) -> Result<(), String> {
+    if RESERVED_INDEX_NAMES.contains(&name) {
+        return Err(format!(
+            "Invalid index name '{name}'. The names {RESERVED_INDEX_NAMES:?} are reserved"
+        ));
+    }
+
+    let valid = (1..=MAX_INDEX_NAME_CHARS).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match valid {
+        true => Ok(()),
+        false => Err(format!(
+            "Invalid index name '{name}'. It must be made of 1 to {MAX_INDEX_NAME_CHARS} letters, digits, `_` and `-`"
+        )),
+    }
+}
+
+/// Check that `index_name` names an entry of the index storage directory, and cannot reach outside
+/// of it, before the filesystem is touched.
+pub(crate) fn validate_index_name(index_name: &str) -> Result<(), String> {
+    if index_name.is_empty() || index_name.contains(['/', '\\', '\0']) || index_name.contains("..")
+    {
+        return Err(format!(
+            "Invalid index name '{}'",
+            index_name.escape_debug()
+        ));
+    }
+
+    Ok(())
+}
+
+// Check that a new index can be created under the name given by the request, if any: the name
+// must be valid, and no index may exist under it.
+pub(crate) fn check_new_index_name(index_name: Option<&str>) -> Result<(), ServerError> {
+    let Some(index_name) = index_name else {
+        return Ok(());
+    };
+
+    validate_new_index_name(index_name).map_err(ServerError::BadRequest)?;
+    if index_storage_dir().join(index_name).exists() {
+        return Err(ServerError::Conflict(format!(
+            "Index '{index_name}' already exists"
+        )));
+    }
+
+    Ok(())
+}
+
+// Check that the boosts apply to the default search fields and are positive
+fn validate_field_boosts(field_boosts: &BTreeMap<String, f32>) -> Result<(), String> {
+    for (field_name, boost) in field_boosts {
+        if !DEFAULT_SEARCH_FIELDS.contains(&field_name.as_str()) {
+            return Err(format!(
+                "Cannot boost field '{field_name}'. Only the default search fields can be boosted: {}",
+                DEFAULT_SEARCH_FIELDS.join(", ")
+            ));
+        }
+        if !boost.is_finite() || *boost <= 0.0 {
+            return Err(format!(
+                "Invalid boost {boost} of field '{field_name}'. It must be a positive number"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Process JSON input
+pub(crate) async fn process_json(request: IndexRequest) -> (StatusCode, Json<IndexResponse>) {
+    info!(
+        document_count = request.documents.len(),
+        "Starting JSON request processing"
+    );
+
+    // the index is created under the given name only if it is valid and free
+    if let Err(e) = check_new_index_name(request.index.as_deref()) {
+        error!("{}", &e);
+
+        return (
+            e.status(),
+            Json(IndexResponse {
+                results: vec![DocumentResult {
+                    filename: None,
+                    status: "failed".to_string(),
+                    error: Some(e.to_string()),
+                }],
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // waiting for the memory of the index writer is async, so it is acquired beforehand
+    let writer_budget = memory_budget::acquire().await;
+
+    // indexing is blocking work, kept off the async runtime along with the index writer
+    match request_id::spawn_blocking(move || index_json(request, writer_budget)).await {
+        Ok(response) => response,
+        Err(e) => indexing_task_failed(e),
+    }
+}
+
+// Index the documents of a JSON request, with the memory budget of the index writer
+fn index_json(
+    mut request: IndexRequest,
+    writer_budget: memory_budget::WriterBudget,
+) -> (StatusCode, Json<IndexResponse>) {
+    let documents = std::mem::take(&mut request.documents)
+        .into_iter()
+        .map(|document| (document.title.clone(), Ok(document)));
+
+    index_documents(request, documents, None, writer_budget)
+}
+
+/// Index `documents` into a new index, with the options of `request` and the memory budget of
+/// the index writer. Each document comes with the name reported in its result, and fails if it
+/// could not be read. The documents indexed so far are committed every `commit_interval`
+/// documents, if set, and all of them once `documents` is exhausted.
+pub(crate) fn index_documents(
+    request: IndexRequest,
+    documents: impl Iterator<Item = (Option<String>, Result<DocumentInput, String>)>,
+    commit_interval: Option<usize>,
+    writer_budget: memory_budget::WriterBudget,
+) -> (StatusCode, Json<IndexResponse>) {
+    let mut results = Vec::new();
+
+    // preprocessing of the documents, from the request or the CLI options
+    let preprocessing = request
+        .preprocessing
+        .clone()
+        .or_else(|| PREPROCESSING.get().cloned())
+        .unwrap_or_default();
+    let pipeline = match preprocess::Pipeline::new(&preprocessing) {
+        Ok(pipeline) => pipeline,
+        Err(err_msg) => {
+            error!("{}", &err_msg);
+
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IndexResponse {
+                    results: vec![DocumentResult {
+                        filename: None,
+                        status: "failed".to_string(),
+                        error: Some(err_msg),
+                    }],
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    };
+
+    // Create index directory
+    info!("Starting index creation");
+    let index_storage_dir = index_storage_dir();
+    let index_name = match request.index {
+        Some(name) => name,
+        None => format!("index-{}", uuid::Uuid::new_v4()),
+    };
+    let index_path = index_storage_dir.as_path().join(&index_name);
+    let created_index_dir = !index_path.exists();
+    if created_index_dir {
+        debug!(path = %index_path.display(), "Creating index directory");
+        if let Err(e) = std::fs::create_dir_all(&index_path) {
+            let e = ServerError::Operation(format!("Failed to create the index directory: {e}"));
+
+            error!("{}", &e);
+
+            results.push(DocumentResult {
+                filename: None,
+                status: "failed".to_string(),
+                error: Some(e.to_string()),
+            });
+            return (
+                e.status(),
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    }
+
+    // Define schema
+    info!("Defining index schema");
+    let mut schema_builder = Schema::builder();
+    let min_token_length = MIN_TOKEN_LENGTH
+        .get()
+        .copied()
+        .unwrap_or(token_length::DEFAULT_MIN_TOKEN_LENGTH);
+    let tokenization = token_length::Tokenization::new(
+        request.tokenizer,
+        min_token_length,
+        request.remove_stopwords,
+        &request.stopwords,
+    );
+    let text_options = tokenization.text_options();
+    let title = schema_builder.add_text_field("title", text_options.clone());
+    let body = schema_builder.add_text_field("body", text_options.clone());
+    let content_hash = schema_builder.add_text_field("content_hash", STRING | STORED);
+    let quality = schema_builder.add_f64_field(ranking::QUALITY_FIELD, FAST | STORED);
+    let body_length = schema_builder.add_u64_field(ranking::BODY_LENGTH_FIELD, FAST);
+    let created_at =
+        schema_builder.add_date_field(ranking::CREATED_AT_FIELD, INDEXED | STORED | FAST);
+    let fields = schema_builder.add_json_field(tabular::FIELDS_FIELD, text_options);
+    let tags_field = tags::add_field(&mut schema_builder);
+    if let Some(unique_key) = &request.unique_key {
+        unique_key::add_field(&mut schema_builder, unique_key);
+    }
+    let schema = schema_builder.build();
+    let unique_key = request
+        .unique_key
+        .as_ref()
+        .map(|name| (name.as_str(), schema.get_field(name).unwrap()));
+
+    // Create index
     info!("Creating new index");
-    let index = match Index::create_in_dir(&index_path, schema.clone()) {
+    let index = match durability::create_index(&index_path, schema.clone()) {
         Ok(index) => index,
         Err(e) => {
-            error!(error = %e, "Failed to create index");
-            return Json(IndexResponse {
-                results,
-                index_name: None,
-                download_url: None,
-            });
+            error!(error = %e, "Failed to create index");
+            if created_index_dir {
+                remove_failed_index(&index_path);
+            }
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    };
+
+    tokenization.register(&index);
+
+    // Create index writer, with its share of the total indexing memory
+    info!("Initializing index writer");
+    let mut index_writer = match index.writer(writer_budget.bytes()) {
+        Ok(writer) => writer,
+        Err(e) => {
+            error!(error = %e, "Failed to create index writer");
+            if created_index_dir {
+                remove_failed_index(&index_path);
+            }
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IndexResponse {
+                    results,
+                    index_name: None,
+                    download_url: None,
+                    provenance: None,
+                }),
+            );
+        }
+    };
+
+    // metadata of the index, committed along with the documents indexed so far
+    let metadata = |results: &[DocumentResult]| IndexMetadata {
+        search_defaults: request.search_defaults.clone(),
+        field_boosts: request.field_boosts.clone(),
+        min_token_length: (min_token_length > token_length::DEFAULT_MIN_TOKEN_LENGTH)
+            .then_some(min_token_length),
+        tokenizer: (request.tokenizer != TextTokenizer::Default).then_some(request.tokenizer),
+        stopwords: tokenization.stopwords.clone(),
+        unique_key: request.unique_key.clone(),
+        // the documents of a JSON request are not read from files
+        provenance: Some(IndexProvenance::new(
+            &schema,
+            results
+                .iter()
+                .filter(|result| result.status == "indexed")
+                .count() as u64,
+            [],
+        )),
+    };
+
+    // Process and index documents
+    // position in the results of the document indexed with each unique key
+    let mut key_positions: HashMap<String, usize> = HashMap::new();
+    // content hashes of the documents indexed so far, to skip duplicates
+    let mut indexed_hashes: HashSet<String> = HashSet::new();
+    let max_fields = MAX_FIELDS_PER_DOCUMENT.get().copied().unwrap_or(usize::MAX);
+    let mut added = 0;
+    for (index, (filename, document)) in documents.enumerate() {
+        let mut document = match document {
+            Ok(document) => document,
+            Err(e) => {
+                error!(
+                    document_number = index + 1,
+                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                    error = %e,
+                    "Failed to read document"
+                );
+                results.push(DocumentResult {
+                    filename,
+                    status: "failed".to_string(),
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
+        info!(
+            document_number = index + 1,
+            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+            content_length = document.content.len(),
+            "Processing document"
+        );
+
+        // Process content
+        document.content = match process_content(normalize_text(document.content), &pipeline) {
+            Ok(content) => content,
+            Err(e) => {
+                error!(
+                    document_number = index + 1,
+                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                    error = %e,
+                    "Document processing failed"
+                );
+                results.push(DocumentResult {
+                    filename,
+                    status: "failed".to_string(),
+                    error: Some(e),
+                });
+                continue;
+            }
+        };
+
+        // the creation date must be valid, to sort the hits by it
+        let created_at_value = match document.created_at.as_deref().map(ranking::parse_date) {
+            Some(Ok(date)) => Some(date),
+            Some(Err(e)) => {
+                error!(
+                    document_number = index + 1,
+                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                    error = %e,
+                    "Invalid document creation date"
+                );
+                results.push(DocumentResult {
+                    filename,
+                    status: "failed".to_string(),
+                    error: Some(e),
+                });
+                continue;
+            }
+            None => None,
+        };
+
+        if let Err(e) = check_field_count(&document, max_fields) {
+            error!(
+                document_number = index + 1,
+                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                error = %e,
+                "Rejected document"
+            );
+            results.push(DocumentResult {
+                filename,
+                status: "failed".to_string(),
+                error: Some(e),
+            });
+            continue;
+        }
+
+        // Add document to index, unless its content was already indexed by the request
+        let content_hash_value = compute_content_hash(&document.content);
+        if request.dedup && indexed_hashes.contains(&content_hash_value) {
+            info!(
+                document_number = index + 1,
+                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                "Skipped document with the same content as an earlier document"
+            );
+            results.push(DocumentResult {
+                filename,
+                status: "skipped".to_string(),
+                error: None,
+            });
+            continue;
+        }
+        let mut doc = doc!(
+            title => document.title.clone().unwrap_or("Unknown".to_string()),
+            body => document.content.clone(),
+            content_hash => content_hash_value.clone(),
+            body_length => document.content.chars().count() as u64,
+        );
+        if let Some(quality_value) = document.quality {
+            doc.add_f64(quality, quality_value);
+        }
+        tabular::add_fields(&mut doc, fields, &document.fields);
+        tags::add_tags(&mut doc, tags_field, &document.tags);
+        if let Some(created_at_value) = created_at_value {
+            doc.add_date(created_at, created_at_value);
+        }
+        // the title is added as a second value of the body, so that the content stays the first
+        if let Some(title_value) = document.title.as_ref().filter(|_| request.title_in_body) {
+            doc.add_text(body, title_value);
+        }
+
+        // every document must have a value of the unique key of the index
+        let key = match unique_key {
+            Some((name, key_field)) => {
+                match unique_key::value(name, &document, &content_hash_value) {
+                    Some(key) => {
+                        if unique_key::is_separate_field(name) {
+                            doc.add_text(key_field, &key);
+                        }
+                        Some(key)
+                    }
+                    None => {
+                        let err_msg = format!("Missing value of the unique key '{name}'");
+                        error!(
+                            document_number = index + 1,
+                            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                            "{}", &err_msg
+                        );
+                        results.push(DocumentResult {
+                            filename,
+                            status: "failed".to_string(),
+                            error: Some(err_msg),
+                        });
+                        continue;
+                    }
+                }
+            }
+            None => None,
+        };
+
+        // replace the document indexed before with the same key, if any. The deletion only
+        // applies to the documents added before it.
+        if let (Some((_, key_field)), Some(key)) = (unique_key, &key) {
+            index_writer.delete_term(Term::from_field_text(key_field, key));
+        }
+
+        if let Err(e) = index_writer.add_document(doc) {
+            error!(
+                document_number = index + 1,
+                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
+                error = %e,
+                "Failed to add document to index"
+            );
+            results.push(DocumentResult {
+                filename,
+                status: "failed".to_string(),
+                error: Some(format!("Failed to add to index: {e}")),
+            });
+            continue;
+        }
+
+        if request.dedup {
+            indexed_hashes.insert(content_hash_value);
+        }
+        if let Some(key) = key {
+            if let Some(position) = key_positions.insert(key, results.len()) {
+                info!(
+                    document_number = index + 1,
+                    "Document replaces an earlier document with the same unique key"
+                );
+                results[position].status = "replaced".to_string();
+            }
+        }
+
+        info!("Document processed successfully");
+        results.push(DocumentResult {
+            filename,
+            status: "indexed".to_string(),
+            error: None,
+        });
+
+        // bound the work lost if the indexing of a long stream of documents fails
+        added += 1;
+        if commit_interval.is_some_and(|commit_interval| added % commit_interval == 0) {
+            info!(documents = added, "Committing the documents indexed so far");
+            if let Err(e) = index_meta::commit_with_metadata(&mut index_writer, &metadata(&results))
+            {
+                error!(error = %e, "Failed to commit the documents indexed so far");
+                results.push(DocumentResult {
+                    filename: None,
+                    status: "failed".to_string(),
+                    error: Some(format!("Failed to commit index: {e}")),
+                });
+                break;
+            }
+        }
+    }
+
+    // Commit index, along with the metadata of the index
+    info!("Committing index");
+    let metadata = metadata(&results);
+    if let Err(e) = index_meta::commit_with_metadata(&mut index_writer, &metadata) {
+        error!(error = %e, "Failed to commit index");
+        drop(index_writer);
+        if created_index_dir {
+            remove_failed_index(&index_path);
+        }
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        );
+    }
+
+    // drop any stale reader cached under the same name. The next search opens a reader on the
+    // last commit, so the documents are searchable as soon as the response is returned.
+    reader_cache::invalidate(&index_name);
+
+    info!(
+        total_documents = results.len(),
+        successful = results.iter().filter(|r| r.status == "indexed").count(),
+        failed = results.iter().filter(|r| r.status == "failed").count(),
+        "JSON processing completed"
+    );
+
+    // generate download url for index file
+    let url = download_url(
+        DOWNLOAD_URL_PREFIX.get().unwrap(),
+        DOWNLOAD_URL_SCHEME.get().map(String::as_str),
+        &index_name,
+    );
+    info!(url = %url, "Download URL generated");
+
+    (
+        StatusCode::OK,
+        Json(IndexResponse {
+            results,
+            index_name: Some(index_name),
+            download_url: Some(url),
+            provenance: metadata.provenance,
+        }),
+    )
+}
+
+// Response of an indexing request whose blocking task panicked or was cancelled
+pub(crate) fn indexing_task_failed(e: tokio::task::JoinError) -> (StatusCode, Json<IndexResponse>) {
+    let err_msg = format!("Indexing failed: {e}");
+
+    error!("{}", &err_msg);
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(IndexResponse {
+            results: vec![DocumentResult {
+                filename: None,
+                status: "failed".to_string(),
+                error: Some(err_msg),
+            }],
+            index_name: None,
+            download_url: None,
+            provenance: None,
+        }),
+    )
+}
+
+/// Directory storing the indexes, set by `--index-storage-dir` and resolved at startup. Until then,
+/// the default directory, relative to the working directory.
+pub(crate) fn index_storage_dir() -> PathBuf {
+    INDEX_STORAGE_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_INDEX_STORAGE_DIR))
+}
+
+// Remove the directory of an index that failed before its first successful commit, unless
+// `--keep-failed-indexes` is set
+fn remove_failed_index(index_path: &std::path::Path) {
+    if KEEP_FAILED_INDEXES.get().copied().unwrap_or(false) {
+        info!(path = %index_path.display(), "Keeping directory of failed index");
+        return;
+    }
+
+    match std::fs::remove_dir_all(index_path) {
+        Ok(_) => info!(path = %index_path.display(), "Removed directory of failed index"),
+        Err(e) => {
+            warn!(path = %index_path.display(), error = %e, "Failed to remove directory of failed index")
+        }
+    }
+}
+
+// Strip the leading UTF-8 byte order mark, which would otherwise be indexed as part of the first
+// token, and normalize line endings to `\n` if enabled
+pub(crate) fn normalize_text(content: String) -> String {
+    let normalize_line_endings = NORMALIZE_LINE_ENDINGS.get().copied().unwrap_or(false);
+
+    normalize_text_with(content, normalize_line_endings)
+}
+
+// `normalize_text`, normalizing the line endings if `normalize_line_endings`
+fn normalize_text_with(content: String, normalize_line_endings: bool) -> String {
+    let content = match content.strip_prefix('\u{feff}') {
+        Some(stripped) => stripped.to_string(),
+        None => content,
+    };
+
+    match normalize_line_endings {
+        true => content.replace("\r\n", "\n").replace('\r', "\n"),
+        false => content,
+    }
+}
+
+// Compute the SHA-256 hash of the document content, in lowercase hex
+fn compute_content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+// Check that the document does not have more than `max_fields` fields: its content, its title,
+// quality, tags and creation date if any, and each of its named values. The values of the `fields`
+// of a document are stored in a single JSON field, so they are counted before building it.
+fn check_field_count(document: &DocumentInput, max_fields: usize) -> Result<(), String> {
+    let builtin_fields = [
+        true,
+        document.title.is_some(),
+        document.quality.is_some(),
+        !document.tags.is_empty(),
+        document.created_at.is_some(),
+    ];
+    let field_count =
+        builtin_fields.iter().filter(|present| **present).count() + document.fields.len();
+    if field_count > max_fields {
+        return Err(format!(
+            "Document has {field_count} fields, exceeding the maximum of {max_fields}"
+        ));
+    }
+
+    Ok(())
+}
+
+// Process document content with the preprocessing pipeline, rejecting the documents left empty
+// or too long
+fn process_content(content: String, pipeline: &preprocess::Pipeline) -> Result<String, String> {
+    process_content_with(content, pipeline, MAX_CONTENT_CHARS.get().copied())
+}
+
+// Process document content as `process_content` does, with at most `max_chars` characters
+fn process_content_with(
+    content: String,
+    pipeline: &preprocess::Pipeline,
+    max_chars: Option<usize>,
+) -> Result<String, String> {
+    let content = pipeline.apply(content);
+    if content.trim().is_empty() {
+        return Err("Empty content is not allowed".to_string());
+    }
+    check_content_length(&content, max_chars)?;
+
+    Ok(content)
+}
+
+// Check that the content of a document does not exceed `max_chars` characters, if set
+fn check_content_length(content: &str, max_chars: Option<usize>) -> Result<(), String> {
+    let Some(max_chars) = max_chars else {
+        return Ok(());
+    };
+
+    // counting stops past the maximum, so that huge documents are not scanned entirely
+    if content.chars().nth(max_chars).is_some() {
+        return Err(format!(
+            "Content exceeds the maximum length of {max_chars} characters"
+        ));
+    }
+
+    Ok(())
+}
+
+// Check if a field is a file or text
+fn is_file_field(field: &axum::extract::multipart::Field<'_>) -> bool {
+    field.file_name().is_some() || field.content_type().is_some()
+}
+
+// How the text of an uploaded file is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextFormat {
+    Plain,
+    Html,
+    Pdf,
+}
+
+impl TextFormat {
+    // format of an uploaded file, from its content type or the extension of its name
+    fn detect(content_type: &str, filename: Option<&str>) -> Self {
+        if pdf::is_pdf(content_type, filename) {
+            Self::Pdf
+        } else if html::is_html(content_type, filename) {
+            Self::Html
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+// Validate content type
+fn is_valid_content_type(content_type: &str) -> bool {
+    matches!(
+        content_type,
+        "text/plain"
+            | "text/markdown"
+            | "text/html"
+            | "text/csv"
+            | "text/tab-separated-values"
+            | "application/pdf"
+            | "application/octet-stream" // Sometimes file uploads might not have the correct content-type
+    )
+}
+
+// Add the query handler function
+async fn query_handler(
+    Json(mut request): Json<QueryRequest>,
+) -> Result<axum::response::Response, ServerError> {
+    let index_name = match request.indexes.is_empty() {
+        true => Some(request.index.clone()).filter(|index| !index.is_empty()),
+        false => Some(request.indexes.join(",")),
+    };
+
+    // a search returns at least one hit, and at most the maximum of the server
+    let mut clamp_warning = None;
+    if let Some(top_k) = request.top_k.filter(|top_k| *top_k > 0) {
+        let clamped = clamp_top_k(top_k);
+        if clamped < top_k {
+            clamp_warning = Some(format!(
+                "`top_k` {top_k} exceeds the maximum of the server, {clamped} hits at most are returned"
+            ));
+            request.top_k = Some(clamped);
+        }
+    }
+
+    if request.top_k == Some(0) {
+        let err_msg = "`top_k` must be at least 1".to_string();
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    if let Some(field) = request
+        .return_fields
+        .iter()
+        .find(|field| !QueryRequest::RETURNABLE_FIELDS.contains(&field.as_str()))
+    {
+        let err_msg = format!(
+            "Invalid return field '{field}'. Expected one of: {}",
+            QueryRequest::RETURNABLE_FIELDS.join(", ")
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // the fields are left out once the hits are merged, which needs their scores
+    let return_fields = std::mem::take(&mut request.return_fields);
+
+    // searching is blocking work, kept off the async runtime
+    let search = move || match request.indexes.is_empty() {
+        true => search_index(request),
+        false => multi_index::search_indexes(request),
+    };
+    let mut response = match request_id::spawn_blocking(search).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(e),
+        Err(e) => {
+            let err_msg = format!("Search failed: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    response.warnings.extend(clamp_warning);
+
+    let outcome = RequestOutcome {
+        index_name,
+        successful: response.hits.len(),
+        failed: 0,
+    };
+    let response = response.to_json(&return_fields).map_err(|e| {
+        let err_msg = format!("Failed to serialize the search response: {e}");
+
+        error!("{}", &err_msg);
+
+        ServerError::Operation(err_msg)
+    })?;
+    let mut response = Json(response).into_response();
+    response.extensions_mut().insert(outcome);
+    Ok(response)
+}
+
+// Search an index
+fn search_index(request: QueryRequest) -> Result<QueryResponse, ServerError> {
+    let start = Instant::now();
+
+    // time budget of the search, bounded by the maximum of the server
+    let max_timeout = MAX_SEARCH_TIMEOUT.get().copied();
+    let timeout = match request.timeout_ms.map(Duration::from_millis) {
+        Some(timeout) => Some(max_timeout.map_or(timeout, |max_timeout| timeout.min(max_timeout))),
+        None => max_timeout,
+    };
+    let deadline = timeout.map(Deadline::after);
+
+    info!(
+        query = %request.query,
+        top_k = ?request.top_k,
+        "Received search request"
+    );
+
+    // validate highlight tags
+    let highlight_pre_tag = request.highlight_pre_tag.as_deref().unwrap_or("<b>");
+    let highlight_post_tag = request.highlight_post_tag.as_deref().unwrap_or("</b>");
+    for tag in [highlight_pre_tag, highlight_post_tag] {
+        if tag.chars().count() > MAX_HIGHLIGHT_TAG_CHARS {
+            let err_msg = format!(
+                "Highlight tag '{tag}' is too long. At most {MAX_HIGHLIGHT_TAG_CHARS} characters are allowed"
+            );
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    }
+
+    // validate score precision
+    if let Some(precision) = request.score_precision {
+        if precision > MAX_SCORE_PRECISION {
+            let err_msg = format!(
+                "Invalid score precision {precision}. At most {MAX_SCORE_PRECISION} decimal places are allowed"
+            );
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    }
+
+    // validate fuzzy distance
+    let fuzzy_distance = request.fuzzy_distance.unwrap_or(1);
+    if fuzzy_distance > MAX_FUZZY_DISTANCE {
+        let err_msg = format!(
+            "Invalid fuzzy distance {fuzzy_distance}. At most {MAX_FUZZY_DISTANCE} typos per word are allowed"
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // validate offset
+    if request.offset > MAX_SEARCH_OFFSET {
+        let err_msg = format!(
+            "Invalid offset {}. At most {MAX_SEARCH_OFFSET} hits can be skipped",
+            request.offset
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // resolve the index to search if the request does not name one
+    let requested_index = match Some(request.index.clone())
+        .filter(|index| !index.is_empty())
+        .or_else(|| DEFAULT_INDEX.get().cloned())
+    {
+        Some(index) => index,
+        None => match only_index() {
+            Ok(index) => index,
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+    };
+
+    // reject names reaching outside of the index storage directory before touching the filesystem
+    if let Err(err_msg) = validate_index_name(&requested_index) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // resolve aliases to the name of the index they point to
+    let index_name = aliases::resolve(&requested_index);
+
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.exists() {
+        let err_msg = format!("Index '{requested_index}' does not exist");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    // get the index and its reader from the cache, opening the index if needed
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    // acquire searcher
+    let searcher = reader.searcher();
+
+    // get schema
+    let schema = index.schema();
+
+    // resolve the parameters omitted by the request from the defaults of the index
+    let index_metadata = index_meta::load(&index);
+    let search_defaults = index_metadata.search_defaults.unwrap_or_default();
+    let top_k = request
+        .top_k
+        .or(search_defaults
+            .top_k
+            .filter(|top_k| *top_k > 0)
+            .map(clamp_top_k))
+        .unwrap_or_else(default_top_k);
+    let min_score = request.min_score.or(search_defaults.min_score);
+
+    // get fields, which a malformed index may lack
+    let (title, body) = match (schema.get_field("title"), schema.get_field("body")) {
+        (Ok(title), Ok(body)) => (title, body),
+        (Err(e), _) | (_, Err(e)) => {
+            let err_msg = format!("Invalid index '{index_name}': {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    // get the quality field, missing in indexes created by older versions
+    let quality = schema.get_field(ranking::QUALITY_FIELD).ok();
+    let created_at = schema.get_field(ranking::CREATED_AT_FIELD).ok();
+
+    // validate the sort field
+    if let Some(sort_by) = &request.sort_by {
+        if let Err(err_msg) = ranking::validate_sort_field(&schema, sort_by) {
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    }
+    let ranking = Ranking {
+        sort_by: request
+            .sort_by
+            .clone()
+            .map(|sort_by| (sort_by, request.order)),
+        quality_weight: request.quality_weight,
+    };
+
+    // validate the fields statistics are computed for
+    let stats_collector = match request.stats.is_empty() {
+        true => None,
+        false => match FieldStatsCollector::new(&schema, &request.stats) {
+            Ok(collector) => Some(collector),
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+    };
+
+    // get the field hits are deduplicated by, if deduplication is requested
+    let dedup_field = match request.dedup_by {
+        Some(DedupBy::ContentHash) => match schema.get_field("content_hash") {
+            Ok(field) => Some(field),
+            Err(_) => {
+                let err_msg = format!(
+                    "Index '{requested_index}' has no `content_hash` field and cannot be deduplicated"
+                );
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+        Some(DedupBy::UniqueKey) => match index_metadata
+            .unique_key
+            .as_ref()
+            .and_then(|unique_key| schema.get_field(unique_key).ok())
+        {
+            Some(field) => Some(field),
+            None => {
+                let err_msg = format!(
+                    "Index '{requested_index}' has no unique key and cannot be deduplicated by it"
+                );
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+        None => None,
+    };
+
+    // validate the filter of the hits
+    let tag_filter = match tags::TagFilter::new(&schema, request.filter.as_ref()) {
+        Ok(tag_filter) => tag_filter,
+        Err(err_msg) => {
+            let err_msg = format!("Invalid filter for index '{requested_index}': {err_msg}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+
+    // make sure the analyzer of each default field is registered on the index, since the query
+    // parser analyzes the terms of each field with that field's own tokenizer
+    for field in [title, body] {
+        if let Err(e) = index.tokenizer_for_field(field) {
+            let err_msg = format!(
+                "Failed to get the tokenizer of field `{}`: {e}",
+                schema.get_field_name(field)
+            );
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    // fields searched by the words of the query without a field reference
+    let default_fields = match query_fields::default_fields(&request.fields, &schema) {
+        Ok(fields) => fields,
+        Err(err_msg) => {
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+
+    // create query parser. It resolves the tokenizer of each field from the index, so that e.g.
+    // a raw keyword field and a stemmed text field are analyzed differently in the same query.
+    let mut query_parser = QueryParser::for_index(&index, default_fields.clone());
+
+    // apply the field boosts of the index. They multiply the boosts of the query, e.g. `rust^2`.
+    let field_boosts: Vec<(Field, Score)> = [title, body]
+        .into_iter()
+        .filter_map(|field| {
+            let boost = index_metadata
+                .field_boosts
+                .get(schema.get_field_name(field))?;
+            Some((field, *boost))
+        })
+        .collect();
+    for &(field, boost) in &field_boosts {
+        query_parser.set_field_boost(field, boost);
+    }
+
+    // check the fields referenced by the query, e.g. `author` in `author:smith`. A fuzzy search
+    // ignores the query syntax, field references included.
+    let unknown_fields = match request.fuzzy {
+        true => Vec::new(),
+        false => query_fields::unknown_field_references(&request.query, &schema),
+    };
+    let query_text = if unknown_fields.is_empty() {
+        request.query.clone()
+    } else if UNKNOWN_FIELDS_AS_TEXT.get().copied().unwrap_or(false) {
+        warn!(
+            unknown_fields = ?unknown_fields.iter().map(|field| &field.name).collect::<Vec<_>>(),
+            "Searching references to unknown fields as plain words"
+        );
+        query_fields::neutralize(&request.query, &unknown_fields)
+    } else {
+        let err_msg = format!(
+            "Unknown field '{}' in query. Valid fields are: {}",
+            unknown_fields[0].name,
+            query_fields::queryable_fields(&schema).join(", ")
+        );
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    };
+
+    // the fuzzy search and the fallback search the title and the body, unless the request names
+    // its fields
+    let relaxed_fields = match request.fields.is_empty() {
+        true => vec![title, body],
+        false => default_fields.clone(),
+    };
+
+    // parse query. Its phrases, boolean operators and field references are handled by the parser.
+    // A fuzzy search matches each word of the query instead, allowing typos.
+    let parsed = match request.fuzzy {
+        true => build_relaxed_query(
+            &index,
+            &relaxed_fields,
+            &field_boosts,
+            &request.query,
+            FallbackStrategy::Fuzzy,
+            fuzzy_distance,
+        )
+        .map_err(|e| format!("Failed to build fuzzy query: {e}"))
+        .and_then(|query| query.ok_or_else(|| "No searchable terms in query".to_string())),
+        false => query_parser
+            .parse_query(&query_text)
+            .map_err(|e| format!("Failed to parse query: {e}")),
+    };
+    let mut query = match parsed {
+        Ok(q) => match &tag_filter {
+            Some(tag_filter) => tag_filter.apply(q),
+            None => q,
+        },
+        Err(err_msg) => {
+            error!("{}", &err_msg);
+
+            return Err(ServerError::BadRequest(err_msg));
+        }
+    };
+
+    // execute search
+    info!("Executing search");
+    // the hits of the previous pages are collected as well, and skipped afterwards. Extra
+    // candidates are collected when deduplicating, so that enough distinct hits are left.
+    let offset = request.offset;
+    let page_end = offset.saturating_add(top_k);
+    let limit = match request.dedup_by {
+        Some(_) => page_end.saturating_mul(DEDUP_CANDIDATES_FACTOR),
+        None => page_end,
+    };
+    // the matching documents are counted, and their statistics collected, in the same pass
+    let (mut top_docs, (mut total_hits, mut stats)) = match ranking::collect_top_docs(
+        &searcher,
+        &*query,
+        limit,
+        &ranking,
+        (Count, stats_collector.clone()),
+        deadline.as_ref(),
+    ) {
+        Ok(collected) => collected,
+        Err(e) => {
+            let err_msg = format!("Search failed: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
         }
     };
 
-    // Create index writer
-    info!("Initializing index writer");
-    let mut index_writer = match index.writer(MEMORY_BUDGET_IN_BYTES) {
-        Ok(writer) => writer,
+    if let Some(min_score) = min_score {
+        top_docs.retain(|(score, _)| *score >= min_score);
+    }
+
+    // retry with a relaxed query if the primary search yields no hits, and had time to complete
+    let mut fallback_used = false;
+    let timed_out = deadline.as_ref().is_some_and(Deadline::expired);
+    if top_docs.is_empty() && request.fallback != FallbackStrategy::None && !timed_out {
+        info!(fallback = ?request.fallback, "No hits found, retrying with fallback query");
+
+        match build_relaxed_query(
+            &index,
+            &relaxed_fields,
+            &field_boosts,
+            &request.query,
+            request.fallback,
+            fuzzy_distance,
+        ) {
+            Ok(Some(fallback_query)) => {
+                let fallback_query = match &tag_filter {
+                    Some(tag_filter) => tag_filter.apply(fallback_query),
+                    None => fallback_query,
+                };
+                match ranking::collect_top_docs(
+                    &searcher,
+                    &*fallback_query,
+                    limit,
+                    &ranking,
+                    (Count, stats_collector),
+                    deadline.as_ref(),
+                ) {
+                    Ok((mut docs, (fallback_total_hits, fallback_stats))) => {
+                        if let Some(min_score) = min_score {
+                            docs.retain(|(score, _)| *score >= min_score);
+                        }
+                        top_docs = docs;
+                        total_hits = fallback_total_hits;
+                        stats = fallback_stats;
+                        query = fallback_query;
+                        fallback_used = true;
+                    }
+                    Err(e) => {
+                        let err_msg = format!("Fallback search failed: {e}");
+
+                        error!("{}", &err_msg);
+
+                        return Err(ServerError::Operation(err_msg));
+                    }
+                }
+            }
+            Ok(None) => {
+                info!("No searchable terms in query, skipping fallback");
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to build fallback query");
+            }
+        }
+    }
+
+    // order similarly relevant hits by length, if requested. Hits sorted by a field keep its order
+    if request.sort_by.is_none() {
+        ranking::prefer_length(&searcher, &mut top_docs, request.prefer_length);
+    }
+
+    // create snippet generator. Only the first `snippet_max_scan_chars` characters of the body
+    // are scanned, so matches beyond that length never show up in the snippet.
+    //
+    // The generator highlights the terms of the query, which were produced by the tokenizer of the
+    // body, and re-tokenizes the body with that same tokenizer, resolved from the index. Words the
+    // analyzer filters out, e.g. stop words, are thus never highlighted nor used to center the
+    // snippet. The fallback query is analyzed the same way, and the tokens of the body also locate
+    // the fragment of a snippet within the body.
+    let snippet_max_scan_chars = request
+        .snippet_max_scan_chars
+        .or_else(|| SNIPPET_MAX_SCAN_CHARS.get().copied())
+        .unwrap_or(usize::MAX);
+    let mut snippet_generator = match SnippetGenerator::create(&searcher, &*query, body)
+        .and_then(|generator| Ok((generator, index.tokenizer_for_field(body)?)))
+    {
+        Ok((mut generator, tokenizer)) => {
+            if let Some(max_chars) = request.snippet_max_chars {
+                generator.set_max_num_chars(max_chars);
+            }
+            Some((generator, tokenizer))
+        }
         Err(e) => {
-            error!(error = %e, "Failed to create index writer");
-            return Json(IndexResponse {
-                results,
-                index_name: None,
-                download_url: None,
+            warn!(error = %e, "Failed to create snippet generator");
+            None
+        }
+    };
+
+    // collect hits. The hits of the previous pages are skipped before retrieving their documents,
+    // unless deduplicating, as they are only known once their duplicates are collapsed.
+    let mut hits: Vec<SearchHit> = Vec::new();
+    let mut hit_positions: HashMap<String, usize> = HashMap::new();
+    let skipped = match dedup_field {
+        Some(_) => 0,
+        None => offset,
+    };
+    for (score, doc_address) in top_docs.into_iter().skip(skipped) {
+        let retrieved_doc: TantivyDocument = match searcher.doc(doc_address) {
+            Ok(doc) => doc,
+            Err(e) => {
+                let err_msg = format!("Failed to retrieve a matching document: {e}");
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        };
+
+        // collapse hits sharing the same key into the highest-scoring one
+        if let Some(dedup_field) = dedup_field {
+            let key_value = retrieved_doc
+                .get_first(dedup_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            if let Some(&position) = hit_positions.get(&key_value) {
+                if let Some(count) = hits[position].duplicate_count.as_mut() {
+                    *count += 1;
+                }
+                continue;
+            }
+            if hits.len() == page_end {
+                continue;
+            }
+            hit_positions.insert(key_value, hits.len());
+        }
+
+        let title_value = retrieved_doc
+            .get_first(title)
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        let body_value = retrieved_doc
+            .get_first(body)
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string();
+
+        info!(
+            score = score,
+            title = title_value,
+            body = body_value,
+            "Retrieved document"
+        );
+
+        let snippet = snippet_generator
+            .as_mut()
+            .and_then(|(generator, tokenizer)| {
+                let scanned = match body_value.char_indices().nth(snippet_max_scan_chars) {
+                    Some((end, _)) => &body_value[..end],
+                    None => body_value.as_str(),
+                };
+                let mut generated = generator.snippet(scanned);
+                if generated.is_empty() {
+                    return None;
+                }
+
+                // the snippet, and the byte range of its passage within the body
+                match request.snippet_mode {
+                    SnippetMode::Fragment => {
+                        let fragment = snippet::locate_fragment(scanned, &generated, tokenizer)?;
+                        generated.set_snippet_prefix_postfix(highlight_pre_tag, highlight_post_tag);
+                        Some((generated.to_html(), fragment))
+                    }
+                    SnippetMode::Sentences => {
+                        // expand the best fragment to the sentences around it
+                        let fragment = snippet::locate_fragment(scanned, &generated, tokenizer)?;
+                        let highlighted: Vec<_> = generated
+                            .highlighted()
+                            .iter()
+                            .map(|range| range.start + fragment.start..range.end + fragment.start)
+                            .collect();
+                        let passage = snippet::expand_to_sentences(
+                            &body_value,
+                            fragment,
+                            request.snippet_context_sentences,
+                        );
+                        let html = snippet::highlight_passage(
+                            &body_value,
+                            passage.clone(),
+                            &highlighted,
+                            highlight_pre_tag,
+                            highlight_post_tag,
+                        );
+                        Some((html, passage))
+                    }
+                }
             });
+        let snippet_offsets = snippet
+            .as_ref()
+            .filter(|_| request.snippet_offsets)
+            .map(|(_, passage)| snippet::char_offsets(&body_value, passage.clone()));
+        let snippet = snippet.map(|(html, _)| html);
+
+        let explanation = match request.explain {
+            true => explain::explain(&searcher, &*query, doc_address)
+                .map_err(|e| warn!(error = %e, "Failed to explain the score of a hit"))
+                .ok(),
+            false => None,
+        };
+
+        hits.push(SearchHit {
+            title: title_value,
+            content: body_value,
+            score: round_score(score as f64, request.score_precision),
+            index: index_name.clone(),
+            snippet,
+            snippet_offsets,
+            duplicate_count: dedup_field.map(|_| 0),
+            quality: quality.and_then(|quality| retrieved_doc.get_first(quality)?.as_f64()),
+            tags: tags::values(&schema, &retrieved_doc),
+            created_at: created_at
+                .and_then(|created_at| retrieved_doc.get_first(created_at)?.as_datetime())
+                .and_then(ranking::format_date),
+            explanation,
+        });
+    }
+
+    if dedup_field.is_some() {
+        hits.drain(..offset.min(hits.len()));
+    }
+
+    info!(hits = hits.len(), "Search completed successfully");
+
+    let field_stats = stats.map(|stats| request.stats.iter().cloned().zip(stats).collect());
+    let timed_out = deadline.as_ref().is_some_and(Deadline::expired);
+    if timed_out {
+        warn!(timeout_ms = ?timeout.map(|timeout| timeout.as_millis()), hits = hits.len(), "Search timed out, returning partial results");
+    }
+
+    // log slow searches, with the total number of matching documents to tell whether they are
+    // slow because of the number of matches or because of the complexity of the query. The count
+    // of a search that timed out covers only the documents collected before the timeout.
+    let elapsed = start.elapsed();
+    let slow_query_threshold = SLOW_QUERY_THRESHOLD.get().copied().unwrap_or_default();
+    if !slow_query_threshold.is_zero() && elapsed > slow_query_threshold {
+        let total_hits = (!timed_out).then_some(total_hits);
+        warn!(
+            target: "slow_query",
+            query = %request.query,
+            index = %index_name,
+            top_k = top_k,
+            elapsed_ms = elapsed.as_millis() as u64,
+            total_hits = ?total_hits,
+            fallback_used = fallback_used,
+            "Slow search"
+        );
+    }
+
+    Ok(QueryResponse {
+        hits,
+        error: None,
+        fallback_used,
+        field_stats,
+        timed_out,
+        total_hits: Some(total_hits),
+        took_ms: elapsed_ms(start),
+        warnings: Vec::new(),
+    })
+}
+
+// Number of hits of the searches without `top_k`, if the index has no default
+pub(crate) fn default_top_k() -> usize {
+    DEFAULT_TOP_K
+        .get()
+        .copied()
+        .unwrap_or(QueryRequest::DEFAULT_TOP_K)
+}
+
+// Lower `top_k` to the maximum number of hits of a search, if it exceeds it
+pub(crate) fn clamp_top_k(top_k: usize) -> usize {
+    let max_top_k = MAX_TOP_K.get().copied().unwrap_or(usize::MAX);
+    if top_k > max_top_k {
+        warn!(top_k, max_top_k, "Lowering top_k to the maximum");
+        return max_top_k;
+    }
+
+    top_k
+}
+
+// Milliseconds elapsed since `start`
+pub(crate) fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+// Name of the only index of the server, searched by the requests that do not name one
+pub(crate) fn only_index() -> Result<String, String> {
+    let index_storage_dir = index_storage_dir();
+
+    // staging directories of uploads and replaced indexes are hidden
+    let mut index_names: Vec<String> = std::fs::read_dir(&index_storage_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.'))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    match index_names.len() {
+        0 => Err("No index to search. Create an index first".to_string()),
+        1 => Ok(index_names.remove(0)),
+        _ => {
+            index_names.sort();
+            Err(format!(
+                "The request must name the index to search, since there are several: {}",
+                index_names.join(", ")
+            ))
+        }
+    }
+}
+
+// Round the score to the given number of decimal places, if any
+fn round_score(score: f64, precision: Option<u32>) -> f64 {
+    match precision {
+        Some(precision) => {
+            let factor = 10f64.powi(precision as i32);
+            (score * factor).round() / factor
+        }
+        None => score,
+    }
+}
+
+// Build a relaxed query matching any of the words of `query`, with the boosts of the index. It is
+// used when the primary query yields no hits, and for fuzzy searches, whose words may have up to
+// `fuzzy_distance` typos. Returns `None` if the query contains no searchable terms.
+fn build_relaxed_query(
+    index: &Index,
+    fields: &[Field],
+    field_boosts: &[(Field, Score)],
+    query: &str,
+    strategy: FallbackStrategy,
+    fuzzy_distance: u8,
+) -> tantivy::Result<Option<Box<dyn Query>>> {
+    let mut subqueries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    for &field in fields {
+        // analyze the query with the tokenizer of the field, which also drops query syntax
+        let mut tokenizer = index.tokenizer_for_field(field)?;
+        let mut token_stream = tokenizer.token_stream(query);
+        while let Some(token) = token_stream.next() {
+            let term = Term::from_field_text(field, &token.text);
+            let subquery: Box<dyn Query> = match strategy {
+                FallbackStrategy::Fuzzy => {
+                    Box::new(FuzzyTermQuery::new(term, fuzzy_distance, true))
+                }
+                FallbackStrategy::Or => {
+                    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))
+                }
+                FallbackStrategy::None => return Ok(None),
+            };
+            let subquery = match field_boosts.iter().find(|(boosted, _)| *boosted == field) {
+                Some(&(_, boost)) => Box::new(BoostQuery::new(subquery, boost)),
+                None => subquery,
+            };
+            subqueries.push((Occur::Should, subquery));
+        }
+    }
+
+    match subqueries.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(Box::new(BooleanQuery::new(subqueries)))),
+    }
+}
+
+/// Query parameters of the `GET /v1/index/download/{index_name}` endpoint
+#[derive(Debug, serde::Deserialize)]
+struct DownloadParams {
+    /// Compression of the archive, `gzip` by default
+    #[serde(default)]
+    format: archive::ArchiveFormat,
+}
+
+// download index file
+async fn download_index_file_handler(
+    Path(index_name): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<DownloadParams>,
+    headers: http::HeaderMap,
+) -> Result<axum::response::Response, ServerError> {
+    info!(
+        index_name = %index_name,
+        format = ?params.format,
+        range = ?headers.get(http::header::RANGE),
+        "Received index file download request"
+    );
+
+    // reject names reaching outside of the index storage directory before touching the filesystem
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // limit the number of concurrent downloads. The permit is held until the archive is sent.
+    let permit = match DOWNLOAD_SEMAPHORE.get() {
+        Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                let err_msg = "Too many concurrent downloads, please retry later";
+                warn!(index_name = %index_name, "{}", err_msg);
+                return Ok((
+                    [(
+                        http::header::RETRY_AFTER,
+                        DOWNLOAD_RETRY_AFTER_SECS.to_string(),
+                    )],
+                    ServerError::Unavailable(err_msg.to_string()),
+                )
+                    .into_response());
+            }
+        },
+        None => None,
+    };
+
+    let index_storage_dir = index_storage_dir();
+    let index_path = index_storage_dir.as_path().join(&index_name);
+
+    // Check if index exists
+    if !index_path.exists() {
+        let err_msg = format!("Index '{index_name}' not found");
+        error!(
+            index_name = %index_name,
+            path = %index_path.display(),
+            "Index directory not found"
+        );
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    info!("Found index directory");
+
+    // Prepare compression. Each format has its own cached archive.
+    let compressed_filename = params.format.file_name(&index_name);
+    let compressed_index_path = index_storage_dir.as_path().join(&compressed_filename);
+
+    // the cached archive is reused unless the index was written after it was built, e.g. when the
+    // index was recreated under the same name, in which case it is rebuilt
+    if !archive_is_fresh(&compressed_index_path, &index_path) {
+        info!(format = ?params.format, "Starting index compression");
+
+        // the archive is built under a temporary name and then renamed, so that downloads of the
+        // previous archive in progress keep reading it
+        let temp_archive_path =
+            index_storage_dir.join(format!(".{compressed_filename}.{}", uuid::Uuid::new_v4()));
+
+        // compressing is blocking work
+        let format = params.format;
+        let source_path = index_path.clone();
+        let target_path = temp_archive_path.clone();
+        let built =
+            request_id::spawn_blocking(move || archive::build(&source_path, &target_path, format))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|built| built.map_err(|e| e.to_string()));
+        if let Err(e) = built {
+            let err_msg = format!("Failed to compress index directory: {e}");
+            error!(
+                error = %e,
+                source = %index_path.display(),
+                target = %temp_archive_path.display(),
+                "Failed to compress index directory"
+            );
+            let _ = std::fs::remove_file(&temp_archive_path);
+            return Err(ServerError::Operation(err_msg));
+        }
+
+        if let Err(e) = std::fs::rename(&temp_archive_path, &compressed_index_path) {
+            let err_msg = format!("Failed to store the compressed index file: {e}");
+            error!(
+                error = %e,
+                path = %compressed_index_path.display(),
+                "Failed to rename compressed file"
+            );
+            let _ = std::fs::remove_file(&temp_archive_path);
+            return Err(ServerError::Operation(err_msg));
+        }
+    }
+
+    info!("Index compression completed");
+
+    // Open compressed file
+    let mut file = match tokio::fs::File::open(&compressed_index_path).await {
+        Ok(file) => file,
+        Err(e) => {
+            let err_msg = format!("Failed to open the compressed file: {e}");
+            error!(
+                error = %e,
+                path = %compressed_index_path.display(),
+                "Failed to open compressed file"
+            );
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    let content_length = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            let err_msg = format!("Failed to read the compressed file metadata: {e}");
+            error!(
+                error = %e,
+                path = %compressed_index_path.display(),
+                "Failed to read file metadata"
+            );
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    // a single byte range is sent on request, so that interrupted downloads can be resumed
+    let (status, range) = match byte_range::parse(headers.get(http::header::RANGE), content_length)
+    {
+        byte_range::ByteRange::Full => (StatusCode::OK, None),
+        byte_range::ByteRange::Partial(range) => (StatusCode::PARTIAL_CONTENT, Some(range)),
+        byte_range::ByteRange::Unsatisfiable => {
+            let err_msg = format!(
+                "The requested range is not satisfiable, the archive has {content_length} bytes"
+            );
+            warn!(index_name = %index_name, "{}", &err_msg);
+            return Ok((
+                [(
+                    http::header::CONTENT_RANGE,
+                    format!("bytes */{content_length}"),
+                )],
+                ServerError::RangeNotSatisfiable(err_msg),
+            )
+                .into_response());
+        }
+    };
+    let (start, length) = match &range {
+        Some(range) => (*range.start(), range.end() - range.start() + 1),
+        None => (0, content_length),
+    };
+    if start > 0 {
+        if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
+            let err_msg = format!("Failed to seek in the compressed file: {e}");
+            error!(
+                error = %e,
+                path = %compressed_index_path.display(),
+                start = start,
+                "Failed to seek in compressed file"
+            );
+            return Err(ServerError::Operation(err_msg));
         }
-    };
+    }
+
+    // Prepare response, streaming the file so that large archives are not buffered in memory. The
+    // download permit is held until the stream ends.
+    let content_type = params.format.content_type();
+    let content_disposition = format!("attachment; filename=\"{compressed_filename}\"");
+    let body =
+        axum::body::Body::from_stream(ReaderStream::new(file.take(length)).map(move |chunk| {
+            let _permit = &permit;
+            chunk
+        }));
 
-    // Add documents to index
     info!(
-        document_count = documents.len(),
-        "Starting document indexing"
+        index_name = %index_name,
+        content_type = %content_type,
+        content_length = length,
+        range = ?range,
+        filename = %compressed_filename,
+        "Prepared download response"
     );
-    for (i, document) in documents.iter().enumerate() {
-        let doc = doc!(
-            title => document.title.clone().unwrap_or("Unknown".to_string()),
-            body => document.content.clone(),
+
+    let mut builder = axum::response::Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .header("Content-Disposition", content_disposition.as_str())
+        .header("Content-Length", length.to_string().as_str())
+        .header("Accept-Ranges", "bytes");
+    if let Some(range) = &range {
+        builder = builder.header(
+            "Content-Range",
+            format!("bytes {}-{}/{content_length}", range.start(), range.end()).as_str(),
         );
-        if let Err(e) = index_writer.add_document(doc) {
+    }
+
+    match builder.body(body) {
+        Ok(response) => {
+            info!("Returned download response");
+            Ok(response)
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to build response: {e}");
             error!(
-                document_number = i + 1,
                 error = %e,
-                "Failed to add document to index"
+                index_name = %index_name,
+                "Failed to build response"
             );
-            continue;
+            Err(ServerError::Operation(err_msg))
         }
-        info!(
-            document_number = i + 1,
-            total = documents.len(),
-            "Document added to index"
-        );
     }
+}
 
-    // Commit index
-    info!("Committing index");
-    if let Err(e) = index_writer.commit() {
-        error!(error = %e, "Failed to commit index");
-        return Json(IndexResponse {
-            results,
-            index_name: None,
-            download_url: None,
-        });
-    }
+// Whether the archive at `archive_path` was built after the last write of the index at
+// `index_path`. Modification times equal to the archive's count as later, so that an archive is
+// never reused when the order cannot be told.
+fn archive_is_fresh(archive_path: &std::path::Path, index_path: &std::path::Path) -> bool {
+    let Ok(archived_at) = std::fs::metadata(archive_path).and_then(|metadata| metadata.modified())
+    else {
+        return false;
+    };
 
-    // generate download url for index file
-    let url = {
-        // get the socket address of request
-        let download_url_prefix = DOWNLOAD_URL_PREFIX.get().unwrap();
+    newest_modification(index_path).is_some_and(|modified| modified < archived_at)
+}
 
-        let host = match download_url_prefix.port() {
-            Some(port) => {
-                format!("{}:{}", download_url_prefix.host_str().unwrap(), port)
-            }
-            None => download_url_prefix.host_str().unwrap().to_string(),
+// Latest modification time of the files under `path`, or `None` if one cannot be read
+fn newest_modification(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    let mut newest = std::fs::metadata(path).ok()?.modified().ok()?;
+    for entry in std::fs::read_dir(path).ok()? {
+        let entry = entry.ok()?;
+        let modified = match entry.file_type().ok()?.is_dir() {
+            true => newest_modification(&entry.path())?,
+            false => entry.metadata().ok()?.modified().ok()?,
         };
+        newest = newest.max(modified);
+    }
 
-        format!(
-            "{}://{}/v1/index/download/{}",
-            download_url_prefix.scheme(),
-            host,
-            &index_name,
-        )
-    };
-    info!(url = %url, "Download URL generated");
-
-    Json(IndexResponse {
-        results,
-        index_name: Some(index_name),
-        download_url: Some(url),
-    })
+    Some(newest)
 }
 
-// Helper function to process field content
-async fn process_field_content(
-    results: &mut Vec<DocumentResult>,
-    documents: &mut Vec<DocumentInput>,
-    field: axum::extract::multipart::Field<'_>,
-    filename: Option<String>,
-) {
-    match field.bytes().await {
-        Ok(bytes) => {
-            info!(size_bytes = bytes.len(), "Content read successfully");
-            match String::from_utf8(bytes.to_vec()) {
-                Ok(content) => {
-                    let document = DocumentInput {
-                        content: content.clone(),
-                        title: None,
-                    };
-                    documents.push(document);
-
-                    match process_content(&content) {
-                        Ok(_) => {
-                            info!("Content processed successfully");
-                            results.push(DocumentResult {
-                                filename: filename.clone(),
-                                status: "indexed".to_string(),
-                                error: None,
-                            });
-                        }
-                        Err(e) => {
-                            error!(
-                                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                                error = %e,
-                                "Content processing failed"
-                            );
-                            results.push(DocumentResult {
-                                filename,
-                                status: "failed".to_string(),
-                                error: Some(e.to_string()),
-                            });
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!(
-                        filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                        error = %e,
-                        "UTF-8 decoding failed"
-                    );
-                    results.push(DocumentResult {
-                        filename: filename.clone(),
-                        status: "failed".to_string(),
-                        error: Some("Invalid UTF-8 content".to_string()),
-                    });
-                }
-            }
-        }
-        Err(e) => {
-            error!(
-                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                error = %e,
-                "Failed to read field content"
-            );
-            results.push(DocumentResult {
-                filename,
-                status: "failed".to_string(),
-                error: Some(format!("Failed to read file: {e}")),
-            });
-        }
+// delete an index, along with its cached archive
+async fn delete_index_handler(
+    Path(index_name): Path<String>,
+) -> Result<Json<serde_json::Value>, ServerError> {
+    info!(index_name = %index_name, "Received index deletion request");
+
+    // reject names reaching outside of the index storage directory before touching the filesystem
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
     }
-}
 
-// Process JSON input
-async fn process_json(request: IndexRequest) -> Json<IndexResponse> {
-    info!(
-        document_count = request.documents.len(),
-        "Starting JSON request processing"
-    );
-    let mut results = Vec::new();
+    let index_storage_dir = index_storage_dir();
+    let index_path = index_storage_dir.join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{index_name}' not found");
 
-    // Create index directory
-    info!("Starting index creation");
-    let index_storage_dir = std::env::current_dir().unwrap().join(INDEX_STORAGE_DIR);
-    let index_name = match request.index {
-        Some(name) => name,
-        None => format!("index-{}", uuid::Uuid::new_v4()),
-    };
-    let index_path = index_storage_dir.as_path().join(&index_name);
-    if !index_path.exists() {
-        debug!(path = %index_path.display(), "Creating index directory");
-        std::fs::create_dir_all(&index_path).unwrap();
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
     }
 
-    // Define schema
-    info!("Defining index schema");
-    let mut schema_builder = Schema::builder();
-    let title = schema_builder.add_text_field("title", TEXT | STORED);
-    let body = schema_builder.add_text_field("body", TEXT | STORED);
-    let schema = schema_builder.build();
+    // release the cached reader before its files are removed
+    reader_cache::invalidate(&index_name);
 
-    // Create index
-    info!("Creating new index");
-    let index = match Index::create_in_dir(&index_path, schema.clone()) {
-        Ok(index) => index,
+    if let Err(e) = tokio::fs::remove_dir_all(&index_path).await {
+        let err_msg = format!("Failed to delete index: {e}");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::Operation(err_msg));
+    }
+
+    // the archives are only created on the first download
+    archive::remove_cached(&index_storage_dir, &index_name);
+
+    // aliases pointing to the deleted index would otherwise resolve to nothing
+    let removed_aliases = match aliases::remove_pointing_to(&index_name) {
+        Ok(removed_aliases) => removed_aliases,
         Err(e) => {
-            error!(error = %e, "Failed to create index");
-            return Json(IndexResponse {
-                results,
-                index_name: None,
-                download_url: None,
-            });
+            let err_msg =
+                format!("Index '{index_name}' deleted, but failed to remove its aliases: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
         }
     };
 
-    // Create index writer
-    info!("Initializing index writer");
-    let mut index_writer = match index.writer(MEMORY_BUDGET_IN_BYTES) {
-        Ok(writer) => writer,
-        Err(e) => {
-            error!(error = %e, "Failed to create index writer");
-            return Json(IndexResponse {
-                results,
-                index_name: None,
-                download_url: None,
+    info!(index_name = %index_name, removed_aliases = ?removed_aliases, "Index deleted");
+
+    Ok(Json(serde_json::json!({
+        "deleted": true,
+        "index_name": index_name,
+        "removed_aliases": removed_aliases,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::collector::Count;
+    use test_support::{index, search, unique_index_name};
+
+    // an index of a single document with a `body` field, and its field
+    fn body_index(content: &str) -> (Index, Field) {
+        let mut schema = Schema::builder();
+        let body = schema.add_text_field("body", TEXT);
+        let index = Index::create_in_ram(schema.build());
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer.add_document(doc!(body => content)).unwrap();
+        writer.commit().unwrap();
+
+        (index, body)
+    }
+
+    fn relaxed_hits(index: &Index, body: Field, query: &str, strategy: FallbackStrategy) -> usize {
+        let query = build_relaxed_query(index, &[body], &[], query, strategy, 1)
+            .unwrap()
+            .unwrap();
+        let searcher = index.reader().unwrap().searcher();
+
+        searcher.search(&*query, &Count).unwrap()
+    }
+
+    #[test]
+    fn or_fallback_matches_any_word_and_drops_query_syntax() {
+        let (index, body) = body_index("The quick brown fox");
+
+        assert_eq!(
+            relaxed_hits(&index, body, "+quick +zebra", FallbackStrategy::Or),
+            1
+        );
+        assert_eq!(relaxed_hits(&index, body, "quikc", FallbackStrategy::Or), 0);
+    }
+
+    #[test]
+    fn fuzzy_fallback_matches_words_with_typos() {
+        let (index, body) = body_index("The quick brown fox");
+
+        assert_eq!(
+            relaxed_hits(&index, body, "quikc", FallbackStrategy::Fuzzy),
+            1
+        );
+        assert_eq!(
+            relaxed_hits(&index, body, "quxxk", FallbackStrategy::Fuzzy),
+            0
+        );
+    }
+
+    #[test]
+    fn fallback_query_needs_searchable_terms() {
+        let (index, body) = body_index("The quick brown fox");
+
+        let build = |query, strategy| {
+            build_relaxed_query(&index, &[body], &[], query, strategy, 1).unwrap()
+        };
+        assert!(build("!!! ???", FallbackStrategy::Or).is_none());
+        assert!(build("quick", FallbackStrategy::None).is_none());
+    }
+
+    #[tokio::test]
+    async fn fallback_used_reports_the_relaxed_search() {
+        let index_name = unique_index_name("fallback");
+        let (status, response) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "The quick brown fox" }],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.index_name.as_deref(), Some(index_name.as_str()));
+
+        let search = |query: &str, fallback: &str| {
+            search(serde_json::json!({ "index": index_name, "query": query, "fallback": fallback }))
+                .unwrap()
+        };
+        let relaxed = search("+quick +zebra", "or");
+        assert!(relaxed.fallback_used);
+        assert_eq!(relaxed.hits.len(), 1);
+
+        let direct = search("quick", "or");
+        assert!(!direct.fallback_used);
+        assert_eq!(direct.hits.len(), 1);
+
+        let unrelaxed = search("+quick +zebra", "none");
+        assert!(!unrelaxed.fallback_used);
+        assert!(unrelaxed.hits.is_empty());
+    }
+
+    #[test]
+    fn leading_bom_is_stripped() {
+        let text = "\u{feff}Hello\r\nWorld\rEnd".to_string();
+
+        assert_eq!(
+            normalize_text_with(text.clone(), false),
+            "Hello\r\nWorld\rEnd"
+        );
+        assert_eq!(normalize_text_with(text, true), "Hello\nWorld\nEnd");
+        assert_eq!(
+            normalize_text_with("Inner\u{feff}mark".to_string(), false),
+            "Inner\u{feff}mark"
+        );
+    }
+
+    #[tokio::test]
+    async fn first_word_after_a_bom_is_searchable() {
+        let index_name = unique_index_name("bom");
+        index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "\u{feff}Exported from a Windows editor" }],
+        }))
+        .await;
+
+        let found =
+            search(serde_json::json!({ "query": "exported", "index": index_name })).unwrap();
+        assert_eq!(found.hits.len(), 1);
+        assert_eq!(found.hits[0].content, "Exported from a Windows editor");
+    }
+
+    #[test]
+    fn each_field_of_a_query_is_analyzed_by_its_own_tokenizer() {
+        let mut schema = Schema::builder();
+        let sku = schema.add_text_field("sku", STRING);
+        let body_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer("en_stem")
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let body = schema.add_text_field("body", body_options);
+        let index = Index::create_in_ram(schema.build());
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer
+            .add_document(doc!(sku => "SKU-Run-1", body => "Running shoes"))
+            .unwrap();
+        writer
+            .add_document(doc!(sku => "SKU-Run-2", body => "Running socks"))
+            .unwrap();
+        writer.commit().unwrap();
+        let searcher = index.reader().unwrap().searcher();
+
+        let query_parser = QueryParser::for_index(&index, vec![body]);
+        let hits = |query: &str| {
+            let query = query_parser.parse_query(query).unwrap();
+            searcher.search(&query, &Count).unwrap()
+        };
+        // the keyword is matched as a whole, as written, and the words of the body by their stem
+        assert_eq!(hits(r#"+sku:"SKU-Run-1" +run"#), 1);
+        assert_eq!(hits(r#"+sku:"sku-run-1" +run"#), 0);
+        assert_eq!(hits(r#"+sku:"SKU-Run" +run"#), 0);
+    }
+
+    #[tokio::test]
+    async fn sorting_by_quality_overrides_relevance() {
+        let index_name = unique_index_name("quality");
+        index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "rust rust rust", "title": "Relevant", "quality": 0.1 },
+                { "content": "rust and a lot of other words", "title": "Popular", "quality": 0.9 },
+            ],
+        }))
+        .await;
+
+        let titles = |request: serde_json::Value| {
+            search(request)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|hit| (hit.title, hit.quality))
+                .collect::<Vec<_>>()
+        };
+        let by_relevance = titles(serde_json::json!({ "query": "rust", "index": index_name }));
+        assert_eq!(by_relevance[0], ("Relevant".to_string(), Some(0.1)));
+
+        let by_quality = titles(serde_json::json!({
+            "query": "rust",
+            "index": index_name,
+            "sort_by": "quality",
+            "order": "desc",
+        }));
+        assert_eq!(
+            by_quality,
+            [
+                ("Popular".to_string(), Some(0.9)),
+                ("Relevant".to_string(), Some(0.1))
+            ]
+        );
+
+        let blended = titles(serde_json::json!({
+            "query": "rust",
+            "index": index_name,
+            "quality_weight": 100.0,
+        }));
+        assert_eq!(blended[0].0, "Popular");
+    }
+
+    // a multipart request naming the index `index_name`, whose connection drops in the middle of
+    // its second file
+    async fn interrupted_multipart(index_name: &str) -> Multipart {
+        use futures_util::StreamExt;
+
+        test_support::storage_dir();
+        let complete = format!(
+            "--BOUNDARY\r\nContent-Disposition: form-data; name=\"index\"\r\n\r\n{index_name}\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"first.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nThe first file is complete\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"second.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nThe second file is cut"
+        );
+        // the parser reports an error of the body as soon as it reads it, so the connection only
+        // drops once the complete fields had time to be processed
+        let body = futures_util::stream::iter([Ok(axum::body::Bytes::from(complete))]).chain(
+            futures_util::stream::once(async {
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+            }),
+        );
+        let request = http::Request::builder()
+            .header(
+                http::header::CONTENT_TYPE,
+                "multipart/form-data; boundary=BOUNDARY",
+            )
+            .body(axum::body::Body::from_stream(body))
+            .unwrap();
+
+        Multipart::from_request(request, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn atomic_multipart_aborts_on_a_read_error() {
+        let index_name = unique_index_name("atomic");
+
+        let (status, Json(response)) =
+            process_multipart(interrupted_multipart(&index_name).await, true).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(response.index_name.is_none());
+        assert!(response.results.iter().all(|r| r.status == "failed"));
+        assert!(!test_support::storage_dir().join(&index_name).exists());
+    }
+
+    #[tokio::test]
+    async fn oversized_option_field_is_rejected_with_413() {
+        use futures_util::StreamExt;
+
+        test_support::storage_dir();
+        for name in ["index", "title", "delimiter", "has_header", "dedup"] {
+            let head =
+                format!("--BOUNDARY\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n");
+            // the value is larger than the default body limit of multipart requests, 2 MB, and
+            // arrives after the headers of the field, as over a network
+            let value = futures_util::stream::once(async {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok::<_, std::io::Error>(axum::body::Bytes::from("x".repeat(3_000_000)))
             });
+            let body = futures_util::stream::iter([Ok(axum::body::Bytes::from(head))]).chain(value);
+            let request = http::Request::builder()
+                .header(
+                    http::header::CONTENT_TYPE,
+                    "multipart/form-data; boundary=BOUNDARY",
+                )
+                .body(axum::body::Body::from_stream(body))
+                .unwrap();
+            let multipart = Multipart::from_request(request, &()).await.unwrap();
+
+            let (status, Json(response)) = process_multipart(multipart, true).await;
+
+            assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{name}");
+            assert!(response.index_name.is_none());
         }
-    };
+    }
 
-    // Process and index documents
-    let total = request.documents.len();
-    for (index, document) in request.documents.into_iter().enumerate() {
-        let filename = document.title.clone();
-        info!(
-            document_number = index + 1,
-            total = total,
-            filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-            content_length = document.content.len(),
-            "Processing document"
-        );
+    #[tokio::test]
+    async fn best_effort_multipart_keeps_the_complete_files() {
+        let index_name = unique_index_name("best-effort");
 
-        // Add document to index
-        let doc = doc!(
-            title => document.title.clone().unwrap_or("Unknown".to_string()),
-            body => document.content.clone(),
+        let (status, Json(response)) =
+            process_multipart(interrupted_multipart(&index_name).await, false).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.index_name.as_deref(), Some(index_name.as_str()));
+        assert_eq!(response.results[0].filename.as_deref(), Some("first.txt"));
+        assert_eq!(response.results[0].status, "indexed");
+        assert!(response.results[1..].iter().all(|r| r.status == "failed"));
+    }
+
+    #[test]
+    fn snippet_centers_on_the_content_words_of_the_query() {
+        use tantivy::tokenizer::{LowerCaser, SimpleTokenizer, StopWordFilter, TextAnalyzer};
+
+        let mut schema = Schema::builder();
+        let body_options = TextOptions::default()
+            .set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer("stopwords")
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+            .set_stored();
+        let body = schema.add_text_field("body", body_options);
+        let index = Index::create_in_ram(schema.build());
+        let stopwords = ["the", "of", "is", "it", "that", "for", "in"].map(String::from);
+        index.tokenizers().register(
+            "stopwords",
+            TextAnalyzer::builder(SimpleTokenizer::default())
+                .filter(LowerCaser)
+                .filter(StopWordFilter::remove(stopwords))
+                .build(),
         );
+        let content = "The end of the day is the time of the year for the rest of the team. \
+                       It is then that the quantum computer is in the lab.";
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer.add_document(doc!(body => content)).unwrap();
+        writer.commit().unwrap();
 
-        if let Err(e) = index_writer.add_document(doc) {
-            error!(
-                document_number = index + 1,
-                filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                error = %e,
-                "Failed to add document to index"
+        // the snippet is generated as the search does, from the query parsed with the analyzer of
+        // the body
+        let searcher = index.reader().unwrap().searcher();
+        let query = QueryParser::for_index(&index, vec![body])
+            .parse_query("the of the is the quantum")
+            .unwrap();
+        let mut generator = SnippetGenerator::create(&searcher, &*query, body).unwrap();
+        generator.set_max_num_chars(40);
+        let snippet = generator.snippet(content).to_html();
+
+        assert!(snippet.contains("<b>quantum</b>"), "{snippet}");
+        for stopword in ["the", "The", "of", "is"] {
+            assert!(
+                !snippet.contains(&format!("<b>{stopword}</b>")),
+                "{snippet}"
             );
-            results.push(DocumentResult {
-                filename,
-                status: "failed".to_string(),
-                error: Some(format!("Failed to add to index: {e}")),
-            });
-            continue;
         }
+    }
 
-        // Process content
-        match process_content(&document.content) {
-            Ok(_) => {
-                info!("Document processed successfully");
-                results.push(DocumentResult {
-                    filename,
-                    status: "indexed".to_string(),
-                    error: None,
-                });
-            }
-            Err(e) => {
-                error!(
-                    document_number = index + 1,
-                    filename = %filename.as_ref().unwrap_or(&"Unknown".to_string()),
-                    error = %e,
-                    "Document processing failed"
-                );
-                results.push(DocumentResult {
-                    filename,
-                    status: "failed".to_string(),
-                    error: Some(e.to_string()),
-                });
-            }
+    #[tokio::test]
+    async fn preferred_length_ranks_first_among_equally_relevant_hits() {
+        let index_name = unique_index_name("prefer-length");
+        index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "apple kiwi", "title": "Short" },
+                { "content": "apple watermelons", "title": "Long" },
+            ],
+        }))
+        .await;
+
+        let first = |prefer_length: &str| {
+            let request = serde_json::json!({
+                "query": "apple",
+                "index": index_name,
+                "prefer_length": prefer_length,
+            });
+            search(request).unwrap().hits[0].title.clone()
+        };
+        assert_eq!(first("short"), "Short");
+        assert_eq!(first("long"), "Long");
+    }
+
+    #[tokio::test]
+    async fn index_field_boosts_apply_to_every_search() {
+        let documents = serde_json::json!([
+            { "content": "A guide to the borrow checker", "title": "Rust" },
+            { "content": "Rust, rust and more rust in the body", "title": "Metals" },
+        ]);
+        // the boosts of an index apply to the fields searched, without any boost in the query
+        let top_title = |index_name: &str| {
+            let request = serde_json::json!({
+                "query": "title:rust OR body:rust",
+                "index": index_name,
+            });
+            search(request).unwrap().hits[0].title.clone()
+        };
+
+        let plain = unique_index_name("unboosted");
+        index(serde_json::json!({ "index": plain, "documents": documents })).await;
+        assert_eq!(top_title(&plain), "Metals");
+
+        let boosted = unique_index_name("boosted");
+        let (status, response) = index(serde_json::json!({
+            "index": boosted,
+            "documents": documents,
+            "field_boosts": { "title": 10.0 },
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.index_name.as_deref(), Some(boosted.as_str()));
+        assert_eq!(top_title(&boosted), "Rust");
+    }
+
+    #[test]
+    fn only_positive_boosts_of_default_fields_are_valid() {
+        let boosts = |field: &str, boost: f32| BTreeMap::from([(field.to_string(), boost)]);
+
+        assert!(validate_field_boosts(&boosts("title", 2.0)).is_ok());
+        assert!(validate_field_boosts(&boosts("content_hash", 2.0)).is_err());
+        assert!(validate_field_boosts(&boosts("body", 0.0)).is_err());
+        assert!(validate_field_boosts(&boosts("body", f32::NAN)).is_err());
+    }
+
+    #[tokio::test]
+    async fn document_with_an_indexed_url_key_replaces_the_earlier_one() {
+        let index_name = unique_index_name("upsert");
+        let (status, response) = index(serde_json::json!({
+            "index": index_name,
+            "unique_key": "url",
+            "documents": [
+                { "content": "Old page about Paris", "fields": { "url": "https://example.com/paris" } },
+                { "content": "Page about Rome", "fields": { "url": "https://example.com/rome" } },
+                { "content": "New page about Paris", "fields": { "url": "https://example.com/paris" } },
+                { "content": "Page without a URL" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let statuses: Vec<_> = response.results.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["replaced", "indexed", "indexed", "failed"]);
+        let found = search(serde_json::json!({ "query": "paris", "index": index_name })).unwrap();
+        assert_eq!(found.hits.len(), 1);
+        assert_eq!(found.hits[0].content, "New page about Paris");
+        let by_key = search(serde_json::json!({
+            "query": r#"url:"https://example.com/rome""#,
+            "index": index_name,
+        }))
+        .unwrap();
+        assert_eq!(by_key.hits.len(), 1);
+    }
+
+    // status of the response to an indexing request of `content_type` with `body`
+    async fn indexing_status(content_type: &str, body: &str) -> StatusCode {
+        test_support::storage_dir();
+        let request = http::Request::builder()
+            .header(http::header::CONTENT_TYPE, content_type)
+            .body(axum::body::Body::from(body.to_string()))
+            .unwrap();
+        let headers = request.headers().clone();
+
+        match index_document_handler(headers, request).await {
+            Ok(response) => response.status(),
+            Err(e) => e.into_response().status(),
         }
     }
 
-    // Commit index
-    info!("Committing index");
-    if let Err(e) = index_writer.commit() {
-        error!(error = %e, "Failed to commit index");
-        return Json(IndexResponse {
-            results,
-            index_name: None,
-            download_url: None,
-        });
+    #[tokio::test]
+    async fn indexing_failures_have_their_own_status() {
+        let documents = serde_json::json!({
+            "index": unique_index_name("status"),
+            "documents": [{ "content": "Status codes" }],
+        })
+        .to_string();
+
+        assert_eq!(
+            indexing_status("application/json", &documents).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            indexing_status("text/html", "<p>Status codes</p>").await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        assert_eq!(
+            indexing_status("application/json", "{\"documents\": [").await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            indexing_status("application/json", r#"{"documents": []}"#).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            indexing_status("multipart/form-data", "--missing-boundary").await,
+            StatusCode::BAD_REQUEST
+        );
     }
 
-    info!(
-        total_documents = results.len(),
-        successful = results.iter().filter(|r| r.status == "indexed").count(),
-        failed = results.iter().filter(|r| r.status == "failed").count(),
-        "JSON processing completed"
-    );
+    // number of documents of the downloaded index `archive` whose body matches `query`
+    fn archived_hits(archive: &[u8], query: &str) -> usize {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("index.tar.gz");
+        std::fs::write(&archive_path, archive).unwrap();
+        let index_path = dir.path().join("index");
+        tar::Archive::new(archive::open(&archive_path).unwrap())
+            .unpack(&index_path)
+            .unwrap();
 
-    // generate download url for index file
-    let url = {
-        // get the socket address of request
-        let download_url_prefix = DOWNLOAD_URL_PREFIX.get().unwrap();
+        let index = Index::open_in_dir(&index_path).unwrap();
+        let body = index.schema().get_field("body").unwrap();
+        let query = QueryParser::for_index(&index, vec![body])
+            .parse_query(query)
+            .unwrap();
+        index
+            .reader()
+            .unwrap()
+            .searcher()
+            .search(&*query, &Count)
+            .unwrap()
+    }
 
-        let host = match download_url_prefix.port() {
-            Some(port) => {
-                format!("{}:{}", download_url_prefix.host_str().unwrap(), port)
-            }
-            None => download_url_prefix.host_str().unwrap().to_string(),
+    #[tokio::test]
+    async fn recreated_index_is_archived_again() {
+        let index_name = unique_index_name("recreated");
+        let index_version = |content: &str| {
+            index(serde_json::json!({
+                "index": index_name,
+                "documents": [{ "content": content }],
+            }))
+        };
+        let download_body = |index_name: String| async move {
+            let response = download(&index_name, None).await;
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
         };
 
-        format!(
-            "{}://{}/v1/index/download/{}",
-            download_url_prefix.scheme(),
-            host,
-            &index_name,
-        )
-    };
-    info!(url = %url, "Download URL generated");
-
-    Json(IndexResponse {
-        results,
-        index_name: Some(index_name),
-        download_url: Some(url),
-    })
-}
+        assert_eq!(index_version("first version").await.0, StatusCode::OK);
+        let first = download_body(index_name.clone()).await;
+        assert_eq!(archived_hits(&first, "first"), 1);
 
-// Process document content
-fn process_content(content: &str) -> Result<(), String> {
-    // Add actual document processing logic here
-    // For now, just validate that content is not empty
-    if content.trim().is_empty() {
-        return Err("Empty content is not allowed".to_string());
+        std::fs::remove_dir_all(test_support::storage_dir().join(&index_name)).unwrap();
+        assert_eq!(index_version("second version").await.0, StatusCode::OK);
+        let second = download_body(index_name.clone()).await;
+        assert_eq!(archived_hits(&second, "second"), 1);
+        assert_eq!(archived_hits(&second, "first"), 0);
     }
-    Ok(())
-}
 
-// Check if a field is a file or text
-fn is_file_field(field: &axum::extract::multipart::Field<'_>) -> bool {
-    field.file_name().is_some() || field.content_type().is_some()
-}
+    #[tokio::test]
+    async fn phrase_boolean_and_field_queries_are_parsed() {
+        let index_name = unique_index_name("query-syntax");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "The quick brown fox jumps", "title": "Fox" },
+                { "content": "The brown quick dog sleeps", "title": "Dog" },
+                { "content": "A fox and a dog", "title": "Both" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-// Validate content type
-fn is_valid_content_type(content_type: &str) -> bool {
-    matches!(
-        content_type,
-        "text/plain" | "text/markdown" | "application/octet-stream" // Sometimes file uploads might not have the correct content-type
-    )
-}
+        let titles = |query: &str, fields: &[&str]| {
+            let found = search(serde_json::json!({
+                "query": query,
+                "index": index_name,
+                "fields": fields,
+            }))
+            .unwrap();
+            let mut titles: Vec<_> = found.hits.into_iter().map(|hit| hit.title).collect();
+            titles.sort_unstable();
+            titles
+        };
+        assert_eq!(titles(r#""quick brown""#, &[]), ["Fox"]);
+        assert_eq!(titles("fox AND dog", &[]), ["Both"]);
+        assert_eq!(titles("fox -dog", &[]), ["Fox"]);
+        assert_eq!(titles("fox OR sleeps", &[]), ["Both", "Dog", "Fox"]);
+        assert_eq!(titles("title:dog", &[]), ["Dog"]);
+        assert_eq!(titles("both", &["title"]), ["Both"]);
+        assert!(titles("both", &[]).is_empty());
+    }
 
-// Add the query handler function
-async fn query_handler(Json(request): Json<QueryRequest>) -> Json<QueryResponse> {
-    info!(
-        query = %request.query,
-        top_k = request.top_k,
-        "Received search request"
-    );
+    #[tokio::test]
+    async fn pages_cover_all_the_hits_once() {
+        let index_name = unique_index_name("pages");
+        let documents: Vec<_> = (1..=25)
+            .map(|i| serde_json::json!({ "content": format!("page {i}"), "title": format!("{i}") }))
+            .collect();
+        let (status, _) =
+            index(serde_json::json!({ "index": index_name, "documents": documents })).await;
+        assert_eq!(status, StatusCode::OK);
 
-    let index_path = std::env::current_dir()
-        .unwrap()
-        .join(INDEX_STORAGE_DIR)
-        .join(&request.index);
-    if !index_path.exists() {
-        let err_msg = format!("Index '{}' does not exist", request.index);
+        let mut seen = Vec::new();
+        for offset in [0, 10, 20] {
+            let page = search(serde_json::json!({
+                "query": "page",
+                "index": index_name,
+                "top_k": 10,
+                "offset": offset,
+            }))
+            .unwrap();
+            assert_eq!(page.total_hits, Some(25));
+            assert_eq!(page.hits.len(), if offset == 20 { 5 } else { 10 });
+            seen.extend(page.hits.into_iter().map(|hit| hit.title));
+        }
+        seen.sort_unstable();
+        seen.dedup();
+        assert_eq!(seen.len(), 25);
 
-        error!("{}", &err_msg);
+        let beyond = search(serde_json::json!({
+            "query": "page",
+            "index": index_name,
+            "offset": 30,
+        }))
+        .unwrap();
+        assert!(beyond.hits.is_empty());
+        assert_eq!(beyond.total_hits, Some(25));
+    }
 
-        return Json(QueryResponse {
-            hits: Vec::new(),
-            error: Some(err_msg),
-        });
+    #[tokio::test]
+    async fn fuzzy_search_tolerates_typos() {
+        let index_name = unique_index_name("fuzzy");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "Deploying services on kubernetes", "title": "Kubernetes" },
+                { "content": "Baking sourdough bread", "title": "Bread" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let titles = |request: serde_json::Value| {
+            let mut request = request;
+            request["index"] = index_name.clone().into();
+            search(request)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|hit| hit.title)
+                .collect::<Vec<_>>()
+        };
+        assert!(titles(serde_json::json!({ "query": "kubernettes" })).is_empty());
+        assert_eq!(
+            titles(serde_json::json!({ "query": "kubernettes", "fuzzy": true })),
+            ["Kubernetes"]
+        );
+        assert!(titles(serde_json::json!({ "query": "kubrnettes", "fuzzy": true })).is_empty());
+        assert_eq!(
+            titles(
+                serde_json::json!({ "query": "kubrnettes", "fuzzy": true, "fuzzy_distance": 2 })
+            ),
+            ["Kubernetes"]
+        );
+        assert!(search(serde_json::json!({
+            "query": "kubernetes",
+            "index": index_name,
+            "fuzzy": true,
+            "fuzzy_distance": 3,
+        }))
+        .is_err());
     }
 
-    info!(path = %index_path.display(), "Opening index");
-    let index = match Index::open_in_dir(&index_path) {
-        Ok(index) => index,
-        Err(e) => {
-            let err_msg = format!("Failed to open index: {e}");
+    // status of the upload of the multipart `body`, whose parts are separated by `--BOUNDARY`
+    async fn upload(body: String) -> StatusCode {
+        let request = http::Request::builder()
+            .header(
+                http::header::CONTENT_TYPE,
+                "multipart/form-data; boundary=BOUNDARY",
+            )
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
 
-            error!("{}", &err_msg);
+        let (status, _) = process_multipart(multipart, false).await;
+        status
+    }
 
-            return Json(QueryResponse {
-                hits: Vec::new(),
-                error: Some(err_msg),
-            });
+    #[tokio::test]
+    async fn uploaded_html_is_indexed_as_text() {
+        test_support::storage_dir();
+        let index_name = unique_index_name("html");
+        let body = format!(
+            "--BOUNDARY\r\nContent-Disposition: form-data; name=\"index\"\r\n\r\n{index_name}\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"page.html\"\r\n\
+             Content-Type: text/html\r\n\r\n\
+             <html><head><title>Paris guide</title><script>var tracking;</script></head>\
+             <body><p>The <b>Louvre</b> museum</p></body></html>\r\n\
+             --BOUNDARY--\r\n"
+        );
+        assert_eq!(upload(body).await, StatusCode::OK);
+
+        let found = search(serde_json::json!({ "query": "louvre", "index": index_name })).unwrap();
+        assert_eq!(found.hits[0].title, "Paris guide");
+        assert_eq!(found.hits[0].content, "The Louvre museum");
+        for markup in ["tracking", "script", "html"] {
+            let found =
+                search(serde_json::json!({ "query": markup, "index": index_name })).unwrap();
+            assert!(found.hits.is_empty(), "{markup}");
         }
-    };
+    }
+
+    #[tokio::test]
+    async fn oversized_upload_is_rejected_with_413() {
+        let app = Router::new().route(
+            "/v1/index/create",
+            post(index_document_handler).layer(DefaultBodyLimit::max(1000)),
+        );
+        let (base_url, client) = test_support::serve(app).await;
+        let url = format!("{base_url}/v1/index/create");
 
-    // create reader
-    let reader = index
-        .reader_builder()
-        .reload_policy(ReloadPolicy::OnCommitWithDelay)
-        .try_into()
+        let oversized = serde_json::json!({ "documents": [{ "content": "x".repeat(2000) }] });
+        let response = client.post(&url).json(&oversized).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "PAYLOAD_TOO_LARGE");
+
+        let form = format!(
+            "--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"large.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n{}\r\n--BOUNDARY--\r\n",
+            "x".repeat(2000)
+        );
+        let response = client
+            .post(&url)
+            .header(
+                http::header::CONTENT_TYPE,
+                "multipart/form-data; boundary=BOUNDARY",
+            )
+            .body(form)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let small = serde_json::json!({
+            "index": unique_index_name("small"),
+            "documents": [{ "content": "small" }],
+        });
+        let response = client.post(&url).json(&small).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn hits_below_the_min_score_are_dropped() {
+        let index_name = unique_index_name("min-score");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "rust rust rust", "title": "Strong" },
+                { "content": "rust and many other unrelated words about cooking pasta", "title": "Weak" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let all = search(serde_json::json!({ "query": "rust", "index": index_name })).unwrap();
+        let scores: Vec<f64> = all.hits.iter().map(|hit| hit.score).collect();
+        assert!(scores[0] > scores[1]);
+
+        let filtered = search(serde_json::json!({
+            "query": "rust",
+            "index": index_name,
+            "min_score": (scores[0] + scores[1]) / 2.0,
+        }))
         .unwrap();
+        assert_eq!(filtered.hits.len(), 1);
+        assert_eq!(filtered.hits[0].title, "Strong");
+        assert_eq!(filtered.total_hits, Some(2));
+    }
 
-    // acquire searcher
-    let searcher = reader.searcher();
+    #[tokio::test]
+    async fn filter_keeps_the_hits_with_the_exact_tag() {
+        let index_name = unique_index_name("tags");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "Quarterly report", "title": "Finance", "tags": ["finance"] },
+                { "content": "Quarterly report", "title": "Sales", "tags": ["sales", "Finance Europe"] },
+                { "content": "Quarterly report", "title": "Untagged" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-    // get schema
-    let schema = index.schema();
+        let titles = |filter: serde_json::Value| {
+            let found = search(serde_json::json!({
+                "query": "report",
+                "index": index_name,
+                "filter": filter,
+            }))
+            .unwrap();
+            let mut titles: Vec<_> = found.hits.into_iter().map(|hit| hit.title).collect();
+            titles.sort_unstable();
+            titles
+        };
+        assert_eq!(
+            titles(serde_json::json!({ "tags": ["finance"] })),
+            ["Finance"]
+        );
+        assert_eq!(
+            titles(serde_json::json!({ "tags": ["Finance Europe"] })),
+            ["Sales"]
+        );
+        assert!(titles(serde_json::json!({ "tags": ["europe"] })).is_empty());
+        assert_eq!(
+            titles(serde_json::json!({})),
+            ["Finance", "Sales", "Untagged"]
+        );
+    }
 
-    // get fields
-    let title = schema.get_field("title").unwrap();
-    let body = schema.get_field("body").unwrap();
+    #[tokio::test]
+    async fn hits_are_sorted_by_creation_date() {
+        let index_name = unique_index_name("sort-by-date");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "Release notes", "title": "March", "created_at": "2024-03-01T00:00:00Z" },
+                { "content": "Release notes release notes", "title": "January", "created_at": "2024-01-01T00:00:00Z" },
+                { "content": "Release notes", "title": "May", "created_at": "2024-05-01T00:00:00Z" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-    // create query parser
-    let query_parser = QueryParser::for_index(&index, vec![title, body]);
+        let titles = |order: &str| {
+            let found = search(serde_json::json!({
+                "query": "release",
+                "index": index_name,
+                "sort_by": "created_at",
+                "order": order,
+            }))
+            .unwrap();
+            found
+                .hits
+                .into_iter()
+                .map(|hit| hit.title)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles("desc"), ["May", "March", "January"]);
+        assert_eq!(titles("asc"), ["January", "March", "May"]);
 
-    // parse query
-    let query_str = format!("body:{}", &request.query);
-    let query = match query_parser.parse_query(&query_str) {
-        Ok(q) => q,
-        Err(e) => {
-            let err_msg = format!("Failed to parse query: {e}");
+        let found = search(serde_json::json!({
+            "query": "release",
+            "index": index_name,
+            "sort_by": "created_at",
+        }))
+        .unwrap();
+        assert_eq!(
+            found.hits[0].created_at.as_deref(),
+            Some("2024-05-01T00:00:00Z")
+        );
 
-            error!("{}", &err_msg);
+        for sort_by in ["body", "missing"] {
+            let err = search(serde_json::json!({
+                "query": "release",
+                "index": index_name,
+                "sort_by": sort_by,
+            }))
+            .err()
+            .unwrap()
+            .to_string();
+            assert!(err.contains(&format!("`{sort_by}`")), "{err}");
+        }
+    }
 
-            return Json(QueryResponse {
-                hits: Vec::new(),
-                error: Some(err_msg),
-            });
+    #[test]
+    fn index_names_escaping_the_storage_directory_are_invalid() {
+        for index_name in ["", "..", "../etc", "a/b", "/etc", "a\\b", "..\\etc", "a\0b"] {
+            assert!(validate_index_name(index_name).is_err(), "{index_name:?}");
         }
-    };
+        for index_name in ["default", "my-index_2", "a.b", ".hidden"] {
+            assert!(validate_index_name(index_name).is_ok(), "{index_name:?}");
+        }
+    }
 
-    // execute search
-    info!("Executing search");
-    let top_docs = match searcher.search(&query, &TopDocs::with_limit(request.top_k)) {
-        Ok(docs) => docs,
-        Err(e) => {
-            let err_msg = format!("Search failed: {e}");
+    #[test]
+    fn route_names_cannot_name_new_indexes() {
+        for index_name in RESERVED_INDEX_NAMES {
+            assert!(validate_new_index_name(index_name).is_err(), "{index_name}");
+        }
+        assert!(validate_new_index_name("create-2").is_ok());
+    }
 
-            error!("{}", &err_msg);
+    #[tokio::test]
+    async fn malicious_index_names_are_refused() {
+        let index_name = unique_index_name("victim");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "Nothing to see here" }],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-            return Json(QueryResponse {
-                hits: Vec::new(),
-                error: Some(err_msg),
-            });
+        let malicious_names = [
+            "..".to_string(),
+            format!("../{index_name}"),
+            format!("{index_name}/.."),
+            format!("..\\{index_name}"),
+            format!("{index_name}\0"),
+        ];
+        for malicious_name in &malicious_names {
+            let params = DownloadParams {
+                format: Default::default(),
+            };
+            let downloaded = download_index_file_handler(
+                Path(malicious_name.clone()),
+                axum::extract::Query(params),
+                http::HeaderMap::new(),
+            )
+            .await;
+            assert_eq!(downloaded.err().unwrap().status(), StatusCode::BAD_REQUEST);
+
+            let request = serde_json::json!({ "query": "nothing", "index": malicious_name });
+            let found = search_index(serde_json::from_value(request).unwrap());
+            assert_eq!(found.err().unwrap().status(), StatusCode::BAD_REQUEST);
+
+            let deleted = delete_index_handler(Path(malicious_name.clone())).await;
+            assert_eq!(deleted.err().unwrap().status(), StatusCode::BAD_REQUEST);
         }
-    };
 
-    // collect hits
-    let mut hits = Vec::new();
-    for (score, doc_address) in top_docs {
-        let retrieved_doc: TantivyDocument = searcher.doc(doc_address).unwrap();
+        // the index the names point to is left untouched
+        assert!(test_support::storage_dir().join(&index_name).is_dir());
+    }
 
-        let title_value = retrieved_doc
-            .get_first(title)
-            .and_then(|v| v.as_str())
-            .unwrap_or("Unknown")
-            .to_string();
+    #[test]
+    fn blank_and_oversized_content_is_rejected() {
+        let pipeline = preprocess::Pipeline::new(&Default::default()).unwrap();
+        let process = |content: &str, max_chars| {
+            process_content_with(content.to_string(), &pipeline, max_chars)
+        };
 
-        let body_value = retrieved_doc
-            .get_first(body)
-            .and_then(|v| v.as_str())
-            .unwrap_or("Unknown")
-            .to_string();
+        assert!(process("", None).is_err());
+        assert!(process(" \t\r\n ", None).is_err());
+        assert!(process("12345", Some(4)).is_err());
+        assert_eq!(process("12345", Some(5)).unwrap(), "12345");
+        assert_eq!(process("12345", None).unwrap(), "12345");
+        // characters are counted, not bytes
+        assert!(process("ééééé", Some(5)).is_ok());
+    }
 
-        info!(
-            score = score,
-            title = title_value,
-            body = body_value,
-            "Retrieved document"
+    #[tokio::test]
+    async fn blank_documents_are_not_indexed() {
+        let index_name = unique_index_name("blank");
+        let (status, response) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [
+                { "content": "", "title": "Empty" },
+                { "content": " \n\t ", "title": "Whitespace" },
+                { "content": "Blank documents are skipped", "title": "Kept" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let statuses: Vec<_> = response
+            .results
+            .iter()
+            .map(|result| result.status.as_str())
+            .collect();
+        assert_eq!(statuses, ["failed", "failed", "indexed"]);
+
+        let found = search(serde_json::json!({
+            "query": "*",
+            "index": index_name,
+        }))
+        .unwrap();
+        assert_eq!(found.total_hits, Some(1));
+        assert_eq!(found.hits[0].title, "Kept");
+    }
+
+    #[test]
+    fn title_is_the_filename_without_its_extension() {
+        assert_eq!(title_from_filename("report.md").as_deref(), Some("report"));
+        assert_eq!(
+            title_from_filename("q3.report.txt").as_deref(),
+            Some("q3.report")
         );
+        assert_eq!(title_from_filename("notes").as_deref(), Some("notes"));
+        assert_eq!(title_from_filename(" .md"), None);
+        assert_eq!(title_from_filename(""), None);
+    }
 
-        hits.push(SearchHit {
-            title: title_value,
-            content: body_value,
-            score: score as f64,
-        });
+    #[tokio::test]
+    async fn uploaded_files_are_titled() {
+        test_support::storage_dir();
+        let index_name = unique_index_name("titles");
+        let body = format!(
+            "--BOUNDARY\r\nContent-Disposition: form-data; name=\"index\"\r\n\r\n{index_name}\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"report.md\"\r\n\
+             Content-Type: text/markdown\r\n\r\nQuarterly revenue\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nMeeting notes\r\n\
+             --BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\nWeekly meeting\r\n\
+             --BOUNDARY--\r\n"
+        );
+        assert_eq!(upload(body).await, StatusCode::OK);
+
+        let title = |query: &str| {
+            let found = search(serde_json::json!({ "query": query, "index": index_name })).unwrap();
+            found.hits[0].title.clone()
+        };
+        assert_eq!(title("revenue"), "report");
+        assert_eq!(title("meeting"), "Meeting notes");
     }
 
-    info!(hits = hits.len(), "Search completed successfully");
+    async fn download(index_name: &str, range: Option<&str>) -> axum::response::Response {
+        let mut headers = http::HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(http::header::RANGE, range.parse().unwrap());
+        }
+        let params = DownloadParams {
+            format: Default::default(),
+        };
 
-    Json(QueryResponse { hits, error: None })
-}
+        download_index_file_handler(
+            Path(index_name.to_string()),
+            axum::extract::Query(params),
+            headers,
+        )
+        .await
+        .unwrap()
+    }
 
-// download index file
-async fn download_index_file_handler(
-    Path(index_name): Path<String>,
-) -> impl axum::response::IntoResponse {
-    info!(
-        index_name = %index_name,
-        "Received index file download request"
-    );
+    #[tokio::test]
+    async fn download_sends_the_requested_byte_range() {
+        let index_name = unique_index_name("download");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "Archives can be downloaded in parts" }],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-    let index_storage_dir = std::env::current_dir().unwrap().join(INDEX_STORAGE_DIR);
-    let index_path = index_storage_dir.as_path().join(&index_name);
+        let full = download(&index_name, None).await;
+        assert_eq!(full.status(), StatusCode::OK);
+        let archive = axum::body::to_bytes(full.into_body(), usize::MAX)
+            .await
+            .unwrap();
 
-    // Check if index exists
-    if !index_path.exists() {
-        let err_msg = format!("Index '{index_name}' not found");
-        error!(
-            index_name = %index_name,
-            path = %index_path.display(),
-            "Index directory not found"
+        let partial = download(&index_name, Some("bytes=0-9")).await;
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            partial.headers()[http::header::CONTENT_RANGE],
+            format!("bytes 0-9/{}", archive.len()).as_str()
+        );
+        assert_eq!(partial.headers()[http::header::CONTENT_LENGTH], "10");
+        let part = axum::body::to_bytes(partial.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(part, archive.slice(..10));
+
+        let past_end = format!("bytes={}-", archive.len());
+        let unsatisfiable = download(&index_name, Some(&past_end)).await;
+        assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            unsatisfiable.headers()[http::header::CONTENT_RANGE],
+            format!("bytes */{}", archive.len()).as_str()
         );
-        return (StatusCode::NOT_FOUND, err_msg).into_response();
     }
 
-    info!("Found index directory");
+    #[tokio::test]
+    async fn duplicate_in_the_same_request_is_skipped() {
+        let index_name = unique_index_name("dedup");
+        let (status, response) = index(serde_json::json!({
+            "index": index_name,
+            "dedup": true,
+            "documents": [
+                { "content": "Duplicated paragraph", "title": "First" },
+                { "content": "Duplicated paragraph", "title": "Second" },
+                { "content": "Unique paragraph" },
+            ],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-    // Prepare compression
-    let compressed_filename = format!("{index_name}.tar.gz");
-    let compressed_index_path = index_storage_dir.as_path().join(&compressed_filename);
+        let statuses: Vec<_> = response.results.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, ["indexed", "skipped", "indexed"]);
+        let found =
+            search(serde_json::json!({ "query": "duplicated", "index": index_name })).unwrap();
+        assert_eq!(found.hits.len(), 1);
+        assert_eq!(found.hits[0].title, "First");
+    }
 
-    // check if compressed file exists
-    if !compressed_index_path.exists() {
-        info!("Starting index compression");
+    fn document(value: serde_json::Value) -> DocumentInput {
+        serde_json::from_value(value).unwrap()
+    }
 
-        // Create compressed file
-        let file = match File::create(&compressed_index_path) {
-            Ok(file) => {
-                info!(
-                    path = %compressed_index_path.display(),
-                    "Created compressed file"
-                );
-                file
-            }
-            Err(e) => {
-                let err_msg = format!("Failed to create compressed index file: {e}");
-                error!(
-                    error = %e,
-                    path = %compressed_index_path.display(),
-                    "Failed to create compressed file"
-                );
-                return (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response();
-            }
-        };
+    #[test]
+    fn field_count_counts_named_values() {
+        let fields: serde_json::Map<_, _> = (0..10)
+            .map(|i| (format!("column_{i}"), serde_json::json!("value")))
+            .collect();
+        let over_limit = document(serde_json::json!({ "content": "row", "fields": fields }));
 
-        // Compress directory
-        let mut builder = tar::Builder::new(file);
-        if let Err(e) = builder.append_dir_all(".", &index_path) {
-            let err_msg = format!("Failed to compress index directory: {e}");
-            error!(
-                error = %e,
-                source = %index_path.display(),
-                target = %compressed_index_path.display(),
-                "Failed to compress index directory"
-            );
-            return (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response();
-        }
+        let err = check_field_count(&over_limit, 8).unwrap_err();
+        assert_eq!(err, "Document has 11 fields, exceeding the maximum of 8");
+        assert!(check_field_count(&over_limit, 11).is_ok());
+    }
 
-        if let Err(e) = builder.finish() {
-            let err_msg = format!("Failed to finalize index compression: {e}");
-            error!(
-                error = %e,
-                path = %compressed_index_path.display(),
-                "Failed to finalize compression"
-            );
-            return (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response();
-        }
+    #[test]
+    fn field_count_counts_tags_once() {
+        let tagged = document(serde_json::json!({
+            "content": "news",
+            "title": "Headline",
+            "tags": ["a", "b", "c", "d", "e"],
+        }));
+
+        assert!(check_field_count(&tagged, 3).is_ok());
+        assert!(check_field_count(&tagged, 2).is_err());
     }
 
-    info!("Index compression completed");
+    #[test]
+    fn zero_concurrent_downloads_is_rejected() {
+        let parse = |value: &str| {
+            Cli::try_parse_from(["kw-search-server", "--max-concurrent-downloads", value])
+        };
 
-    // Read compressed file
-    let mut file = match File::open(&compressed_index_path) {
-        Ok(file) => file,
-        Err(e) => {
-            let err_msg = format!("Failed to open the compressed file: {e}");
-            error!(
-                error = %e,
-                path = %compressed_index_path.display(),
-                "Failed to open compressed file"
-            );
-            return (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response();
-        }
-    };
+        assert!(parse("0").is_err());
+        assert_eq!(parse("1").unwrap().max_concurrent_downloads, 1);
+    }
 
-    // Read file content
-    let mut buffer: Vec<u8> = Vec::new();
-    if let Err(e) = file.read_to_end(&mut buffer) {
-        let err_msg = format!("Failed to read the compressed file content: {e}");
-        error!(
-            error = %e,
-            path = %compressed_index_path.display(),
-            "Failed to read file content"
+    #[tokio::test]
+    async fn deleting_an_index_removes_its_aliases() {
+        let index_name = unique_index_name("aliased");
+        let alias = unique_index_name("alias");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "Aliases point to indexes" }],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        aliases::set(&alias, &index_name).unwrap();
+
+        let Json(deleted) = delete_index_handler(Path(index_name.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(deleted["removed_aliases"], serde_json::json!([alias]));
+        assert_eq!(aliases::resolve(&alias), alias);
+        let result = search(serde_json::json!({ "index": alias, "query": "aliases" }));
+        assert!(matches!(result, Err(ServerError::IndexNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn indexed_document_is_found_right_away() {
+        let index_name = unique_index_name("read-your-writes");
+        let (status, response) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "content": "Ferris the crab is the mascot of Rust" }],
+            "commit_immediately": true,
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.results[0].status, "indexed");
+
+        let found = search(serde_json::json!({ "query": "ferris", "index": index_name })).unwrap();
+        assert_eq!(found.hits.len(), 1);
+        assert_eq!(
+            found.hits[0].content,
+            "Ferris the crab is the mascot of Rust"
         );
-        return (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response();
     }
 
-    // Prepare response
-    let content_type = "application/gzip";
-    let content_disposition = format!("attachment; filename=\"{compressed_filename}\"");
-    let content_length = buffer.len();
-    let body = axum::body::Body::from(buffer);
+    #[tokio::test]
+    async fn return_fields_trim_the_hits_only() {
+        let index_name = unique_index_name("return-fields");
+        let (status, _) = index(serde_json::json!({
+            "index": index_name,
+            "documents": [{ "title": "Crab", "content": "Ferris the crab" }],
+        }))
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-    info!(
-        index_name = %index_name,
-        content_type = %content_type,
-        content_length = content_length,
-        filename = %compressed_filename,
-        "Prepared download response"
-    );
+        let found = search(serde_json::json!({ "query": "ferris", "index": index_name })).unwrap();
+        let full = found.to_json(&[]).unwrap();
+        assert_eq!(full["hits"][0]["content"], "Ferris the crab");
+        assert!(full.get("error").is_none());
 
-    match axum::response::Response::builder()
-        .header("Access-Control-Allow-Origin", "*")
-        .header("Access-Control-Allow-Methods", "*")
-        .header("Access-Control-Allow-Headers", "*")
-        .header("Content-Type", content_type)
-        .header("Content-Disposition", content_disposition.as_str())
-        .header("Content-Length", content_length.to_string().as_str())
-        .body(body)
-    {
-        Ok(response) => {
-            info!("Returned download response");
-            response
-        }
-        Err(e) => {
-            let err_msg = format!("Failed to build response: {e}");
-            error!(
-                error = %e,
-                index_name = %index_name,
-                "Failed to build response"
-            );
-            (StatusCode::INTERNAL_SERVER_ERROR, err_msg).into_response()
-        }
+        let trimmed = found.to_json(&["title".to_string()]).unwrap();
+        let hit = trimmed["hits"][0].as_object().unwrap();
+        assert_eq!(hit["title"], "Crab");
+        assert!(!hit.contains_key("content"));
+        assert!(!hit.contains_key("score"));
+        assert_eq!(hit["index"], index_name.as_str());
+    }
+
+    #[test]
+    fn download_url_prefix_of_an_ipv6_address_is_bracketed() {
+        let loopback: SocketAddr = "[::1]:9069".parse().unwrap();
+        assert_eq!(default_download_url_prefix(loopback), "http://[::1]:9069");
+        let url = Url::parse(&default_download_url_prefix(loopback)).unwrap();
+        assert_eq!(url.host_str(), Some("[::1]"));
+        assert_eq!(url.port(), Some(9069));
+
+        let unspecified: SocketAddr = "[::]:9069".parse().unwrap();
+        assert_eq!(
+            default_download_url_prefix(unspecified),
+            "http://localhost:9069"
+        );
+        let ipv4: SocketAddr = "0.0.0.0:9069".parse().unwrap();
+        assert_eq!(default_download_url_prefix(ipv4), "http://localhost:9069");
+    }
+
+    #[test]
+    fn download_url_uses_the_overridden_scheme() {
+        let prefix = Url::parse("http://localhost:9069").unwrap();
+        assert_eq!(
+            download_url(&prefix, None, "docs"),
+            "http://localhost:9069/v1/index/download/docs"
+        );
+
+        let prefix = Url::parse("http://search.example.com").unwrap();
+        assert_eq!(
+            download_url(&prefix, Some("https"), "docs"),
+            "https://search.example.com/v1/index/download/docs"
+        );
     }
 }
@@ -0,0 +1,160 @@
+//! Arbitration of the memory of the index writers, so that the indexing memory of the server stays
+//! within the budget set by `--total-index-memory`, whatever the number of concurrent writers.
+//!
+//! The total budget is divided among the active writers: a writer gets an equal share of it, at
+//! most the budget set by `--writer-memory-bytes` and at least tantivy's minimum. The budget of a writer is fixed
+//! once it is created, so a writer started under contention keeps its smaller budget, and a writer
+//! waits for the budget of others to be released when less than the minimum is left.
+
+use once_cell::sync::OnceCell;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::info;
+
+/// Minimum memory budget of an index writer, as required by tantivy for a single indexing thread
+pub(crate) const MIN_WRITER_MEMORY_BYTES: usize = 15_000_000;
+
+/// Default memory budget of an index writer
+pub(crate) const DEFAULT_WRITER_MEMORY_BYTES: usize = 100_000_000;
+
+// granularity of the budget handed out to the writers
+const UNIT_BYTES: usize = 1_000_000;
+
+// memory budget of a writer, if configured
+static WRITER_BYTES: OnceCell<usize> = OnceCell::new();
+
+// total memory budget of the writers, divided in units, if configured
+static ARBITER: OnceCell<Arbiter> = OnceCell::new();
+
+// number of writers holding or waiting for a budget
+static ACTIVE_WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+struct Arbiter {
+    total_bytes: usize,
+    units: Arc<Semaphore>,
+}
+
+/// Set the memory budget of an index writer, which must be at least tantivy's minimum. With a
+/// total budget, it is the largest share of the total a writer gets.
+pub(crate) fn init_writer_bytes(bytes: usize) -> Result<(), String> {
+    if bytes < MIN_WRITER_MEMORY_BYTES {
+        return Err(format!(
+            "The writer memory must be at least {MIN_WRITER_MEMORY_BYTES} bytes"
+        ));
+    }
+
+    WRITER_BYTES
+        .set(bytes)
+        .map_err(|_| "The writer memory is already set".to_string())
+}
+
+/// Memory budget of an index writer
+pub(crate) fn writer_bytes() -> usize {
+    WRITER_BYTES
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_WRITER_MEMORY_BYTES)
+}
+
+/// Set the total memory budget of the index writers. Without it, each writer gets
+/// `writer_bytes()`.
+pub(crate) fn init(total_bytes: usize) -> Result<(), String> {
+    if total_bytes < MIN_WRITER_MEMORY_BYTES {
+        return Err(format!(
+            "The total index memory must be at least {MIN_WRITER_MEMORY_BYTES} bytes"
+        ));
+    }
+
+    ARBITER
+        .set(Arbiter {
+            total_bytes,
+            units: Arc::new(Semaphore::new(total_bytes / UNIT_BYTES)),
+        })
+        .map_err(|_| "The total index memory is already set".to_string())
+}
+
+/// Total memory budget of the index writers, if configured
+pub(crate) fn total_bytes() -> Option<usize> {
+    ARBITER.get().map(|arbiter| arbiter.total_bytes)
+}
+
+// a writer counted among the active writers until dropped, whether it got its budget or stopped
+// waiting for it, e.g. when the client disconnects
+#[derive(Debug)]
+struct ActiveWriter;
+
+impl ActiveWriter {
+    // count a new writer, and return the number of active writers including it
+    fn new() -> (Self, usize) {
+        let active_writers = ACTIVE_WRITERS.fetch_add(1, Ordering::SeqCst) + 1;
+        (ActiveWriter, active_writers)
+    }
+}
+
+impl Drop for ActiveWriter {
+    fn drop(&mut self) {
+        ACTIVE_WRITERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Memory budget of an index writer, released when dropped. It must outlive the writer.
+#[derive(Debug)]
+pub(crate) struct WriterBudget {
+    bytes: usize,
+    _permit: Option<OwnedSemaphorePermit>,
+    _active: ActiveWriter,
+}
+
+impl WriterBudget {
+    /// Number of bytes the writer may use
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+/// Get the memory budget of a new index writer, waiting for other writers to finish if less than
+/// the minimum budget is left.
+pub(crate) async fn acquire() -> WriterBudget {
+    let (active, active_writers) = ActiveWriter::new();
+
+    let Some(arbiter) = ARBITER.get() else {
+        return WriterBudget {
+            bytes: writer_bytes(),
+            _permit: None,
+            _active: active,
+        };
+    };
+
+    // the equal share of the writer, reduced to what is left if the other writers hold more
+    let min_units = MIN_WRITER_MEMORY_BYTES / UNIT_BYTES;
+    let share_units = (arbiter.total_bytes / active_writers / UNIT_BYTES)
+        .clamp(min_units, writer_bytes() / UNIT_BYTES);
+    let units = share_units
+        .min(arbiter.units.available_permits())
+        .max(min_units);
+
+    let permit = arbiter
+        .units
+        .clone()
+        .acquire_many_owned(units as u32)
+        .await
+        .expect("the index memory semaphore is never closed");
+    let bytes = units * UNIT_BYTES;
+
+    info!(
+        budget_bytes = bytes,
+        active_writers = active_writers,
+        total_bytes = arbiter.total_bytes,
+        "Index writer memory budget"
+    );
+
+    WriterBudget {
+        bytes,
+        _permit: Some(permit),
+        _active: active,
+    }
+}
@@ -0,0 +1,189 @@
+//! Merge of the segments of an index into one, e.g. after the index accumulated many small
+//! segments, or many deleted documents, which slow searches down.
+
+use crate::{
+    aliases, error::ServerError, index_meta, index_storage_dir,
+    memory_budget::MIN_WRITER_MEMORY_BYTES, reader_cache, request_id, token_length,
+    validate_index_name,
+};
+use axum::{extract::Path, Json};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{collections::HashSet, sync::Mutex};
+use tantivy::{directory::error::LockError, Index, SegmentId, TantivyDocument, TantivyError};
+use tracing::{error, info, warn};
+
+// indexes being merged, which other merge requests must not touch meanwhile
+static MERGING_INDEXES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Response body of the `POST /v1/index/{index_name}/merge` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct MergeResponse {
+    /// Name of the merged index
+    pub(crate) index_name: String,
+    /// Number of segments before the merge
+    pub(crate) segments_before: usize,
+    /// Number of segments after the merge
+    pub(crate) segments_after: usize,
+    /// Number of documents of the index, unchanged by the merge
+    pub(crate) num_docs: u64,
+}
+
+// marks an index as being merged, until dropped
+struct MergeGuard(String);
+
+impl MergeGuard {
+    // mark `index_name` as being merged, unless it already is
+    fn acquire(index_name: &str) -> Option<Self> {
+        let mut merging_indexes = MERGING_INDEXES.lock().unwrap();
+        merging_indexes
+            .insert(index_name.to_string())
+            .then(|| Self(index_name.to_string()))
+    }
+}
+
+impl Drop for MergeGuard {
+    fn drop(&mut self) {
+        MERGING_INDEXES.lock().unwrap().remove(&self.0);
+    }
+}
+
+// merge the segments of an index into one
+pub(crate) async fn merge_index_handler(
+    Path(index_name): Path<String>,
+) -> Result<Json<MergeResponse>, ServerError> {
+    info!(index_name = %index_name, "Received index merge request");
+
+    if let Err(err_msg) = validate_index_name(&index_name) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&index_name);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{index_name}' not found");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let Some(guard) = MergeGuard::acquire(&index_name) else {
+        let err_msg = format!("Index '{index_name}' is already being merged");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::Conflict(err_msg));
+    };
+
+    // merging rewrites the segments, which is blocking work
+    let merged = request_id::spawn_blocking(move || {
+        let merged = merge_index(&index_name, &index_path);
+        drop(guard);
+        merged
+    })
+    .await;
+    match merged {
+        Ok(Ok(response)) => {
+            info!(
+                index_name = %response.index_name,
+                segments_before = response.segments_before,
+                segments_after = response.segments_after,
+                "Index merged"
+            );
+
+            Ok(Json(response))
+        }
+        Ok(Err(e)) => {
+            error!("{}", &e);
+
+            Err(e)
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to merge index: {e}");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::Operation(err_msg))
+        }
+    }
+}
+
+// merge the segments of the index located at `index_path` into one
+fn merge_index(
+    index_name: &str,
+    index_path: &std::path::Path,
+) -> Result<MergeResponse, ServerError> {
+    let internal_error = |context: &str, e: TantivyError| {
+        ServerError::Operation(format!("Failed to {context}: {e}"))
+    };
+
+    let index = Index::open_in_dir(index_path).map_err(|e| internal_error("open index", e))?;
+    token_length::register_from_metadata(&index);
+
+    // no document is added, so the writer gets the minimum budget, outside of the arbitration of
+    // the writers
+    let mut index_writer =
+        match index.writer_with_num_threads::<TantivyDocument>(1, MIN_WRITER_MEMORY_BYTES) {
+            Ok(index_writer) => index_writer,
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) => {
+                return Err(ServerError::Conflict(format!(
+                    "Index '{index_name}' is being written, retry once it is done"
+                )));
+            }
+            Err(e) => return Err(internal_error("create index writer", e)),
+        };
+
+    let metas = index
+        .searchable_segment_metas()
+        .map_err(|e| internal_error("read the segments of the index", e))?;
+    let segments_before = metas.len();
+    let num_docs = metas.iter().map(|meta| meta.num_docs() as u64).sum();
+    let has_deletes = metas.iter().any(|meta| meta.has_deletes());
+
+    // a single segment is only rewritten to drop its deleted documents
+    if segments_before > 1 || has_deletes {
+        let segment_ids: Vec<SegmentId> = metas.iter().map(|meta| meta.id()).collect();
+        index_writer
+            .merge(&segment_ids)
+            .wait()
+            .map_err(|e| internal_error("merge segments", e))?;
+
+        // the commit keeps the metadata of the index, stored in the payload of its commits
+        let metadata = index_meta::load(&index);
+        index_meta::commit_with_metadata(&mut index_writer, &metadata)
+            .map_err(|e| internal_error("commit index", e))?;
+    }
+
+    // drop the cached reader, which holds the merged segments
+    reader_cache::invalidate(index_name);
+
+    if let Err(e) = index_writer.wait_merging_threads() {
+        warn!(index_name = %index_name, error = %e, "Failed to wait for the merging threads");
+    }
+
+    // the files of the merged segments stay protected from deletion while the index that merged
+    // them is open, so they are removed through the index opened anew
+    let removed = Index::open_in_dir(index_path)
+        .and_then(|index| {
+            index.writer_with_num_threads::<TantivyDocument>(1, MIN_WRITER_MEMORY_BYTES)
+        })
+        .and_then(|index_writer| index_writer.garbage_collect_files().wait());
+    if let Err(e) = removed {
+        warn!(index_name = %index_name, error = %e, "Failed to remove the files of the merged segments");
+    }
+
+    let segments_after = index
+        .searchable_segment_ids()
+        .map_err(|e| internal_error("read the segments of the index", e))?
+        .len();
+
+    Ok(MergeResponse {
+        index_name: index_name.to_string(),
+        segments_before,
+        segments_after,
+        num_docs,
+    })
+}
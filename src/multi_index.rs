@@ -0,0 +1,138 @@
+//! Search of several indexes in a single request, e.g. the per-tenant indexes of a federated
+//! search. Each index is searched like a single-index search, and the hits are merged by score.
+//! The indexes that cannot be searched, e.g. because they do not exist, are reported in the
+//! `warnings` of the response instead of failing the whole request.
+
+use crate::{
+    default_top_k, elapsed_ms,
+    error::ServerError,
+    keyword_search::{FieldStats, QueryRequest, QueryResponse},
+    search_index, MAX_SEARCH_OFFSET,
+};
+use std::{collections::BTreeMap, time::Instant};
+use tracing::{error, info, warn};
+
+/// Maximum number of indexes searched by a request
+pub(crate) const MAX_SEARCH_INDEXES: usize = 100;
+
+/// Search the indexes of `request.indexes`, merging their hits by descending score.
+pub(crate) fn search_indexes(request: QueryRequest) -> Result<QueryResponse, ServerError> {
+    let start = Instant::now();
+
+    info!(
+        query = %request.query,
+        indexes = ?request.indexes,
+        top_k = ?request.top_k,
+        "Received multi-index search request"
+    );
+
+    if let Err(err_msg) = validate(&request) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // each index returns the hits up to the end of the page, which is cut from the merged hits
+    let top_k = request.top_k.unwrap_or_else(default_top_k);
+    let page_end = request.offset.saturating_add(top_k);
+
+    let mut index_names: Vec<&String> = Vec::new();
+    for index_name in &request.indexes {
+        if !index_names.contains(&index_name) {
+            index_names.push(index_name);
+        }
+    }
+
+    let mut hits = Vec::new();
+    let mut searched_indexes = 0;
+    let mut total_hits = 0;
+    let mut fallback_used = false;
+    let mut timed_out = false;
+    let mut field_stats: Option<BTreeMap<String, FieldStats>> = None;
+    let mut warnings = Vec::new();
+    let mut first_failure = None;
+    for index_name in index_names {
+        let mut index_request = request.clone();
+        index_request.index = index_name.clone();
+        index_request.indexes = Vec::new();
+        index_request.top_k = Some(page_end);
+        index_request.offset = 0;
+        // the time budget is shared by the searches of all the indexes
+        index_request.timeout_ms = request
+            .timeout_ms
+            .map(|timeout_ms| timeout_ms.saturating_sub(start.elapsed().as_millis() as u64));
+
+        let response = match search_index(index_request) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(index_name = %index_name, error = %e, "Failed to search index");
+
+                warnings.push(format!("{index_name}: {e}"));
+                first_failure.get_or_insert(e);
+                continue;
+            }
+        };
+
+        searched_indexes += 1;
+        hits.extend(response.hits);
+        total_hits += response.total_hits.unwrap_or_default();
+        fallback_used |= response.fallback_used;
+        timed_out |= response.timed_out;
+        if let Some(index_stats) = response.field_stats {
+            let merged = field_stats.get_or_insert_with(BTreeMap::new);
+            for (field_name, stats) in index_stats {
+                merged.entry(field_name).or_default().merge(stats);
+            }
+        }
+    }
+
+    // the request fails only if no index could be searched, with the error of the first index
+    if searched_indexes == 0 {
+        if let Some(e) = first_failure {
+            return Err(e);
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let hits: Vec<_> = hits.into_iter().skip(request.offset).take(top_k).collect();
+
+    info!(
+        hits = hits.len(),
+        failed_indexes = warnings.len(),
+        "Multi-index search completed"
+    );
+
+    Ok(QueryResponse {
+        hits,
+        error: None,
+        fallback_used,
+        field_stats,
+        timed_out,
+        total_hits: Some(total_hits),
+        took_ms: elapsed_ms(start),
+        warnings,
+    })
+}
+
+// check the parameters specific to multi-index searches
+fn validate(request: &QueryRequest) -> Result<(), String> {
+    if !request.index.is_empty() {
+        return Err("Set either `index` or `indexes`, not both".to_string());
+    }
+    if request.indexes.len() > MAX_SEARCH_INDEXES {
+        return Err(format!(
+            "Too many indexes. At most {MAX_SEARCH_INDEXES} indexes can be searched at once"
+        ));
+    }
+    if request.sort_by.is_some() {
+        return Err("`sort_by` is not supported when searching several indexes".to_string());
+    }
+    if request.offset > MAX_SEARCH_OFFSET {
+        return Err(format!(
+            "Invalid offset {}. At most {MAX_SEARCH_OFFSET} hits can be skipped",
+            request.offset
+        ));
+    }
+
+    Ok(())
+}
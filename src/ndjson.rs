@@ -0,0 +1,189 @@
+//! Bulk indexing of newline-delimited JSON bodies (`application/x-ndjson`), one document per
+//! line. The body is streamed line by line into the index writer, so that it is never buffered
+//! as a whole, and the documents indexed so far are committed periodically.
+
+use crate::{
+    check_new_index_name,
+    error::ServerError,
+    index_documents, indexing_task_failed,
+    keyword_search::{DocumentInput, IndexRequest, IndexResponse, TextTokenizer},
+    memory_budget, request_id, unique_key, MAX_UPLOAD_BYTES,
+};
+use axum::{body::Body, Json};
+use futures_util::StreamExt;
+use http::status::StatusCode;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Number of documents indexed between two commits
+pub(crate) const COMMIT_INTERVAL: usize = 10_000;
+
+// number of documents parsed ahead of the indexing
+const PARSED_DOCUMENTS_CAPACITY: usize = 1_024;
+
+// a document to index, or the error of reading it, with the name reported in its result
+type ParsedDocument = (Option<String>, Result<DocumentInput, String>);
+
+/// Options of an NDJSON indexing request, given as query parameters since the body only holds
+/// the documents
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct NdjsonParams {
+    /// Name of the index. If not provided, a name is generated.
+    #[serde(alias = "index_name")]
+    pub(crate) index: Option<String>,
+    /// Field identifying the documents, as the `unique_key` of JSON requests
+    pub(crate) unique_key: Option<String>,
+    /// Also index the title of each document into its body
+    #[serde(default)]
+    pub(crate) title_in_body: bool,
+    /// Accepted for compatibility, as the `commit_immediately` of JSON requests, with no effect
+    #[serde(default)]
+    pub(crate) commit_immediately: bool,
+    /// Skip the documents with the same content as an earlier one, as the `dedup` of JSON
+    /// requests
+    #[serde(default)]
+    pub(crate) dedup: bool,
+    /// Tokenizer of the `title` and `body` fields, as the `tokenizer` of JSON requests
+    #[serde(default)]
+    pub(crate) tokenizer: TextTokenizer,
+    /// Remove the English stop words, as the `remove_stopwords` of JSON requests
+    #[serde(default)]
+    pub(crate) remove_stopwords: bool,
+}
+
+// log the error of a request failing before any document is indexed, and fail it
+fn failed<T>(e: ServerError) -> Result<T, ServerError> {
+    error!("{}", &e);
+
+    Err(e)
+}
+
+/// Index the documents of an NDJSON body, one JSON object per line. The results of the lines
+/// which could not be parsed are reported with the line number as their filename.
+pub(crate) async fn process_ndjson(
+    params: NdjsonParams,
+    body: Body,
+) -> Result<(StatusCode, Json<IndexResponse>), ServerError> {
+    info!(index = ?params.index, "Starting NDJSON request processing");
+
+    if let Some(unique_key) = &params.unique_key {
+        if let Err(err_msg) = unique_key::validate(unique_key) {
+            return failed(ServerError::BadRequest(err_msg));
+        }
+    }
+
+    // the index is created under the given name only if it is valid and free
+    if let Err(e) = check_new_index_name(params.index.as_deref()) {
+        return failed(e);
+    }
+
+    let request = IndexRequest {
+        documents: Vec::new(),
+        index: params.index,
+        callback_url: None,
+        commit_immediately: params.commit_immediately,
+        title_in_body: params.title_in_body,
+        allow_empty: true,
+        search_defaults: None,
+        field_boosts: Default::default(),
+        preprocessing: None,
+        unique_key: params.unique_key,
+        dedup: params.dedup,
+        tokenizer: params.tokenizer,
+        remove_stopwords: params.remove_stopwords,
+        stopwords: Vec::new(),
+    };
+
+    // waiting for the memory of the index writer is async, so it is acquired beforehand
+    let writer_budget = memory_budget::acquire().await;
+
+    // the lines are parsed as they arrive, while the indexing runs off the async runtime along
+    // with the index writer
+    let (sender, mut receiver) = mpsc::channel::<ParsedDocument>(PARSED_DOCUMENTS_CAPACITY);
+    let indexing = request_id::spawn_blocking(move || {
+        let documents = std::iter::from_fn(|| receiver.blocking_recv());
+        index_documents(request, documents, Some(COMMIT_INTERVAL), writer_budget)
+    });
+
+    read_lines(body, sender).await;
+
+    match indexing.await {
+        Ok(response) => Ok(response),
+        Err(e) => Ok(indexing_task_failed(e)),
+    }
+}
+
+// parse the lines of `body` into documents sent to the indexing, until the body ends, fails, or
+// the indexing stops
+async fn read_lines(body: Body, sender: mpsc::Sender<ParsedDocument>) {
+    // each line is bounded, rather than the whole body
+    let max_line_bytes = MAX_UPLOAD_BYTES.get().copied().unwrap_or(usize::MAX);
+    let mut chunks = body.into_data_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut line_number = 0;
+    // bytes of the buffer known to hold no newline
+    let mut scanned = 0;
+
+    loop {
+        // the line is complete once a newline, or the end of the body, is reached
+        let newline = buffer[scanned..].iter().position(|byte| *byte == b'\n');
+        let line: Vec<u8> = match newline {
+            Some(end) => {
+                let line = buffer.drain(..=scanned + end).collect();
+                scanned = 0;
+                line
+            }
+            None if buffer.len() > max_line_bytes => {
+                let err_msg = format!(
+                    "The line exceeds the maximum size of {max_line_bytes} bytes, the remaining lines are skipped"
+                );
+                let _ = sender
+                    .send((Some(line_name(line_number + 1)), Err(err_msg)))
+                    .await;
+                return;
+            }
+            None => match chunks.next().await {
+                Some(Ok(chunk)) => {
+                    scanned = buffer.len();
+                    buffer.extend_from_slice(&chunk);
+                    continue;
+                }
+                Some(Err(e)) => {
+                    let err_msg = format!(
+                        "Failed to read the request body after line {line_number}, the remaining lines are skipped: {e}"
+                    );
+                    let _ = sender.send((None, Err(err_msg))).await;
+                    return;
+                }
+                None if buffer.is_empty() => return,
+                None => {
+                    scanned = 0;
+                    std::mem::take(&mut buffer)
+                }
+            },
+        };
+        line_number += 1;
+
+        // blank lines, e.g. a trailing one, are no documents
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+
+        let document = serde_json::from_slice::<DocumentInput>(&line)
+            .map_err(|e| format!("Invalid document: {e}"));
+        if sender
+            .send((Some(line_name(line_number)), document))
+            .await
+            .is_err()
+        {
+            // the indexing stopped, e.g. as the index could not be created
+            return;
+        }
+    }
+}
+
+// name of a line in the results
+fn line_name(line_number: usize) -> String {
+    format!("line {line_number}")
+}
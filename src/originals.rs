@@ -0,0 +1,178 @@
+//! Original bytes of the files uploaded to create an index, kept with `--store-originals` in the
+//! `originals` directory of the index so that users can retrieve exactly what they uploaded.
+
+use crate::{
+    aliases, error::ServerError, index_storage_dir, upload::TempUpload, validate_index_name,
+};
+use axum::{
+    body::Body,
+    extract::Path,
+    response::{IntoResponse, Response},
+};
+use std::path::{Path as FsPath, PathBuf};
+use tokio_util::io::ReaderStream;
+use tracing::{error, info, warn};
+
+/// Name of the directory of an index holding the original uploaded files
+pub(crate) const ORIGINALS_DIR: &str = "originals";
+
+/// Store the uploaded files in the `originals` directory of the index at `index_path`, under their
+/// file names. A file whose name is already taken is stored under the first free `{stem}-{n}` name.
+pub(crate) fn store(index_path: &FsPath, uploads: Vec<(String, TempUpload)>) {
+    if uploads.is_empty() {
+        return;
+    }
+
+    let originals_path = index_path.join(ORIGINALS_DIR);
+    if let Err(e) = std::fs::create_dir_all(&originals_path) {
+        error!(path = %originals_path.display(), error = %e, "Failed to create originals directory");
+        return;
+    }
+
+    for (filename, upload) in uploads {
+        let Some(filename) = sanitize_filename(&filename) else {
+            warn!(filename = %filename, "Not storing original file with an invalid name");
+            continue;
+        };
+
+        let destination = free_path(&originals_path, &filename);
+        match upload.persist(&destination) {
+            Ok(()) => info!(path = %destination.display(), "Stored original file"),
+            Err(e) => {
+                error!(path = %destination.display(), error = %e, "Failed to store original file")
+            }
+        }
+    }
+}
+
+// the last component of `filename`, if it is a plain file name
+fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = filename.rsplit(['/', '\\']).next()?;
+    let is_invalid = name.is_empty()
+        || name.starts_with('.')
+        || name.chars().any(|c| c.is_control() || c == '"');
+    match is_invalid {
+        true => None,
+        false => Some(name.to_string()),
+    }
+}
+
+// first path of the form `{stem}-{n}.{extension}` not taken yet, or `filename` if free
+fn free_path(originals_path: &FsPath, filename: &str) -> PathBuf {
+    let path = originals_path.join(filename);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) => (stem, format!(".{extension}")),
+        None => (filename, String::new()),
+    };
+    (1..)
+        .map(|n| originals_path.join(format!("{stem}-{n}{extension}")))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// return the original bytes of a file uploaded to create an index
+pub(crate) async fn original_file_handler(
+    Path((index_name, filename)): Path<(String, String)>,
+) -> Result<Response, ServerError> {
+    info!(index_name = %index_name, filename = %filename, "Received original file request");
+
+    // reject names that could escape the directory of the index or of its originals
+    if validate_index_name(&index_name).is_err()
+        || sanitize_filename(&filename).as_deref() != Some(filename.as_str())
+    {
+        let err_msg = format!("Invalid index name '{index_name}' or file name '{filename}'");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&index_name);
+    let path = index_storage_dir()
+        .join(&index_name)
+        .join(ORIGINALS_DIR)
+        .join(&filename);
+    // the file is streamed, so that large originals are not buffered in memory
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let err_msg = format!("No original file '{filename}' in index '{index_name}'");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::NotFound(err_msg));
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to read original file '{filename}': {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    let size_bytes = match file.metadata().await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => {
+            let err_msg = format!("Failed to read the metadata of original file '{filename}': {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    info!(index_name = %index_name, filename = %filename, size_bytes = size_bytes, "Returned original file");
+
+    Ok((
+        [
+            (
+                http::header::CONTENT_TYPE,
+                "application/octet-stream".to_string(),
+            ),
+            (
+                http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+            (http::header::CONTENT_LENGTH, size_bytes.to_string()),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{storage_dir, unique_index_name};
+
+    #[tokio::test]
+    async fn original_file_is_streamed_with_its_length() {
+        let index_name = unique_index_name("originals");
+        let originals_path = storage_dir().join(&index_name).join(ORIGINALS_DIR);
+        std::fs::create_dir_all(&originals_path).unwrap();
+        std::fs::write(originals_path.join("notes.txt"), "original bytes").unwrap();
+
+        let response = original_file_handler(Path((index_name, "notes.txt".to_string())))
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[http::header::CONTENT_LENGTH], "14");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"original bytes");
+    }
+
+    #[tokio::test]
+    async fn missing_original_file_is_not_found() {
+        let index_name = unique_index_name("originals");
+
+        let result = original_file_handler(Path((index_name, "missing.txt".to_string()))).await;
+
+        assert!(matches!(result, Err(ServerError::NotFound(_))));
+    }
+}
@@ -0,0 +1,48 @@
+//! Extraction of the text of uploaded PDF files, which is indexed as the body of a document.
+
+use crate::request_id;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+/// Whether an uploaded file is a PDF file, from its content type or, for a generic content type,
+/// from the extension of its name.
+pub(crate) fn is_pdf(content_type: &str, filename: Option<&str>) -> bool {
+    match content_type {
+        "application/pdf" => true,
+        "application/octet-stream" => filename
+            .and_then(|filename| filename.rsplit_once('.'))
+            .is_some_and(|(_, extension)| extension.eq_ignore_ascii_case("pdf")),
+        _ => false,
+    }
+}
+
+/// Extract the plain text of the PDF file at `path`. Fails if the file is not a valid PDF file,
+/// is encrypted, or has no text, e.g. a scanned document.
+pub(crate) async fn extract_text(path: &Path) -> Result<String, String> {
+    // parsing a PDF file is blocking work
+    let path = path.to_path_buf();
+    request_id::spawn_blocking(move || extract_text_blocking(path))
+        .await
+        .map_err(|e| format!("Failed to extract the text of the PDF file: {e}"))?
+}
+
+fn extract_text_blocking(path: PathBuf) -> Result<String, String> {
+    // the parser panics on some malformed files, which must only fail their upload
+    let extracted = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text(&path)))
+        .map_err(|_| {
+            "Failed to extract the text of the PDF file: malformed PDF file".to_string()
+        })?;
+
+    match extracted {
+        Ok(text) if text.trim().is_empty() => {
+            Err("The PDF file has no extractable text, e.g. it is a scanned document".to_string())
+        }
+        Ok(text) => Ok(text),
+        Err(pdf_extract::OutputError::PdfError(pdf_extract::Error::Decryption(_))) => {
+            Err("Encrypted PDF files are not supported".to_string())
+        }
+        Err(e) => Err(format!("Failed to extract the text of the PDF file: {e}")),
+    }
+}
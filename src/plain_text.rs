@@ -0,0 +1,108 @@
+//! Indexing of a single document sent as a plain-text body (`text/plain`), e.g. with
+//! `curl --data-binary @notes.txt`. The whole body is the content of the document, and its title
+//! is taken from the `X-Document-Title` header.
+
+use crate::{
+    error::ServerError,
+    keyword_search::{DocumentInput, IndexRequest, IndexResponse},
+    process_json, MAX_UPLOAD_BYTES,
+};
+use axum::{extract::FromRequest, extract::Request, Json};
+use http::{status::StatusCode, HeaderName};
+use serde::Deserialize;
+use tracing::{error, info};
+
+// header carrying the title of the document
+const DOCUMENT_TITLE_HEADER: HeaderName = HeaderName::from_static("x-document-title");
+
+/// Options of a plain-text indexing request, given as query parameters since the body only holds
+/// the content of the document
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct PlainTextParams {
+    /// Name of the index. If not provided, a name is generated.
+    #[serde(alias = "index_name")]
+    pub(crate) index: Option<String>,
+    /// Also index the title of the document into its body
+    #[serde(default)]
+    pub(crate) title_in_body: bool,
+    /// Accepted for compatibility, as the `commit_immediately` of JSON requests, with no effect
+    #[serde(default)]
+    pub(crate) commit_immediately: bool,
+}
+
+// log the error of a request failing before the document is indexed, and fail it
+fn failed<T>(e: ServerError) -> Result<T, ServerError> {
+    error!("{}", &e);
+
+    Err(e)
+}
+
+/// Index the body of `request` as the content of a single document, titled by the
+/// `X-Document-Title` header, if any.
+pub(crate) async fn process_plain_text(
+    params: PlainTextParams,
+    request: Request,
+) -> Result<(StatusCode, Json<IndexResponse>), ServerError> {
+    info!(index = ?params.index, "Starting plain-text request processing");
+
+    // the title may hold any UTF-8 text, which header values cannot be read as otherwise
+    let title = match request.headers().get(&DOCUMENT_TITLE_HEADER) {
+        Some(value) => match std::str::from_utf8(value.as_bytes()) {
+            Ok(title) => Some(title.trim().to_string()).filter(|title| !title.is_empty()),
+            Err(_) => {
+                return failed(ServerError::BadRequest(
+                    "The X-Document-Title header is not valid UTF-8".to_string(),
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let content = match String::from_request(request, &()).await {
+        Ok(content) => content,
+        Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+            return failed(ServerError::PayloadTooLarge(format!(
+                "The request exceeds the maximum upload size of {} bytes",
+                MAX_UPLOAD_BYTES.get().copied().unwrap_or_default()
+            )));
+        }
+        Err(e) => {
+            return failed(ServerError::BadRequest(format!(
+                "Failed to read the plain-text body: {e}"
+            )));
+        }
+    };
+
+    // an empty body would only leave an empty index behind
+    if content.trim().is_empty() {
+        return failed(ServerError::BadRequest(
+            "No content to index. The body of the request is empty".to_string(),
+        ));
+    }
+
+    let request = IndexRequest {
+        documents: vec![DocumentInput {
+            content,
+            title,
+            quality: None,
+            fields: Default::default(),
+            tags: Vec::new(),
+            created_at: None,
+        }],
+        index: params.index,
+        callback_url: None,
+        commit_immediately: params.commit_immediately,
+        title_in_body: params.title_in_body,
+        allow_empty: false,
+        search_defaults: None,
+        field_boosts: Default::default(),
+        preprocessing: None,
+        unique_key: None,
+        dedup: false,
+        tokenizer: Default::default(),
+        remove_stopwords: false,
+        stopwords: Vec::new(),
+    };
+
+    Ok(process_json(request).await)
+}
@@ -0,0 +1,231 @@
+//! Pipeline of transformations of the content of the documents before indexing, configured with
+//! the `--preprocess`, `--redact` and `--redact-regex` CLI options, or per request.
+
+use crate::keyword_search::{PiiPattern, Preprocessing};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+/// Replacement of the redacted personal data
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Step of the pipeline enabled with `--preprocess`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub(crate) enum PreprocessStep {
+    /// Remove control and zero-width characters
+    Cleanup,
+    /// Remove HTML markup
+    StripHtml,
+    /// Remove Markdown syntax
+    StripMarkdown,
+    /// Collapse runs of whitespace
+    NormalizeWhitespace,
+}
+
+/// Preprocessing enabling `steps`, and redacting the `redact` patterns and `redact_regex`
+/// regular expressions.
+pub(crate) fn from_options(
+    steps: &[PreprocessStep],
+    redact: &[PiiPattern],
+    redact_regex: &[String],
+) -> Preprocessing {
+    Preprocessing {
+        cleanup: steps.contains(&PreprocessStep::Cleanup),
+        strip_html: steps.contains(&PreprocessStep::StripHtml),
+        strip_markdown: steps.contains(&PreprocessStep::StripMarkdown),
+        redact: redact.to_vec(),
+        redact_regex: redact_regex.to_vec(),
+        normalize_whitespace: steps.contains(&PreprocessStep::NormalizeWhitespace),
+    }
+}
+
+/// Compiled preprocessing, applied to the content of each document
+#[derive(Debug, Clone)]
+pub(crate) struct Pipeline {
+    preprocessing: Preprocessing,
+    // built-in patterns, then custom regular expressions
+    redactions: Vec<Regex>,
+}
+
+impl Pipeline {
+    /// Compile `preprocessing`, failing if one of its regular expressions is invalid.
+    pub(crate) fn new(preprocessing: &Preprocessing) -> Result<Self, String> {
+        // patterns are matched from the most to the least specific, so that e.g. the digits of an
+        // IP address are not taken for a phone number
+        let mut redactions: Vec<Regex> = [
+            PiiPattern::CreditCard,
+            PiiPattern::Email,
+            PiiPattern::IpAddress,
+            PiiPattern::Phone,
+        ]
+        .into_iter()
+        .filter(|pattern| preprocessing.redact.contains(pattern))
+        .map(|pattern| pii_regex(pattern).clone())
+        .collect();
+        for regex in &preprocessing.redact_regex {
+            let regex = Regex::new(regex)
+                .map_err(|e| format!("Invalid redaction regular expression '{regex}': {e}"))?;
+            redactions.push(regex);
+        }
+
+        Ok(Self {
+            preprocessing: preprocessing.clone(),
+            redactions,
+        })
+    }
+
+    /// Apply the enabled steps to `content`.
+    pub(crate) fn apply(&self, mut content: String) -> String {
+        if self.preprocessing.cleanup {
+            content = cleanup(&content);
+        }
+        if self.preprocessing.strip_html {
+            content = strip_html(&content);
+        }
+        if self.preprocessing.strip_markdown {
+            content = strip_markdown(&content);
+        }
+        for regex in &self.redactions {
+            content = regex.replace_all(&content, REDACTED).into_owned();
+        }
+        if self.preprocessing.normalize_whitespace {
+            content = normalize_whitespace(&content);
+        }
+        content
+    }
+}
+
+// regular expression of a built-in pattern of personal data
+fn pii_regex(pattern: PiiPattern) -> &'static Regex {
+    static EMAIL: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9.-]+\.[A-Z]{2,}\b").unwrap());
+    static PHONE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?|\b)\d{3}[ .-]\d{3,4}(?:[ .-]\d{4})?\b",
+        )
+        .unwrap()
+    });
+    static IP_ADDRESS: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"\b(?:(?:25[0-5]|2[0-4]\d|1?\d?\d)\.){3}(?:25[0-5]|2[0-4]\d|1?\d?\d)\b")
+            .unwrap()
+    });
+    static CREDIT_CARD: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").unwrap());
+
+    match pattern {
+        PiiPattern::Email => &EMAIL,
+        PiiPattern::Phone => &PHONE,
+        PiiPattern::IpAddress => &IP_ADDRESS,
+        PiiPattern::CreditCard => &CREDIT_CARD,
+    }
+}
+
+// remove control characters other than line breaks and tabs, and zero-width characters, and
+// replace non-breaking spaces with spaces
+fn cleanup(content: &str) -> String {
+    content
+        .chars()
+        .filter(|&c| {
+            (!c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+                && !matches!(c, '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}')
+        })
+        .map(|c| match c {
+            '\u{00A0}' | '\u{202F}' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+// remove the HTML markup, breaking lines at block elements, and decode character references
+fn strip_html(content: &str) -> String {
+    static SCRIPTS: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->").unwrap()
+    });
+    static BLOCK_TAGS: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?i)</?(?:address|article|aside|blockquote|br|dd|div|dl|dt|footer|h[1-6]|header|hr|li|main|nav|ol|p|pre|section|table|td|th|tr|ul)\b[^>]*>",
+        )
+        .unwrap()
+    });
+    static TAGS: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+    static REFERENCES: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)&(?:#(\d+)|#x([0-9a-f]+)|([a-z]+));").unwrap());
+
+    let content = SCRIPTS.replace_all(content, " ");
+    let content = BLOCK_TAGS.replace_all(&content, "\n");
+    let content = TAGS.replace_all(&content, "");
+    REFERENCES
+        .replace_all(&content, |captures: &Captures| {
+            let decoded = match (captures.get(1), captures.get(2), captures.get(3)) {
+                (Some(decimal), _, _) => decimal.as_str().parse().ok().and_then(char::from_u32),
+                (_, Some(hex), _) => u32::from_str_radix(hex.as_str(), 16)
+                    .ok()
+                    .and_then(char::from_u32),
+                (_, _, Some(name)) => match name.as_str().to_ascii_lowercase().as_str() {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match decoded {
+                Some(c) => c.to_string(),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+// remove the Markdown syntax, keeping the text it marks up
+fn strip_markdown(content: &str) -> String {
+    static RULES: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+        [
+            // code fences
+            (r"(?m)^[ \t]*(?:```|~~~).*$", ""),
+            // horizontal rules, before they are taken for list items
+            (
+                r"(?m)^[ \t]{0,3}(?:(?:-[ \t]*){3,}|(?:\*[ \t]*){3,}|(?:_[ \t]*){3,})$",
+                "",
+            ),
+            // headings
+            (r"(?m)^[ \t]{0,3}#{1,6}[ \t]+", ""),
+            // block quotes
+            (r"(?m)^[ \t]{0,3}>[ \t]?", ""),
+            // list items
+            (r"(?m)^([ \t]*)(?:[-*+]|\d+[.)])[ \t]+", "$1"),
+            // images and links
+            (r"!?\[([^\]]*)\]\([^)]*\)", "$1"),
+            // emphasis, strikethrough and inline code
+            (r"\*\*([^*\n]+)\*\*", "$1"),
+            (r"__([^_\n]+)__", "$1"),
+            (r"\*([^*\n]+)\*", "$1"),
+            (r"\b_([^_\n]+)_\b", "$1"),
+            (r"~~([^~\n]+)~~", "$1"),
+            (r"`([^`\n]+)`", "$1"),
+        ]
+        .into_iter()
+        .map(|(regex, replacement)| (Regex::new(regex).unwrap(), replacement))
+        .collect()
+    });
+
+    RULES
+        .iter()
+        .fold(content.to_string(), |content, (regex, replacement)| {
+            regex.replace_all(&content, *replacement).into_owned()
+        })
+}
+
+// collapse runs of spaces and tabs, trim the lines, and collapse runs of blank lines
+fn normalize_whitespace(content: &str) -> String {
+    static SPACES: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t]+").unwrap());
+    static BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").unwrap());
+
+    let content = SPACES.replace_all(content, " ");
+    let content: Vec<&str> = content.lines().map(str::trim).collect();
+    let content = content.join("\n");
+    BLANK_LINES.replace_all(&content, "\n\n").trim().to_string()
+}
@@ -0,0 +1,136 @@
+//! Detection of the field references of a query, e.g. `author` in `author:smith`, or
+//! `fields.city` in `fields.city:paris` for a path within a JSON field, so that references to
+//! fields missing from the schema can be reported or neutralized before parsing.
+
+use std::ops::Range;
+use tantivy::schema::{Field, FieldType, Schema};
+
+/// A `field:` reference of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FieldReference {
+    /// Name of the referenced field
+    pub(crate) name: String,
+    /// Byte range of the reference within the query, including the trailing `:`
+    pub(crate) range: Range<usize>,
+}
+
+/// Return the field references of `query`, ignoring the ones within quoted phrases.
+pub(crate) fn field_references(query: &str) -> Vec<FieldReference> {
+    let mut references = Vec::new();
+    let mut in_phrase = false;
+    let mut word_start = true;
+    let mut chars = query.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        if c == '"' {
+            in_phrase = !in_phrase;
+            word_start = false;
+            continue;
+        }
+        if in_phrase || !word_start || !is_field_name_char(c) {
+            word_start = !in_phrase && (c.is_whitespace() || matches!(c, '(' | '+' | '-'));
+            continue;
+        }
+
+        // read the rest of the word, which is a reference if it ends with `:`
+        let mut end = offset + c.len_utf8();
+        while let Some(&(next_offset, next)) = chars.peek() {
+            if !is_field_name_char(next) && next != '.' {
+                break;
+            }
+            end = next_offset + next.len_utf8();
+            chars.next();
+        }
+        if let Some(&(colon_offset, ':')) = chars.peek() {
+            references.push(FieldReference {
+                name: query[offset..end].to_string(),
+                range: offset..colon_offset + 1,
+            });
+            chars.next();
+        }
+        word_start = false;
+    }
+
+    references
+}
+
+/// Return the references of `query` to fields missing from `schema`. A path within a JSON field,
+/// e.g. `fields.city`, is known if the JSON field is.
+pub(crate) fn unknown_field_references(query: &str, schema: &Schema) -> Vec<FieldReference> {
+    field_references(query)
+        .into_iter()
+        .filter(|reference| match schema.find_field(&reference.name) {
+            Some((_, "")) => false,
+            Some((field, _)) => !matches!(
+                schema.get_field_entry(field).field_type(),
+                FieldType::JsonObject(_)
+            ),
+            None => true,
+        })
+        .collect()
+}
+
+/// Turn the given references of `query` into plain words, e.g. `author:smith` into
+/// `author smith`, so that they are searched in the default fields.
+pub(crate) fn neutralize(query: &str, references: &[FieldReference]) -> String {
+    let mut neutralized = query.to_string();
+    // replace from the end, so that the ranges of the remaining references stay valid
+    for reference in references.iter().rev() {
+        neutralized.replace_range(reference.range.end - 1..reference.range.end, " ");
+    }
+    neutralized
+}
+
+/// Return the names of the indexed fields of `schema`, which can be referenced in queries.
+pub(crate) fn queryable_fields(schema: &Schema) -> Vec<String> {
+    schema
+        .fields()
+        .filter(|(_, entry)| entry.is_indexed())
+        .map(|(_, entry)| entry.name().to_string())
+        .collect()
+}
+
+// Whether `c` can be part of a field name. Field references may also contain `.` after their
+// first character, separating the path within a JSON field.
+fn is_field_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Return the fields searched by the terms of a query without a field reference: the fields
+/// named in `names`, or the body if none. Only the indexed text and JSON fields of `schema` can be
+/// searched by default.
+pub(crate) fn default_fields(names: &[String], schema: &Schema) -> Result<Vec<Field>, String> {
+    if names.is_empty() {
+        return schema
+            .get_field("body")
+            .map(|body| vec![body])
+            .map_err(|e| e.to_string());
+    }
+
+    names
+        .iter()
+        .map(|name| match schema.get_field(name) {
+            Ok(field) if is_text_field(schema, field) => Ok(field),
+            _ => {
+                let text_fields: Vec<&str> = schema
+                    .fields()
+                    .filter(|(field, _)| is_text_field(schema, *field))
+                    .map(|(_, entry)| entry.name())
+                    .collect();
+                Err(format!(
+                    "Invalid search field '{name}'. Valid fields are: {}",
+                    text_fields.join(", ")
+                ))
+            }
+        })
+        .collect()
+}
+
+// Whether `field` is an indexed text or JSON field, whose words can be searched
+fn is_text_field(schema: &Schema, field: Field) -> bool {
+    let entry = schema.get_field_entry(field);
+    entry.is_indexed()
+        && matches!(
+            entry.field_type(),
+            FieldType::Str(_) | FieldType::JsonObject(_)
+        )
+}
@@ -0,0 +1,216 @@
+//! Collection of the top documents of a search, ordered by relevance, by a fast field, or by
+//! relevance blended with the `quality` of the documents.
+
+use crate::{
+    deadline::{Deadline, TimeBounded},
+    keyword_search::{PreferLength, SortOrder},
+    search_executor,
+};
+use tantivy::{
+    collector::{Collector, TopDocs},
+    query::Query,
+    schema::{FieldType, Schema},
+    time::{format_description::well_known::Rfc3339, OffsetDateTime},
+    DateTime, DocAddress, DocId, Score, Searcher, SegmentReader,
+};
+
+/// Name of the fast field holding the quality of a document
+pub(crate) const QUALITY_FIELD: &str = "quality";
+
+/// Name of the fast field holding the length of the body of a document, in characters
+pub(crate) const BODY_LENGTH_FIELD: &str = "body_length";
+
+/// Name of the fast field holding the creation date of a document, if provided
+pub(crate) const CREATED_AT_FIELD: &str = "created_at";
+
+// maximum ratio between the scores of hits considered similarly relevant, which are ordered by
+// length if a length is preferred
+const SIMILAR_SCORE_RATIO: Score = 1.1;
+
+/// How the top documents of a search are ranked
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Ranking {
+    /// Fast field the documents are sorted by, and its order. Ties are broken by relevance.
+    pub(crate) sort_by: Option<(String, SortOrder)>,
+    /// Weight of the `quality` field added to the relevance score
+    pub(crate) quality_weight: Option<f32>,
+}
+
+/// Check that `field_name` can be used to sort search results, i.e. it is a numeric or date field
+/// declared `FAST` in the schema.
+pub(crate) fn validate_sort_field(schema: &Schema, field_name: &str) -> Result<(), String> {
+    let field = schema
+        .get_field(field_name)
+        .map_err(|_| format!("Unknown sort field `{field_name}`"))?;
+    let entry = schema.get_field_entry(field);
+
+    let is_sortable = matches!(
+        entry.field_type(),
+        FieldType::U64(_) | FieldType::I64(_) | FieldType::F64(_) | FieldType::Date(_)
+    );
+    if !is_sortable || !entry.is_fast() {
+        return Err(format!(
+            "Field `{field_name}` cannot be used for sorting. Only numeric and date fields declared `FAST` are supported"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parse the RFC 3339 creation date of a document, e.g. `2024-05-01T12:00:00Z`.
+pub(crate) fn parse_date(date: &str) -> Result<DateTime, String> {
+    OffsetDateTime::parse(date, &Rfc3339)
+        .map(DateTime::from_utc)
+        .map_err(|e| {
+            format!("Invalid `{CREATED_AT_FIELD}` date '{date}'. Expected an RFC 3339 date, e.g. `2024-05-01T12:00:00Z`: {e}")
+        })
+}
+
+/// Format a creation date as an RFC 3339 date in UTC.
+pub(crate) fn format_date(date: DateTime) -> Option<String> {
+    date.into_utc().format(&Rfc3339).ok()
+}
+
+/// Top documents of a search, with their scores
+pub(crate) type TopDocsWithScores = Vec<(Score, DocAddress)>;
+
+/// Collect the `limit` top documents matching `query`, ranked according to `ranking`, along with
+/// the fruit of `extra`, collected in the same pass over the matching documents. The returned
+/// scores are the relevance scores, blended with the quality if requested. With a `deadline`, the
+/// documents matching before it passes are collected.
+pub(crate) fn collect_top_docs<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    limit: usize,
+    ranking: &Ranking,
+    extra: C,
+    deadline: Option<&Deadline>,
+) -> tantivy::Result<(TopDocsWithScores, C::Fruit)> {
+    let quality_weight = ranking.quality_weight.unwrap_or(0.0);
+
+    // score of a document, blended with its quality
+    let blended_score = move |segment_reader: &SegmentReader| {
+        let quality = segment_reader
+            .fast_fields()
+            .f64(QUALITY_FIELD)
+            .ok()
+            .map(|column| column.first_or_default_col(0.0));
+
+        move |doc: DocId, score: Score| match &quality {
+            Some(quality) if quality_weight != 0.0 => {
+                score + quality_weight * quality.get_val(doc) as Score
+            }
+            _ => score,
+        }
+    };
+
+    match &ranking.sort_by {
+        None if quality_weight == 0.0 => search(
+            searcher,
+            query,
+            (TopDocs::with_limit(limit), extra),
+            deadline,
+        ),
+        None => search(
+            searcher,
+            query,
+            (TopDocs::with_limit(limit).tweak_score(blended_score), extra),
+            deadline,
+        ),
+        Some((field_name, order)) => {
+            let field_name = field_name.clone();
+            let order = *order;
+
+            // sort by the value of the field, mapped monotonically to `u64`, and then by score.
+            // Documents without a value are ranked last in both orders.
+            let collector =
+                TopDocs::with_limit(limit).tweak_score(move |segment_reader: &SegmentReader| {
+                    let column = segment_reader
+                        .fast_fields()
+                        .u64_lenient(&field_name)
+                        .ok()
+                        .flatten()
+                        .map(|(column, _)| column);
+                    let score_of = blended_score(segment_reader);
+
+                    move |doc: DocId, score: Score| {
+                        let value = column.as_ref().and_then(|column| column.first(doc));
+                        let key = match (value, order) {
+                            (Some(value), SortOrder::Desc) => value,
+                            (Some(value), SortOrder::Asc) => u64::MAX - value,
+                            (None, _) => 0,
+                        };
+                        (key, score_of(doc, score))
+                    }
+                });
+
+            let (top_docs, extra_fruit) = search(searcher, query, (collector, extra), deadline)?;
+            let top_docs = top_docs
+                .into_iter()
+                .map(|((_, score), doc_address)| (score, doc_address))
+                .collect();
+            Ok((top_docs, extra_fruit))
+        }
+    }
+}
+
+// search `query` with `collector`, within `deadline` if any
+fn search<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    collector: C,
+    deadline: Option<&Deadline>,
+) -> tantivy::Result<C::Fruit> {
+    match deadline {
+        Some(deadline) => search_executor::search(
+            searcher,
+            query,
+            &TimeBounded::new(collector, deadline.clone()),
+        ),
+        None => search_executor::search(searcher, query, &collector),
+    }
+}
+
+/// Order the hits with similar scores by length, as preferred. `top_docs` must be sorted by
+/// decreasing score. Each group of similar hits starts at the highest-scoring hit not grouped yet,
+/// and holds the following hits scoring at least its score divided by `SIMILAR_SCORE_RATIO`.
+/// Documents without a length, i.e. indexed by older versions, are ordered last within their
+/// group.
+pub(crate) fn prefer_length(
+    searcher: &Searcher,
+    top_docs: &mut TopDocsWithScores,
+    prefer_length: PreferLength,
+) {
+    if prefer_length == PreferLength::None {
+        return;
+    }
+
+    let length_of = |doc_address: DocAddress| {
+        searcher
+            .segment_reader(doc_address.segment_ord)
+            .fast_fields()
+            .u64(BODY_LENGTH_FIELD)
+            .ok()
+            .and_then(|column| column.first(doc_address.doc_id))
+    };
+
+    let mut start = 0;
+    while start < top_docs.len() {
+        let threshold = top_docs[start].0 / SIMILAR_SCORE_RATIO;
+        let end = top_docs[start..]
+            .iter()
+            .position(|(score, _)| *score < threshold)
+            .map_or(top_docs.len(), |len| start + len);
+
+        // the sort is stable, so hits of the same length stay ordered by score
+        top_docs[start..end].sort_by_key(|(_, doc_address)| {
+            match (length_of(*doc_address), prefer_length) {
+                (Some(length), PreferLength::Long) => (false, u64::MAX - length),
+                (Some(length), _) => (false, length),
+                (None, _) => (true, 0),
+            }
+        });
+
+        start = end;
+    }
+}
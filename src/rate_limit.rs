@@ -0,0 +1,157 @@
+//! Middleware limiting the rate of the requests of each client, identified by its IP address, with
+//! a token bucket. Every response of a rate-limited server tells the client the state of its
+//! bucket, so that well-behaved clients can throttle themselves before being rejected:
+//!
+//! - `X-RateLimit-Limit`: capacity of the bucket, i.e. the number of requests allowed per minute
+//! - `X-RateLimit-Remaining`: number of requests the client can still send right away
+//! - `X-RateLimit-Reset`: number of seconds until the bucket is full again
+
+use crate::error::ServerError;
+use axum::{
+    extract::{ConnectInfo, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::Instant,
+};
+use tracing::warn;
+
+// number of tracked clients above which the clients with a full bucket are forgotten
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+// rate limiter, if rate limiting is enabled
+static RATE_LIMITER: OnceCell<RateLimiter> = OnceCell::new();
+
+struct RateLimiter {
+    // capacity of the buckets
+    requests_per_minute: u32,
+    // buckets of the clients
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// State of the bucket of a client after a request
+#[derive(Debug, Clone, Copy)]
+struct BucketState {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset_secs: u64,
+    retry_after_secs: u64,
+}
+
+/// Enable rate limiting, allowing each client `requests_per_minute` requests per minute, in
+/// bursts of at most as many requests.
+pub(crate) fn init(requests_per_minute: u32) -> Result<(), String> {
+    if requests_per_minute == 0 {
+        return Err("The rate limit must be at least 1 request per minute".to_string());
+    }
+
+    RATE_LIMITER
+        .set(RateLimiter {
+            requests_per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        })
+        .map_err(|_| "The rate limit is already set".to_string())
+}
+
+/// Number of requests per minute allowed to each client, if rate limiting is enabled
+pub(crate) fn requests_per_minute() -> Option<u32> {
+    RATE_LIMITER
+        .get()
+        .map(|rate_limiter| rate_limiter.requests_per_minute)
+}
+
+impl RateLimiter {
+    // take a token from the bucket of `client`, if any is left
+    fn acquire(&self, client: IpAddr) -> BucketState {
+        let capacity = self.requests_per_minute as f64;
+        let tokens_per_sec = capacity / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+                bucket.tokens + elapsed * tokens_per_sec < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_sec).min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        BucketState {
+            allowed,
+            limit: self.requests_per_minute,
+            remaining: bucket.tokens.floor() as u32,
+            reset_secs: ((capacity - bucket.tokens) / tokens_per_sec).ceil() as u64,
+            retry_after_secs: ((1.0 - bucket.tokens).max(0.0) / tokens_per_sec).ceil() as u64,
+        }
+    }
+}
+
+/// Reject the requests of clients that exhausted their bucket with `429 Too Many Requests`, and
+/// add the rate-limit headers to every response.
+pub(crate) async fn limit_rate(request: Request, next: Next) -> Response {
+    let Some(rate_limiter) = RATE_LIMITER.get() else {
+        return next.run(request).await;
+    };
+
+    // requests without a peer address, which do not come from the listener, are not limited
+    let Some(ConnectInfo(peer)) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .copied()
+    else {
+        return next.run(request).await;
+    };
+
+    let state = rate_limiter.acquire(peer.ip());
+    let mut response = match state.allowed {
+        true => next.run(request).await,
+        false => {
+            warn!(client = %peer.ip(), path = %request.uri().path(), "Rate limit exceeded");
+
+            let mut response = ServerError::RateLimited(
+                "Rate limit exceeded. Retry after the number of seconds in the `Retry-After` header"
+                    .to_string(),
+            )
+            .into_response();
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(state.retry_after_secs));
+            response
+        }
+    };
+
+    insert_headers(response.headers_mut(), state);
+    response
+}
+
+// add the headers telling the client the state of its bucket
+fn insert_headers(headers: &mut HeaderMap, state: BucketState) {
+    headers.insert("x-ratelimit-limit", HeaderValue::from(state.limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(state.remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(state.reset_secs));
+}
@@ -0,0 +1,200 @@
+//! Bounded LRU cache of opened indexes and their readers, shared by the search handlers.
+
+use crate::{token_length, READER_CACHE_SIZE};
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{num::NonZeroUsize, path::Path, sync::Mutex};
+use tantivy::{Index, IndexReader, ReloadPolicy};
+use tracing::{debug, info};
+
+// cache of opened indexes, keyed by index name
+static READER_CACHE: Lazy<Mutex<ReaderCache>> = Lazy::new(|| {
+    let capacity = READER_CACHE_SIZE.get().copied().unwrap_or_default();
+    Mutex::new(ReaderCache::new(capacity))
+});
+
+/// Counters of the reader cache
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub(crate) struct ReaderCacheStats {
+    /// Maximum number of cached indexes. `0` means caching is disabled.
+    pub(crate) capacity: usize,
+    /// Number of cached indexes
+    pub(crate) size: usize,
+    /// Number of lookups served from the cache
+    pub(crate) hits: u64,
+    /// Number of lookups that opened the index
+    pub(crate) misses: u64,
+    /// Number of indexes evicted to make room for another one
+    pub(crate) evictions: u64,
+}
+
+struct ReaderCache {
+    entries: Option<LruCache<String, (Index, IndexReader)>>,
+    stats: ReaderCacheStats,
+}
+
+impl ReaderCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity).map(LruCache::new),
+            stats: ReaderCacheStats {
+                capacity,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Return the index and reader of `index_name`, opening the index located at `index_path` if it
+/// is not cached yet. The least-recently-used index is evicted if the cache is full.
+pub(crate) fn get_or_open(
+    index_name: &str,
+    index_path: &Path,
+) -> tantivy::Result<(Index, IndexReader)> {
+    get_or_open_in(&READER_CACHE, index_name, index_path)
+}
+
+// look `index_name` up in `cache`, opening the index at `index_path` on a miss
+fn get_or_open_in(
+    cache: &Mutex<ReaderCache>,
+    index_name: &str,
+    index_path: &Path,
+) -> tantivy::Result<(Index, IndexReader)> {
+    {
+        let mut cache = cache.lock().unwrap();
+        let cached = cache
+            .entries
+            .as_mut()
+            .and_then(|entries| entries.get(index_name).cloned());
+        match cached {
+            Some(entry) => {
+                cache.stats.hits += 1;
+                debug!(index_name = %index_name, "Reader cache hit");
+                return Ok(entry);
+            }
+            None => cache.stats.misses += 1,
+        }
+    }
+
+    // open the index outside of the lock, so that a slow open does not block other searches
+    info!(path = %index_path.display(), "Opening index");
+    let index = Index::open_in_dir(index_path)?;
+    token_length::register_from_metadata(&index);
+    let reader: IndexReader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()?;
+
+    let mut cache = cache.lock().unwrap();
+    if let Some(entries) = cache.entries.as_mut() {
+        let evicted = entries.push(index_name.to_string(), (index.clone(), reader.clone()));
+        if let Some((evicted_name, _)) = evicted.filter(|(name, _)| name != index_name) {
+            cache.stats.evictions += 1;
+            info!(index_name = %evicted_name, "Evicted index from reader cache");
+        }
+    }
+
+    Ok((index, reader))
+}
+
+/// Remove `index_name` from the cache, releasing its resources once no search uses it anymore.
+pub(crate) fn invalidate(index_name: &str) {
+    invalidate_in(&READER_CACHE, index_name);
+}
+
+// remove `index_name` from `cache`
+fn invalidate_in(cache: &Mutex<ReaderCache>, index_name: &str) {
+    let mut cache = cache.lock().unwrap();
+    if let Some(entries) = cache.entries.as_mut() {
+        entries.pop(index_name);
+    }
+}
+
+/// Return the counters of the cache.
+pub(crate) fn stats() -> ReaderCacheStats {
+    let cache = READER_CACHE.lock().unwrap();
+    ReaderCacheStats {
+        size: cache
+            .entries
+            .as_ref()
+            .map(LruCache::len)
+            .unwrap_or_default(),
+        ..cache.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::{
+        doc,
+        schema::{Schema, TEXT},
+    };
+
+    // create an index holding one document in a new directory under `dir`
+    fn create_index(dir: &Path, name: &str) -> std::path::PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir(&path).unwrap();
+        let mut schema_builder = Schema::builder();
+        let body = schema_builder.add_text_field("body", TEXT);
+        let index = Index::create_in_dir(&path, schema_builder.build()).unwrap();
+        let mut writer = index.writer_with_num_threads(1, 15_000_000).unwrap();
+        writer.add_document(doc!(body => "cached")).unwrap();
+        writer.commit().unwrap();
+        path
+    }
+
+    #[test]
+    fn repeated_lookups_do_not_reopen_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create_index(dir.path(), "docs");
+        let cache = Mutex::new(ReaderCache::new(2));
+
+        let (_, reader) = get_or_open_in(&cache, "docs", &path).unwrap();
+        assert_eq!(reader.searcher().num_docs(), 1);
+
+        // once cached, the index is served without touching its directory
+        std::fs::remove_dir_all(&path).unwrap();
+        for _ in 0..3 {
+            let (_, reader) = get_or_open_in(&cache, "docs", &path).unwrap();
+            assert_eq!(reader.searcher().num_docs(), 1);
+        }
+        let stats = cache.lock().unwrap().stats;
+        assert_eq!((stats.misses, stats.hits), (1, 3));
+
+        // after invalidation, the index is opened again, and is gone
+        invalidate_in(&cache, "docs");
+        assert!(get_or_open_in(&cache, "docs", &path).is_err());
+        assert_eq!(cache.lock().unwrap().stats.misses, 2);
+    }
+
+    #[test]
+    fn least_recently_used_index_is_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = create_index(dir.path(), "first");
+        let second = create_index(dir.path(), "second");
+        let cache = Mutex::new(ReaderCache::new(1));
+
+        get_or_open_in(&cache, "first", &first).unwrap();
+        get_or_open_in(&cache, "second", &second).unwrap();
+        get_or_open_in(&cache, "first", &first).unwrap();
+
+        let stats = cache.lock().unwrap().stats;
+        assert_eq!((stats.misses, stats.hits, stats.evictions), (3, 0, 2));
+    }
+
+    #[test]
+    fn zero_capacity_disables_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = create_index(dir.path(), "docs");
+        let cache = Mutex::new(ReaderCache::new(0));
+
+        get_or_open_in(&cache, "docs", &path).unwrap();
+        get_or_open_in(&cache, "docs", &path).unwrap();
+
+        let cache = cache.lock().unwrap();
+        assert!(cache.entries.is_none());
+        assert_eq!((cache.stats.misses, cache.stats.hits), (2, 0));
+    }
+}
@@ -0,0 +1,50 @@
+//! Correlation of the log lines of a request. Each request gets an id, taken from its
+//! `X-Request-Id` header or generated, which is recorded on a span wrapping the handling of the
+//! request, and echoed back in the `X-Request-Id` header of the response.
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use http::{HeaderName, HeaderValue};
+use tokio::task::JoinHandle;
+use tracing::{info_span, Instrument, Span};
+
+// header carrying the id of a request and of its response
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// maximum length of the ids sent by clients, longer ones are replaced by a generated id
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Handle each request in a span recording its id, and echo the id in the response.
+pub(crate) async fn set_request_id(request: Request, next: Next) -> Response {
+    // ids are logged, so only short printable ones are kept from the clients
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|byte| byte.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = info_span!("request", request_id = %request_id);
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
+/// Run the blocking function `f` on the blocking threads of the runtime, in the span of the
+/// current request, so that its log lines carry the id of the request.
+pub(crate) fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
+}
@@ -0,0 +1,585 @@
+//! Upload of an index archive, as produced by the download endpoint, to restore it on the server,
+//! either via `POST /v1/index/upload` or under the name in the path via
+//! `POST /v1/files/upload/{index_name}`.
+//!
+//! The archive is unpacked and validated in a staging directory, which is then renamed into place,
+//! so a failed upload never leaves a partial index behind.
+
+use crate::{
+    archive, error::ServerError, index_storage_dir, reader_cache, request_id, upload,
+    validate_new_index_name,
+};
+use axum::{
+    extract::{multipart::Field, FromRequest, Multipart, Path as UrlPath, Request},
+    Json,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tantivy::Index;
+use tracing::{error, info, warn};
+
+/// Maximum size of an uploaded index archive
+pub(crate) const MAX_INDEX_UPLOAD_BYTES: usize = 1 << 30;
+
+// maximum total size of the files of an uploaded index archive, once unpacked, so that a small
+// archive cannot fill the disk
+const MAX_UNPACKED_INDEX_BYTES: u64 = 4 * MAX_INDEX_UPLOAD_BYTES as u64;
+
+/// What to do when the name of an uploaded index is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum OnConflict {
+    /// Reject the upload with `409 Conflict`
+    #[default]
+    Reject,
+    /// Replace the existing index
+    Overwrite,
+    /// Restore the index under the name suffixed with the first free `-{n}`
+    Rename,
+}
+
+/// Response body of the `POST /v1/index/upload` endpoint
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UploadResponse {
+    /// Name the index was restored under
+    pub(crate) index_name: String,
+    /// Whether an existing index was replaced
+    pub(crate) overwritten: bool,
+    /// Whether the index was restored under another name than requested
+    pub(crate) renamed: bool,
+}
+
+// restore an index from an archive uploaded as the `file` field of a multipart request
+pub(crate) async fn upload_index_handler(
+    mut multipart: Multipart,
+) -> Result<Json<UploadResponse>, ServerError> {
+    info!("Received index upload request");
+
+    let mut index_name: Option<String> = None;
+    let mut on_conflict = OnConflict::default();
+    let mut archive = None;
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return bad_request(format!("Failed to read multipart field: {e}")),
+        };
+
+        match field.name().unwrap_or_default() {
+            "index" => match field.text().await {
+                Ok(text) => index_name = Some(text.trim_matches('"').to_string()),
+                Err(e) => return bad_request(format!("Failed to read index field: {e}")),
+            },
+            "on_conflict" => {
+                let value = match field.text().await {
+                    Ok(text) => text,
+                    Err(e) => return bad_request(format!("Failed to read on_conflict field: {e}")),
+                };
+                on_conflict = match value.trim_matches('"') {
+                    "reject" => OnConflict::Reject,
+                    "overwrite" => OnConflict::Overwrite,
+                    "rename" => OnConflict::Rename,
+                    _ => {
+                        return bad_request(format!(
+                        "Invalid on_conflict '{value}'. Expected one of: reject, overwrite, rename"
+                    ))
+                    }
+                };
+            }
+            "file" => match store_archive(field).await {
+                Ok(upload) => archive = Some(upload),
+                Err(e) => return upload_failed(e),
+            },
+            name => warn!(field_name = %name, "Ignoring unknown multipart field"),
+        }
+    }
+
+    let Some(archive) = archive else {
+        return bad_request("Missing `file` field holding the index archive".to_string());
+    };
+    let index_name = index_name.unwrap_or_else(|| format!("index-{}", uuid::Uuid::new_v4()));
+
+    spawn_restore(archive, index_name, on_conflict).await
+}
+
+// restore an index under the name in the path from an archive uploaded as the raw request body,
+// or as the `file` field of a multipart request. An existing index is never replaced.
+pub(crate) async fn restore_named_index_handler(
+    UrlPath(index_name): UrlPath<String>,
+    request: Request,
+) -> Result<Json<UploadResponse>, ServerError> {
+    info!(index_name = %index_name, "Received named index upload request");
+
+    let is_multipart = request
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+    let archive = match is_multipart {
+        true => {
+            let mut multipart = match Multipart::from_request(request, &()).await {
+                Ok(multipart) => multipart,
+                Err(e) => return bad_request(format!("Failed to parse multipart request: {e}")),
+            };
+            let mut archive = None;
+            loop {
+                let field = match multipart.next_field().await {
+                    Ok(Some(field)) => field,
+                    Ok(None) => break,
+                    Err(e) => return bad_request(format!("Failed to read multipart field: {e}")),
+                };
+
+                match field.name().unwrap_or_default() {
+                    "file" => match store_archive(field).await {
+                        Ok(upload) => archive = Some(upload),
+                        Err(e) => return upload_failed(e),
+                    },
+                    name => warn!(field_name = %name, "Ignoring unknown multipart field"),
+                }
+            }
+            match archive {
+                Some(archive) => archive,
+                None => {
+                    return bad_request(
+                        "Missing `file` field holding the index archive".to_string(),
+                    )
+                }
+            }
+        }
+        false => {
+            match upload::stream_body_to_temp_file(
+                request.into_body(),
+                MAX_INDEX_UPLOAD_BYTES as u64,
+            )
+            .await
+            {
+                Ok(archive) => archive,
+                Err(e) => return upload_failed(e),
+            }
+        }
+    };
+
+    spawn_restore(archive, index_name, OnConflict::Reject).await
+}
+
+// store the archive of the multipart field `field` in a temporary file, rejecting archives larger
+// than `MAX_INDEX_UPLOAD_BYTES` like raw request bodies
+async fn store_archive(field: Field<'_>) -> Result<upload::TempUpload, upload::UploadError> {
+    upload::stream_to_temp_file(field, Some(MAX_INDEX_UPLOAD_BYTES as u64)).await
+}
+
+// restore the index of `archive` off the async runtime, as unpacking, validating and moving an
+// index of up to `MAX_INDEX_UPLOAD_BYTES` is blocking work
+async fn spawn_restore(
+    archive: upload::TempUpload,
+    index_name: String,
+    on_conflict: OnConflict,
+) -> Result<Json<UploadResponse>, ServerError> {
+    let index_storage_dir = index_storage_dir();
+    let restored = request_id::spawn_blocking(move || {
+        restore(archive, &index_storage_dir, &index_name, on_conflict)
+    });
+    match restored.await {
+        Ok(restored) => restored,
+        Err(e) => {
+            let err_msg = format!("Failed to restore index: {e}");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::Operation(err_msg))
+        }
+    }
+}
+
+// restore the index of `archive` as `index_name` in `index_storage_dir`, resolving a name
+// conflict as requested
+fn restore(
+    archive: upload::TempUpload,
+    index_storage_dir: &Path,
+    index_name: &str,
+    on_conflict: OnConflict,
+) -> Result<Json<UploadResponse>, ServerError> {
+    // the names of new indexes exclude the hidden names of the staging directories
+    if let Err(err_msg) = validate_new_index_name(index_name) {
+        return bad_request(err_msg);
+    }
+
+    // unpack and validate the archive in a staging directory
+    let staging_path = index_storage_dir.join(format!(".upload-{}", uuid::Uuid::new_v4()));
+    if let Err(e) = unpack(archive.path(), &staging_path, MAX_UNPACKED_INDEX_BYTES) {
+        let _ = std::fs::remove_dir_all(&staging_path);
+        return bad_request(e);
+    }
+    drop(archive);
+
+    let response = match move_into_place(index_storage_dir, &staging_path, index_name, on_conflict)
+    {
+        Ok(response) => response,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging_path);
+
+            error!("{}", &e);
+
+            return Err(e);
+        }
+    };
+
+    info!(
+        index_name = %response.index_name,
+        overwritten = response.overwritten,
+        renamed = response.renamed,
+        "Index restored"
+    );
+
+    Ok(Json(response))
+}
+
+// unpack the archive at `archive_path` into `staging_path`, entry by entry so that its files are
+// at most `max_bytes` in total, and check it holds a valid index
+fn unpack(archive_path: &Path, staging_path: &Path, max_bytes: u64) -> Result<(), String> {
+    let tarball = archive::open(archive_path)
+        .map_err(|e| format!("Failed to open the uploaded archive: {e}"))?;
+    std::fs::create_dir_all(staging_path)
+        .map_err(|e| format!("Failed to create staging directory: {e}"))?;
+
+    let unpack_failed = |e: std::io::Error| format!("Failed to unpack the uploaded archive: {e}");
+    let mut tarball = tar::Archive::new(tarball);
+    let mut unpacked_bytes = 0;
+    for entry in tarball.entries().map_err(unpack_failed)? {
+        let mut entry = entry.map_err(unpack_failed)?;
+        let entry_path = entry.path().map_err(unpack_failed)?.display().to_string();
+
+        // the archive of an index only holds its files and directories
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(format!(
+                "The uploaded archive holds '{entry_path}', which is neither a file nor a directory"
+            ));
+        }
+        unpacked_bytes += entry.size();
+        if unpacked_bytes > max_bytes {
+            return Err(format!(
+                "The uploaded archive exceeds {max_bytes} bytes once unpacked"
+            ));
+        }
+        // entries escaping the destination directory are not unpacked by `unpack_in`
+        if !entry.unpack_in(staging_path).map_err(unpack_failed)? {
+            return Err(format!(
+                "The uploaded archive holds '{entry_path}', outside of the index directory"
+            ));
+        }
+    }
+
+    Index::open_in_dir(staging_path)
+        .map_err(|e| format!("The uploaded archive does not hold a valid index: {e}"))?;
+
+    Ok(())
+}
+
+// move the staged index to its final location, resolving a name conflict as requested
+fn move_into_place(
+    index_storage_dir: &Path,
+    staging_path: &Path,
+    index_name: &str,
+    on_conflict: OnConflict,
+) -> Result<UploadResponse, ServerError> {
+    let mut response = UploadResponse {
+        index_name: index_name.to_string(),
+        overwritten: false,
+        renamed: false,
+    };
+
+    let mut index_path = index_storage_dir.join(index_name);
+    if index_path.exists() {
+        match on_conflict {
+            OnConflict::Reject => {
+                return Err(ServerError::Conflict(format!(
+                    "Index '{index_name}' already exists"
+                )))
+            }
+            OnConflict::Overwrite => {
+                // move the existing index aside first, so that it is only removed once replaced
+                let replaced_path =
+                    index_storage_dir.join(format!(".replaced-{}", uuid::Uuid::new_v4()));
+                std::fs::rename(&index_path, &replaced_path).map_err(|e| {
+                    ServerError::Operation(format!("Failed to replace index '{index_name}': {e}"))
+                })?;
+                if let Err(e) = std::fs::rename(staging_path, &index_path) {
+                    let _ = std::fs::rename(&replaced_path, &index_path);
+                    return Err(ServerError::Operation(format!(
+                        "Failed to replace index '{index_name}': {e}"
+                    )));
+                }
+                if let Err(e) = std::fs::remove_dir_all(&replaced_path) {
+                    warn!(path = %replaced_path.display(), error = %e, "Failed to remove replaced index");
+                }
+
+                // the cached archives and reader belong to the replaced index
+                archive::remove_cached(index_storage_dir, index_name);
+                reader_cache::invalidate(index_name);
+
+                response.overwritten = true;
+                return Ok(response);
+            }
+            OnConflict::Rename => {
+                let (name, path) = free_name(index_storage_dir, index_name);
+                response.index_name = name;
+                response.renamed = true;
+                index_path = path;
+            }
+        }
+    }
+
+    std::fs::rename(staging_path, &index_path).map_err(|e| {
+        ServerError::Operation(format!("Failed to move the uploaded index into place: {e}"))
+    })?;
+
+    Ok(response)
+}
+
+// first name of the form `{index_name}-{n}` not taken yet
+fn free_name(index_storage_dir: &Path, index_name: &str) -> (String, PathBuf) {
+    (1..)
+        .map(|n| format!("{index_name}-{n}"))
+        .map(|name| {
+            let path = index_storage_dir.join(&name);
+            (name, path)
+        })
+        .find(|(_, path)| !path.exists())
+        .unwrap()
+}
+
+// reject the request whose archive could not be stored, with `413 Payload Too Large` if it is too
+// large
+fn upload_failed<T>(e: upload::UploadError) -> Result<T, ServerError> {
+    error!("{}", &e);
+
+    Err(e.into())
+}
+
+// reject the request with `400 Bad Request`
+fn bad_request<T>(err_msg: String) -> Result<T, ServerError> {
+    error!("{}", &err_msg);
+
+    Err(ServerError::BadRequest(err_msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a storage directory holding the index `notes`, and an index staged to replace it
+    fn staged_upload() -> (tempfile::TempDir, PathBuf) {
+        let storage_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(storage_dir.path().join("notes")).unwrap();
+        std::fs::write(storage_dir.path().join("notes/meta.json"), "existing").unwrap();
+        let staging_path = storage_dir.path().join(".upload-test");
+        std::fs::create_dir(&staging_path).unwrap();
+        std::fs::write(staging_path.join("meta.json"), "uploaded").unwrap();
+        (storage_dir, staging_path)
+    }
+
+    // an uncompressed archive holding the files `files`, with their names as is
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in files {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *content).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    // the uploaded archive holding `content`
+    async fn uploaded(content: Vec<u8>) -> upload::TempUpload {
+        let body = axum::body::Body::from(content);
+        upload::stream_body_to_temp_file(body, u64::MAX)
+            .await
+            .unwrap()
+    }
+
+    // the archive of an empty index
+    fn index_archive() -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let index_path = dir.path().join("index");
+        std::fs::create_dir(&index_path).unwrap();
+        let mut schema = tantivy::schema::Schema::builder();
+        schema.add_text_field("body", tantivy::schema::TEXT);
+        let index = Index::create_in_dir(&index_path, schema.build()).unwrap();
+        index
+            .writer::<tantivy::TantivyDocument>(15_000_000)
+            .unwrap()
+            .commit()
+            .unwrap();
+
+        let archive_path = dir.path().join("index.tar.gz");
+        archive::build(&index_path, &archive_path, archive::ArchiveFormat::Gzip).unwrap();
+        std::fs::read(archive_path).unwrap()
+    }
+
+    fn meta(storage_dir: &Path, index_name: &str) -> String {
+        std::fs::read_to_string(storage_dir.join(index_name).join("meta.json")).unwrap()
+    }
+
+    #[test]
+    fn conflicting_upload_is_rejected() {
+        let (storage_dir, staging_path) = staged_upload();
+
+        let result = move_into_place(
+            storage_dir.path(),
+            &staging_path,
+            "notes",
+            OnConflict::Reject,
+        );
+
+        assert!(matches!(result, Err(ServerError::Conflict(_))));
+        assert_eq!(meta(storage_dir.path(), "notes"), "existing");
+        assert!(staging_path.exists());
+    }
+
+    #[test]
+    fn conflicting_upload_overwrites_the_index() {
+        let (storage_dir, staging_path) = staged_upload();
+
+        let response = move_into_place(
+            storage_dir.path(),
+            &staging_path,
+            "notes",
+            OnConflict::Overwrite,
+        )
+        .unwrap();
+
+        assert_eq!(response.index_name, "notes");
+        assert!(response.overwritten);
+        assert!(!response.renamed);
+        assert_eq!(meta(storage_dir.path(), "notes"), "uploaded");
+        assert!(!staging_path.exists());
+        // the replaced index is removed once the upload is in place
+        let entries = std::fs::read_dir(storage_dir.path()).unwrap().count();
+        assert_eq!(entries, 1);
+    }
+
+    #[test]
+    fn failed_overwrite_restores_the_existing_index() {
+        let (storage_dir, staging_path) = staged_upload();
+        std::fs::remove_dir_all(&staging_path).unwrap();
+
+        let result = move_into_place(
+            storage_dir.path(),
+            &staging_path,
+            "notes",
+            OnConflict::Overwrite,
+        );
+
+        assert!(matches!(result, Err(ServerError::Operation(_))));
+        assert_eq!(meta(storage_dir.path(), "notes"), "existing");
+        let entries = std::fs::read_dir(storage_dir.path()).unwrap().count();
+        assert_eq!(entries, 1);
+    }
+
+    #[test]
+    fn conflicting_upload_is_renamed_to_the_first_free_name() {
+        let (storage_dir, staging_path) = staged_upload();
+        std::fs::create_dir(storage_dir.path().join("notes-1")).unwrap();
+
+        let response = move_into_place(
+            storage_dir.path(),
+            &staging_path,
+            "notes",
+            OnConflict::Rename,
+        )
+        .unwrap();
+
+        assert_eq!(response.index_name, "notes-2");
+        assert!(response.renamed);
+        assert!(!response.overwritten);
+        assert_eq!(meta(storage_dir.path(), "notes"), "existing");
+        assert_eq!(meta(storage_dir.path(), "notes-2"), "uploaded");
+    }
+
+    #[tokio::test]
+    async fn upload_under_a_route_name_is_rejected() {
+        let request = Request::new(axum::body::Body::from("not read"));
+
+        let result = restore_named_index_handler(UrlPath("compare".to_string()), request).await;
+
+        assert!(matches!(result, Err(ServerError::BadRequest(_))));
+    }
+
+    #[test]
+    fn archive_larger_than_the_limit_once_unpacked_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("index.tar");
+        std::fs::write(
+            &archive_path,
+            tarball(&[("a", &[0; 600]), ("b", &[0; 600])]),
+        )
+        .unwrap();
+        let staging_path = dir.path().join("staging");
+
+        let result = unpack(&archive_path, &staging_path, 1000);
+
+        assert!(result.unwrap_err().contains("exceeds 1000 bytes"));
+        assert!(!staging_path.join("b").exists());
+    }
+
+    #[tokio::test]
+    async fn archive_escaping_the_index_directory_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_dir = dir.path().join("storage");
+        std::fs::create_dir(&storage_dir).unwrap();
+        let archive = uploaded(tarball(&[("../../evil", b"escaped")])).await;
+
+        let result = restore(archive, &storage_dir, "notes", OnConflict::Reject);
+
+        assert!(matches!(result, Err(ServerError::BadRequest(e)) if e.contains("outside")));
+        assert!(!dir.path().join("evil").exists());
+        assert_eq!(std::fs::read_dir(&storage_dir).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn invalid_archive_leaves_nothing_behind() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        for content in [
+            b"not an archive".to_vec(),
+            tarball(&[("notes.txt", b"no index")]),
+        ] {
+            let archive = uploaded(content).await;
+
+            let result = restore(archive, storage_dir.path(), "notes", OnConflict::Reject);
+
+            assert!(matches!(result, Err(ServerError::BadRequest(_))));
+            assert_eq!(std::fs::read_dir(storage_dir.path()).unwrap().count(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn restoring_an_existing_index_is_a_conflict() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(storage_dir.path().join("notes")).unwrap();
+        let archive = uploaded(index_archive()).await;
+
+        let error = restore(archive, storage_dir.path(), "notes", OnConflict::Reject).unwrap_err();
+
+        assert_eq!(error.status(), http::StatusCode::CONFLICT);
+        assert_eq!(std::fs::read_dir(storage_dir.path()).unwrap().count(), 1);
+
+        let archive = uploaded(index_archive()).await;
+        let Json(response) =
+            restore(archive, storage_dir.path(), "paris", OnConflict::Reject).unwrap();
+        assert_eq!(response.index_name, "paris");
+        assert!(Index::open_in_dir(storage_dir.path().join("paris")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn hidden_index_name_is_invalid() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let archive = uploaded(index_archive()).await;
+
+        let result = restore(archive, storage_dir.path(), ".upload-1", OnConflict::Reject);
+
+        assert!(matches!(result, Err(ServerError::BadRequest(_))));
+    }
+}
@@ -0,0 +1,714 @@
+//! Resumable uploads of large files, sent in chunks over several requests so that an upload
+//! interrupted by a network failure resumes where it stopped instead of restarting from scratch.
+//!
+//! An upload is created with `POST /v1/upload/init`, its chunks are appended in order with
+//! `PATCH /v1/upload/{upload_id}` at the byte offset given by the `Upload-Offset` header, and the
+//! assembled file is indexed with `POST /v1/upload/{upload_id}/finalize`. The current offset of an
+//! upload is returned by `GET /v1/upload/{upload_id}`, e.g. to resume after a failed chunk.
+//!
+//! The state of each upload is kept in the hidden `.uploads` directory of the index storage
+//! directory, as `{upload_id}.json` for its description and `{upload_id}.part` for the bytes
+//! received so far, so that uploads survive a restart of the server. The offset of an upload is
+//! the size of its `.part` file, which is synced to disk before a chunk is acknowledged. Uploads
+//! not updated for `--upload-expiry-secs` are removed.
+
+use crate::{
+    error::ServerError,
+    index_storage_dir,
+    keyword_search::{DocumentInput, DocumentResult, IndexRequest, IndexResponse, Preprocessing},
+    normalize_text, preprocess, process_json, process_rows, tabular, title_from_filename,
+    unique_key, validate_new_index_name, MAX_UPLOAD_BYTES, PREPROCESSING,
+};
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::status::StatusCode;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{error, info, warn};
+
+/// Name of the directory holding the state of the uploads, within the index storage directory and
+/// hidden so that it is not taken for an index
+const UPLOADS_DIR: &str = ".uploads";
+
+/// Maximum size of a chunk
+pub(crate) const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+/// Default number of seconds after its last update an unfinished upload is removed
+pub(crate) const DEFAULT_UPLOAD_EXPIRY_SECS: &str = "86400";
+
+// header giving the offset a chunk is appended at
+const UPLOAD_OFFSET_HEADER: &str = "upload-offset";
+
+// interval between two removals of the expired uploads
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// duration after its last update an unfinished upload is removed
+static UPLOAD_EXPIRY: OnceCell<Duration> = OnceCell::new();
+
+// uploads being appended to or finalized, which other requests must not touch meanwhile
+static BUSY_UPLOADS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Request body of the `POST /v1/upload/init` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct InitUploadRequest {
+    /// Name of the uploaded file. Its extension tells how it is indexed: `.csv` and `.tsv` files
+    /// are indexed as one document per row, other files as a single document.
+    pub(crate) filename: String,
+    /// Name of the index created from the file. If not provided, a name is generated.
+    #[serde(default)]
+    pub(crate) index: Option<String>,
+    /// Size of the file in bytes, if known. Chunks beyond it are rejected, and the upload can only
+    /// be finalized once complete.
+    #[serde(default)]
+    pub(crate) total_size: Option<u64>,
+    /// Delimiter of the values of a CSV or TSV file
+    #[serde(default)]
+    pub(crate) delimiter: Option<char>,
+    /// Whether the first row of a CSV or TSV file holds the names of the columns. Defaults to
+    /// `true`.
+    #[serde(default)]
+    pub(crate) has_header: Option<bool>,
+    /// Field identifying the documents, as for the `/v1/index/create` endpoint
+    #[serde(default)]
+    pub(crate) unique_key: Option<String>,
+    /// Skip the documents with the same content as an earlier one, as for the
+    /// `/v1/index/create` endpoint
+    #[serde(default)]
+    pub(crate) dedup: bool,
+}
+
+/// Description of an upload, persisted in `{upload_id}.json`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct UploadState {
+    upload_id: String,
+    filename: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delimiter: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_header: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unique_key: Option<String>,
+    #[serde(default)]
+    dedup: bool,
+}
+
+/// Response body of the upload endpoints, except the finalization
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UploadStatus {
+    /// Id of the upload
+    pub(crate) upload_id: String,
+    /// Name of the uploaded file
+    pub(crate) filename: String,
+    /// Number of bytes received, which is the offset of the next chunk
+    pub(crate) offset: u64,
+    /// Size of the file in bytes, if given when the upload was created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) total_size: Option<u64>,
+}
+
+// guard marking an upload as busy until dropped
+struct BusyGuard(String);
+
+impl BusyGuard {
+    // mark `upload_id` as busy, unless it already is
+    fn acquire(upload_id: &str) -> Option<Self> {
+        let mut busy_uploads = BUSY_UPLOADS.lock().unwrap();
+        busy_uploads
+            .insert(upload_id.to_string())
+            .then(|| Self(upload_id.to_string()))
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        BUSY_UPLOADS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Set the duration after its last update an unfinished upload is removed, and remove the expired
+/// uploads periodically.
+pub(crate) fn init(expiry: Duration) -> Result<(), String> {
+    UPLOAD_EXPIRY
+        .set(expiry)
+        .map_err(|_| "Failed to set the upload expiry".to_string())?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            remove_expired_uploads(expiry).await;
+        }
+    });
+
+    Ok(())
+}
+
+/// Duration after its last update an unfinished upload is removed
+pub(crate) fn expiry() -> Option<Duration> {
+    UPLOAD_EXPIRY.get().copied()
+}
+
+// maximum size of an uploaded file, set by `--max-upload-bytes` like for the other uploads
+fn max_upload_bytes() -> u64 {
+    MAX_UPLOAD_BYTES.get().copied().unwrap_or(usize::MAX) as u64
+}
+
+// fail with `413 Payload Too Large` the request making an upload larger than the maximum
+fn too_large<T>(size_bytes: u64) -> Result<T, ServerError> {
+    fail(ServerError::PayloadTooLarge(format!(
+        "The upload of {size_bytes} bytes exceeds the maximum upload size of {} bytes",
+        max_upload_bytes()
+    )))
+}
+
+// directory holding the state of the uploads
+fn uploads_dir() -> PathBuf {
+    index_storage_dir().join(UPLOADS_DIR)
+}
+
+fn state_path(upload_id: &str) -> PathBuf {
+    uploads_dir().join(format!("{upload_id}.json"))
+}
+
+fn part_path(upload_id: &str) -> PathBuf {
+    uploads_dir().join(format!("{upload_id}.part"))
+}
+
+// load the state of an upload, failing with `404` if it does not exist. Upload ids are UUIDs,
+// which also keeps the paths derived from them inside the uploads directory.
+async fn load_state(upload_id: &str) -> Result<UploadState, ServerError> {
+    let not_found = || ServerError::NotFound(format!("Upload '{upload_id}' not found"));
+    if uuid::Uuid::parse_str(upload_id).is_err() {
+        return Err(not_found());
+    }
+
+    let state = match tokio::fs::read(state_path(upload_id)).await {
+        Ok(state) => state,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+        Err(e) => {
+            return Err(ServerError::Operation(format!(
+                "Failed to read upload state: {e}"
+            )))
+        }
+    };
+    serde_json::from_slice(&state)
+        .map_err(|e| ServerError::Operation(format!("Failed to parse upload state: {e}")))
+}
+
+// number of bytes received for an upload
+async fn current_offset(upload_id: &str) -> std::io::Result<u64> {
+    match tokio::fs::metadata(part_path(upload_id)).await {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+// status of an upload, with its current offset
+async fn upload_status(state: UploadState) -> Result<UploadStatus, ServerError> {
+    let offset = current_offset(&state.upload_id)
+        .await
+        .map_err(|e| ServerError::Operation(format!("Failed to read upload: {e}")))?;
+
+    Ok(UploadStatus {
+        upload_id: state.upload_id,
+        filename: state.filename,
+        offset,
+        total_size: state.total_size,
+    })
+}
+
+// log an error and fail the request with it
+fn fail<T>(e: ServerError) -> Result<T, ServerError> {
+    error!("{}", &e);
+
+    Err(e)
+}
+
+// create an upload
+pub(crate) async fn init_upload_handler(
+    Json(request): Json<InitUploadRequest>,
+) -> Result<Json<UploadStatus>, ServerError> {
+    info!(filename = %request.filename, total_size = ?request.total_size, "Received upload init request");
+
+    if request.filename.is_empty() || request.filename.chars().any(char::is_control) {
+        return fail(ServerError::BadRequest(format!(
+            "Invalid file name '{}'",
+            request.filename
+        )));
+    }
+    let extension = request
+        .filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    if !matches!(
+        extension.as_deref(),
+        None | Some("txt" | "md" | "csv" | "tsv")
+    ) {
+        return fail(ServerError::BadRequest(
+            "Unsupported file type. Only .txt, .md, .csv and .tsv files are allowed".to_string(),
+        ));
+    }
+    if let Some(index) = &request.index {
+        if let Err(err_msg) = validate_new_index_name(index) {
+            return fail(ServerError::BadRequest(err_msg));
+        }
+    }
+    if let Some(unique_key) = &request.unique_key {
+        if let Err(err_msg) = unique_key::validate(unique_key) {
+            return fail(ServerError::BadRequest(err_msg));
+        }
+    }
+    if let Some(delimiter) = request.delimiter {
+        if matches!(delimiter, '"' | '\n' | '\r') {
+            return fail(ServerError::BadRequest(
+                "Invalid delimiter. It must not be a quote or a line break".to_string(),
+            ));
+        }
+    }
+    if let Some(total_size) = request.total_size.filter(|&size| size > max_upload_bytes()) {
+        return too_large(total_size);
+    }
+
+    let state = UploadState {
+        upload_id: uuid::Uuid::new_v4().to_string(),
+        filename: request.filename,
+        index: request.index,
+        total_size: request.total_size,
+        delimiter: request.delimiter,
+        has_header: request.has_header,
+        unique_key: request.unique_key,
+        dedup: request.dedup,
+    };
+    let created = async {
+        tokio::fs::create_dir_all(uploads_dir()).await?;
+        tokio::fs::File::create(part_path(&state.upload_id)).await?;
+        let json = serde_json::to_vec_pretty(&state).map_err(std::io::Error::other)?;
+        tokio::fs::write(state_path(&state.upload_id), json).await
+    }
+    .await;
+    if let Err(e) = created {
+        return fail(ServerError::Operation(format!(
+            "Failed to create upload: {e}"
+        )));
+    }
+
+    info!(upload_id = %state.upload_id, "Upload created");
+
+    Ok(Json(UploadStatus {
+        upload_id: state.upload_id,
+        filename: state.filename,
+        offset: 0,
+        total_size: state.total_size,
+    }))
+}
+
+// return the status of an upload
+pub(crate) async fn upload_status_handler(
+    Path(upload_id): Path<String>,
+) -> Result<Json<UploadStatus>, ServerError> {
+    let status = match load_state(&upload_id).await {
+        Ok(state) => upload_status(state).await,
+        Err(e) => Err(e),
+    };
+    match status {
+        Ok(status) => Ok(Json(status)),
+        Err(e) => fail(e),
+    }
+}
+
+// append a chunk to an upload at the offset given by the `Upload-Offset` header
+pub(crate) async fn append_chunk_handler(
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+    chunk: Bytes,
+) -> Result<Json<UploadStatus>, ServerError> {
+    info!(upload_id = %upload_id, size_bytes = chunk.len(), "Received upload chunk");
+
+    let state = match load_state(&upload_id).await {
+        Ok(state) => state,
+        Err(e) => return fail(e),
+    };
+
+    let Some(offset) = headers
+        .get(UPLOAD_OFFSET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+    else {
+        return fail(ServerError::BadRequest(
+            "Missing or invalid `Upload-Offset` header".to_string(),
+        ));
+    };
+
+    let Some(_guard) = BusyGuard::acquire(&upload_id) else {
+        return fail(ServerError::Conflict(format!(
+            "Upload '{upload_id}' is being updated by another request"
+        )));
+    };
+
+    let current = match current_offset(&upload_id).await {
+        Ok(current) => current,
+        Err(e) => {
+            return fail(ServerError::Operation(format!(
+                "Failed to read upload: {e}"
+            )))
+        }
+    };
+    // chunks are appended in order, so the client resumes from the offset of the upload
+    if offset != current {
+        return fail(ServerError::Conflict(format!(
+            "Offset {offset} does not match the offset {current} of the upload"
+        )));
+    }
+    if let Some(total_size) = state.total_size {
+        if current + chunk.len() as u64 > total_size {
+            return fail(ServerError::BadRequest(format!(
+                    "Chunk exceeds the total size of the upload: {} bytes at offset {current}, of {total_size} bytes",
+                    chunk.len()
+                )));
+        }
+    }
+    if current + chunk.len() as u64 > max_upload_bytes() {
+        return too_large(current + chunk.len() as u64);
+    }
+
+    // the chunk is synced before being acknowledged, so that a crash cannot lose it
+    let appended = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(part_path(&upload_id))
+            .await?;
+        file.write_all(&chunk).await?;
+        file.sync_data().await
+    }
+    .await;
+    if let Err(e) = appended {
+        return fail(ServerError::Operation(format!(
+            "Failed to append chunk: {e}"
+        )));
+    }
+
+    Ok(Json(UploadStatus {
+        upload_id: state.upload_id,
+        filename: state.filename,
+        offset: current + chunk.len() as u64,
+        total_size: state.total_size,
+    }))
+}
+
+// index the assembled file of an upload, and remove the upload once the index is created
+pub(crate) async fn finalize_upload_handler(
+    Path(upload_id): Path<String>,
+) -> Result<Response, ServerError> {
+    info!(upload_id = %upload_id, "Received upload finalize request");
+
+    let state = match load_state(&upload_id).await {
+        Ok(state) => state,
+        Err(e) => return fail(e),
+    };
+    let Some(_guard) = BusyGuard::acquire(&upload_id) else {
+        return fail(ServerError::Conflict(format!(
+            "Upload '{upload_id}' is being updated by another request"
+        )));
+    };
+
+    // the size is checked before the file is read, which reads no more than that size
+    let size_bytes = match current_offset(&upload_id).await {
+        Ok(size_bytes) => size_bytes,
+        Err(e) => {
+            return fail(ServerError::Operation(format!(
+                "Failed to read upload: {e}"
+            )))
+        }
+    };
+    if let Some(total_size) = state.total_size {
+        if size_bytes != total_size {
+            return fail(ServerError::BadRequest(format!(
+                "Upload is incomplete: {size_bytes} of {total_size} bytes received"
+            )));
+        }
+    }
+    if size_bytes > max_upload_bytes() {
+        return too_large(size_bytes);
+    }
+    let read = async {
+        let file = tokio::fs::File::open(part_path(&upload_id)).await?;
+        let mut content = Vec::with_capacity(size_bytes as usize);
+        file.take(size_bytes).read_to_end(&mut content).await?;
+        Ok::<_, std::io::Error>(content)
+    }
+    .await;
+    let content = match read {
+        Ok(content) => content,
+        Err(e) => {
+            return fail(ServerError::Operation(format!(
+                "Failed to read upload: {e}"
+            )))
+        }
+    };
+    let content = match String::from_utf8(content) {
+        Ok(content) => normalize_text(content),
+        Err(_) => return fail(ServerError::BadRequest("Invalid UTF-8 content".to_string())),
+    };
+
+    // build the documents like for a multipart upload of the file
+    let mut results = Vec::new();
+    let mut documents = Vec::new();
+    let mut preprocessing = None;
+    match tabular::TabularFormat::detect("application/octet-stream", Some(&state.filename)) {
+        Some(format) => {
+            let pipeline = match preprocess::Pipeline::new(
+                &PREPROCESSING.get().cloned().unwrap_or_default(),
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(err_msg) => return fail(ServerError::Operation(err_msg)),
+            };
+            let options = tabular::TabularOptions {
+                delimiter: state.delimiter,
+                has_header: state.has_header.unwrap_or(true),
+            };
+            process_rows(
+                &mut results,
+                &mut documents,
+                &pipeline,
+                Some(&state.filename),
+                &content,
+                format,
+                options,
+            );
+            // the indexed rows are reported by the indexing, and were already preprocessed
+            results.retain(|result: &DocumentResult| result.status == "failed");
+            preprocessing = Some(Preprocessing::default());
+        }
+        None => documents.push(DocumentInput {
+            content,
+            title: title_from_filename(&state.filename),
+            quality: None,
+            fields: BTreeMap::new(),
+            tags: Vec::new(),
+            created_at: None,
+        }),
+    }
+
+    if documents.is_empty() {
+        let err_msg = "No documents to index".to_string();
+
+        error!(upload_id = %upload_id, "{}", &err_msg);
+
+        results.push(DocumentResult {
+            filename: Some(state.filename),
+            status: "failed".to_string(),
+            error: Some(err_msg),
+        });
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(IndexResponse {
+                results,
+                index_name: None,
+                download_url: None,
+                provenance: None,
+            }),
+        )
+            .into_response());
+    }
+
+    let (status, Json(mut response)) = process_json(IndexRequest {
+        documents,
+        index: state.index,
+        callback_url: None,
+        commit_immediately: false,
+        title_in_body: false,
+        allow_empty: false,
+        search_defaults: None,
+        field_boosts: BTreeMap::new(),
+        preprocessing,
+        unique_key: state.unique_key,
+        dedup: state.dedup,
+        tokenizer: Default::default(),
+        remove_stopwords: false,
+        stopwords: Vec::new(),
+    })
+    .await;
+    results.append(&mut response.results);
+    response.results = results;
+
+    // the upload is kept if no index was created, so that finalizing can be retried
+    if response.index_name.is_some() {
+        remove_upload(&upload_id).await;
+        info!(upload_id = %upload_id, index_name = ?response.index_name, "Upload finalized");
+    }
+
+    Ok((status, Json(response)).into_response())
+}
+
+// remove the files of an upload
+async fn remove_upload(upload_id: &str) {
+    for path in [part_path(upload_id), state_path(upload_id)] {
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = %path.display(), error = %e, "Failed to remove upload file"),
+        }
+    }
+}
+
+// remove the uploads not updated for `expiry`, skipping the ones being updated
+async fn remove_expired_uploads(expiry: Duration) {
+    let Ok(mut entries) = tokio::fs::read_dir(uploads_dir()).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(upload_id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Some(_guard) = BusyGuard::acquire(upload_id) else {
+            continue;
+        };
+
+        // the part file is updated by every chunk, the state file only at creation
+        let last_update = match tokio::fs::metadata(part_path(upload_id)).await {
+            Ok(metadata) => metadata.modified(),
+            Err(_) => entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified()),
+        };
+        let expired = last_update
+            .ok()
+            .and_then(|last_update| SystemTime::now().duration_since(last_update).ok())
+            .is_some_and(|age| age >= expiry);
+        if expired {
+            info!(upload_id = %upload_id, "Removing expired upload");
+            remove_upload(upload_id).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{search, storage_dir, unique_index_name};
+
+    async fn init(request: serde_json::Value) -> UploadStatus {
+        storage_dir();
+        let request = serde_json::from_value(request).unwrap();
+        init_upload_handler(Json(request)).await.unwrap().0
+    }
+
+    async fn append(
+        upload_id: &str,
+        offset: u64,
+        chunk: &str,
+    ) -> Result<UploadStatus, ServerError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(UPLOAD_OFFSET_HEADER, offset.into());
+        append_chunk_handler(
+            Path(upload_id.to_string()),
+            headers,
+            Bytes::from(chunk.to_string()),
+        )
+        .await
+        .map(|Json(status)| status)
+    }
+
+    #[tokio::test]
+    async fn chunk_at_another_offset_is_a_conflict() {
+        let upload = init(serde_json::json!({ "filename": "notes.txt" })).await;
+        append(&upload.upload_id, 0, "first chunk").await.unwrap();
+
+        let result = append(&upload.upload_id, 0, "first chunk").await;
+        assert!(matches!(result, Err(ServerError::Conflict(_))));
+
+        let status = append(&upload.upload_id, 11, ", second chunk")
+            .await
+            .unwrap();
+        assert_eq!(status.offset, 25);
+    }
+
+    #[tokio::test]
+    async fn chunk_past_the_total_size_is_rejected() {
+        let upload = init(serde_json::json!({ "filename": "notes.txt", "total_size": 8 })).await;
+
+        let result = append(&upload.upload_id, 0, "too many bytes").await;
+        assert!(matches!(result, Err(ServerError::BadRequest(_))));
+
+        let Json(status) = upload_status_handler(Path(upload.upload_id)).await.unwrap();
+        assert_eq!(status.offset, 0);
+    }
+
+    #[tokio::test]
+    async fn busy_upload_rejects_other_requests() {
+        let upload = init(serde_json::json!({ "filename": "notes.txt" })).await;
+        let guard = BusyGuard::acquire(&upload.upload_id).unwrap();
+
+        let result = append(&upload.upload_id, 0, "chunk").await;
+        assert!(matches!(result, Err(ServerError::Conflict(_))));
+        let result = finalize_upload_handler(Path(upload.upload_id.clone())).await;
+        assert!(matches!(result, Err(ServerError::Conflict(_))));
+
+        drop(guard);
+        append(&upload.upload_id, 0, "chunk").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_upload_is_removed() {
+        let expired = init(serde_json::json!({ "filename": "notes.txt" })).await;
+        let recent = init(serde_json::json!({ "filename": "notes.txt" })).await;
+        let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 86400);
+        std::fs::File::options()
+            .append(true)
+            .open(part_path(&expired.upload_id))
+            .unwrap()
+            .set_modified(two_days_ago)
+            .unwrap();
+
+        remove_expired_uploads(Duration::from_secs(86400)).await;
+
+        assert!(!state_path(&expired.upload_id).exists());
+        assert!(!part_path(&expired.upload_id).exists());
+        assert!(state_path(&recent.upload_id).exists());
+    }
+
+    #[tokio::test]
+    async fn finalized_file_is_titled_without_its_extension() {
+        let index_name = unique_index_name("resumable");
+        let upload = init(serde_json::json!({
+            "filename": "paris-guide.txt",
+            "index": index_name,
+        }))
+        .await;
+        append(&upload.upload_id, 0, "The Louvre museum")
+            .await
+            .unwrap();
+
+        let response = finalize_upload_handler(Path(upload.upload_id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!state_path(&upload.upload_id).exists());
+
+        let found = search(serde_json::json!({ "query": "louvre", "index": index_name })).unwrap();
+        assert_eq!(found.hits[0].title, "paris-guide");
+    }
+}
@@ -0,0 +1,65 @@
+//! Executor of the searches, which collects the segments of large indexes in parallel on a thread
+//! pool shared by all the indexes, sized by `--search-executor-threads`.
+//!
+//! Collecting a segment on the pool costs a thread hand-off, which outweighs the gain on small
+//! indexes, so they are still collected on the calling thread.
+
+use once_cell::sync::OnceCell;
+use tantivy::{
+    collector::Collector,
+    query::{EnableScoring, Query},
+    Executor, Searcher,
+};
+
+// minimum number of documents of an index for its segments to be collected in parallel
+const MIN_DOCS_FOR_PARALLEL_SEARCH: u64 = 100_000;
+
+// thread pool collecting the segments in parallel, if more than one thread is configured
+static EXECUTOR: OnceCell<Executor> = OnceCell::new();
+
+// number of threads of the pool, if created
+static NUM_THREADS: OnceCell<usize> = OnceCell::new();
+
+/// Create the thread pool collecting the segments in parallel. A single thread disables it.
+pub(crate) fn init(num_threads: usize) -> Result<(), String> {
+    if num_threads <= 1 {
+        return Ok(());
+    }
+
+    let executor = Executor::multi_thread(num_threads, "search-executor-")
+        .map_err(|e| format!("Failed to create the search thread pool: {e}"))?;
+    EXECUTOR
+        .set(executor)
+        .map_err(|_| "The search thread pool is already created".to_string())?;
+    let _ = NUM_THREADS.set(num_threads);
+
+    Ok(())
+}
+
+/// Number of threads collecting the segments of large indexes
+pub(crate) fn num_threads() -> usize {
+    NUM_THREADS.get().copied().unwrap_or(1)
+}
+
+/// Search `query` with `collector`, collecting the segments in parallel if the index is large
+/// enough and has several segments.
+pub(crate) fn search<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    collector: &C,
+) -> tantivy::Result<C::Fruit> {
+    let executor = EXECUTOR.get().filter(|_| {
+        searcher.segment_readers().len() > 1 && searcher.num_docs() >= MIN_DOCS_FOR_PARALLEL_SEARCH
+    });
+
+    match executor {
+        Some(executor) => {
+            let enabled_scoring = match collector.requires_scoring() {
+                true => EnableScoring::enabled_from_searcher(searcher),
+                false => EnableScoring::disabled_from_searcher(searcher),
+            };
+            searcher.search_with_executor(query, collector, executor, enabled_scoring)
+        }
+        None => searcher.search(query, collector),
+    }
+}
@@ -0,0 +1,63 @@
+//! Graceful shutdown on `SIGINT` (Ctrl-C) or `SIGTERM`, e.g. on a container restart. The server
+//! stops accepting connections, and waits for the requests in flight and the background indexing
+//! jobs to finish, so that no commit is interrupted midway.
+
+use once_cell::sync::Lazy;
+use std::future::Future;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, Instrument};
+
+// background tasks the shutdown waits for, e.g. the indexing jobs with a callback
+static BACKGROUND_TASKS: Lazy<TaskTracker> = Lazy::new(TaskTracker::new);
+
+/// Spawn `task` in background, in the span of the current request, and have the shutdown wait for
+/// it.
+pub(crate) fn spawn_tracked<F>(task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    BACKGROUND_TASKS.spawn(task.in_current_span());
+}
+
+/// Wait for a shutdown signal.
+pub(crate) async fn signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!(error = %e, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!(target: "stdout", "Shutting down gracefully, waiting for the requests in flight");
+}
+
+/// Wait for the background tasks to finish, once the server stopped accepting requests.
+pub(crate) async fn drain() {
+    BACKGROUND_TASKS.close();
+    if !BACKGROUND_TASKS.is_empty() {
+        info!(target: "stdout", "Waiting for {} background tasks", BACKGROUND_TASKS.len());
+    }
+    BACKGROUND_TASKS.wait().await;
+
+    info!(target: "stdout", "Shutdown complete");
+}
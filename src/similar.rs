@@ -0,0 +1,325 @@
+//! "More like this" search, finding the documents similar to a reference document of an index. The
+//! most significant terms of the title and the body of the reference, weighted by their frequency
+//! in it and their rarity in the index, make up the query, and the reference itself is left out of
+//! the hits.
+
+use crate::{
+    aliases, clamp_top_k, default_top_k, elapsed_ms, error::ServerError, index_meta,
+    index_storage_dir, keyword_search::SearchHit, only_index, ranking, reader_cache, request_id,
+    tags, validate_index_name, DEFAULT_INDEX,
+};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tantivy::{
+    collector::TopDocs,
+    query::{BooleanQuery, MoreLikeThisQuery, Occur, Query, TermQuery},
+    schema::{IndexRecordOption, OwnedValue, Value},
+    DocAddress, Index, Score, Searcher, TantivyDocument, Term,
+};
+use tracing::{error, info};
+
+// number of terms of the reference document making up the query
+const MAX_QUERY_TERMS: usize = 25;
+
+// number of documents matching the words of a title checked for the exact title
+const TITLE_CANDIDATES: usize = 100;
+
+/// Request body of the `POST /v1/search/similar` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct SimilarRequest {
+    /// Name of the index, or alias, to search. Defaults to the default index of the server.
+    #[serde(default)]
+    pub(crate) index: Option<String>,
+    /// Reference document: the value of its unique key, its content hash, or its exact title
+    pub(crate) doc_title_or_id: String,
+    /// Number of hits to return. Defaults to the default of the index, or 5.
+    #[serde(default)]
+    pub(crate) top_k: Option<usize>,
+}
+
+/// Response body of the `POST /v1/search/similar` endpoint
+#[derive(Debug, Serialize)]
+pub(crate) struct SimilarResponse {
+    /// Title of the reference document
+    pub(crate) reference: String,
+    /// Documents similar to the reference, the most similar first
+    pub(crate) hits: Vec<SearchHit>,
+    /// Duration of the search in milliseconds
+    pub(crate) took_ms: u64,
+}
+
+// search the documents similar to a reference document
+pub(crate) async fn similar_handler(
+    Json(request): Json<SimilarRequest>,
+) -> Result<Json<SimilarResponse>, ServerError> {
+    let start = Instant::now();
+    info!(doc_title_or_id = %request.doc_title_or_id, top_k = ?request.top_k, "Received similar search request");
+
+    // resolve the index to search if the request does not name one
+    let requested_index = match request
+        .index
+        .clone()
+        .or_else(|| DEFAULT_INDEX.get().cloned())
+    {
+        Some(index) => index,
+        None => match only_index() {
+            Ok(index) => index,
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+    };
+
+    if let Err(err_msg) = validate_index_name(&requested_index) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&requested_index);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.exists() {
+        let err_msg = format!("Index '{requested_index}' does not exist");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+    let index_metadata = index_meta::load(&index);
+
+    let top_k = request
+        .top_k
+        .or(index_metadata
+            .search_defaults
+            .and_then(|defaults| defaults.top_k))
+        .unwrap_or_else(default_top_k);
+    if top_k == 0 {
+        let err_msg = "`top_k` must be at least 1".to_string();
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+    let top_k = clamp_top_k(top_k);
+
+    // looking the reference up and searching read the segments, which is blocking work
+    let searcher = reader.searcher();
+    let unique_key = index_metadata.unique_key;
+    let doc_title_or_id = request.doc_title_or_id.clone();
+    let searched = request_id::spawn_blocking(move || {
+        search_similar(
+            &index,
+            &searcher,
+            &index_name,
+            unique_key.as_deref(),
+            &doc_title_or_id,
+            top_k,
+        )
+    })
+    .await;
+    match searched {
+        Ok(Ok(Some((reference, hits)))) => {
+            info!(hits = hits.len(), "Similar search completed");
+
+            Ok(Json(SimilarResponse {
+                reference,
+                hits,
+                took_ms: elapsed_ms(start),
+            }))
+        }
+        Ok(Ok(None)) => {
+            let err_msg = format!(
+                "No document of index '{requested_index}' has the unique key, content hash or title '{}'",
+                request.doc_title_or_id
+            );
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::NotFound(err_msg))
+        }
+        Ok(Err(e)) => {
+            let err_msg = format!("Failed to execute search: {e}");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::Operation(err_msg))
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to execute search: {e}");
+
+            error!("{}", &err_msg);
+
+            Err(ServerError::Operation(err_msg))
+        }
+    }
+}
+
+// search the `top_k` documents most similar to the reference document `doc_title_or_id`, along
+// with the title of the reference. `None` if there is no such document.
+fn search_similar(
+    index: &Index,
+    searcher: &Searcher,
+    index_name: &str,
+    unique_key: Option<&str>,
+    doc_title_or_id: &str,
+    top_k: usize,
+) -> tantivy::Result<Option<(String, Vec<SearchHit>)>> {
+    let Some(reference) = find_reference(index, searcher, unique_key, doc_title_or_id)? else {
+        return Ok(None);
+    };
+    let reference_doc: TantivyDocument = searcher.doc(reference)?;
+    let schema = index.schema();
+    let title = schema.get_field("title")?;
+    let body = schema.get_field("body")?;
+
+    // only the text of the document makes up the query, not e.g. its content hash or tags
+    let text_fields = [title, body]
+        .into_iter()
+        .map(|field| {
+            let values = reference_doc
+                .get_all(field)
+                .filter_map(|value| value.as_str())
+                .map(|text| OwnedValue::Str(text.to_string()))
+                .collect();
+            (field, values)
+        })
+        .collect();
+    // terms found in the reference only cannot match other documents
+    let query = MoreLikeThisQuery::builder()
+        .with_min_doc_frequency(2)
+        .with_min_term_frequency(1)
+        .with_max_query_terms(MAX_QUERY_TERMS)
+        .with_boost_factor(1.0)
+        .with_document_fields(text_fields);
+
+    // one more hit is collected, in place of the reference, which matches its own terms
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(top_k + 1))?;
+    let hits = top_docs
+        .into_iter()
+        .filter(|(_, doc_address)| *doc_address != reference)
+        .take(top_k)
+        .map(|(score, doc_address)| search_hit(searcher, index_name, score, doc_address))
+        .collect::<tantivy::Result<_>>()?;
+
+    let reference_title = reference_doc
+        .get_first(title)
+        .and_then(|value| value.as_str())
+        .unwrap_or("Unknown")
+        .to_string();
+
+    Ok(Some((reference_title, hits)))
+}
+
+// address of the document whose unique key, content hash, or exact title is `doc_title_or_id`, in
+// this order of precedence
+fn find_reference(
+    index: &Index,
+    searcher: &Searcher,
+    unique_key: Option<&str>,
+    doc_title_or_id: &str,
+) -> tantivy::Result<Option<DocAddress>> {
+    let schema = index.schema();
+
+    // the unique key and the content hash are keyword fields, matched as a whole
+    for field_name in unique_key.into_iter().chain(["content_hash"]) {
+        let Ok(field) = schema.get_field(field_name) else {
+            continue;
+        };
+        let query = TermQuery::new(
+            Term::from_field_text(field, doc_title_or_id),
+            IndexRecordOption::Basic,
+        );
+        if let Some((_, doc_address)) = searcher.search(&query, &TopDocs::with_limit(1))?.first() {
+            return Ok(Some(*doc_address));
+        }
+    }
+
+    // the title is tokenized, so the documents having all its words are checked for the exact
+    // title
+    let title = schema.get_field("title")?;
+    let mut tokenizer = index.tokenizer_for_field(title)?;
+    let mut token_stream = tokenizer.token_stream(doc_title_or_id);
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    while token_stream.advance() {
+        let term = Term::from_field_text(title, &token_stream.token().text);
+        clauses.push((
+            Occur::Must,
+            Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+        ));
+    }
+    if clauses.is_empty() {
+        return Ok(None);
+    }
+
+    let candidates = searcher.search(
+        &BooleanQuery::new(clauses),
+        &TopDocs::with_limit(TITLE_CANDIDATES),
+    )?;
+    for (_, doc_address) in candidates {
+        let doc: TantivyDocument = searcher.doc(doc_address)?;
+        let has_title = doc
+            .get_first(title)
+            .and_then(|value| value.as_str())
+            .is_some_and(|value| value == doc_title_or_id);
+        if has_title {
+            return Ok(Some(doc_address));
+        }
+    }
+
+    Ok(None)
+}
+
+// hit of a document, without snippet
+fn search_hit(
+    searcher: &Searcher,
+    index_name: &str,
+    score: Score,
+    doc_address: DocAddress,
+) -> tantivy::Result<SearchHit> {
+    let schema = searcher.schema();
+    let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
+    let text = |field_name: &str| {
+        schema
+            .get_field(field_name)
+            .ok()
+            .and_then(|field| retrieved_doc.get_first(field))
+            .and_then(|value| value.as_str())
+            .unwrap_or("Unknown")
+            .to_string()
+    };
+
+    Ok(SearchHit {
+        title: text("title"),
+        content: text("body"),
+        score: score as f64,
+        index: index_name.to_string(),
+        snippet: None,
+        snippet_offsets: None,
+        duplicate_count: None,
+        quality: schema
+            .get_field(ranking::QUALITY_FIELD)
+            .ok()
+            .and_then(|quality| retrieved_doc.get_first(quality)?.as_f64()),
+        tags: tags::values(schema, &retrieved_doc),
+        created_at: schema
+            .get_field(ranking::CREATED_AT_FIELD)
+            .ok()
+            .and_then(|created_at| retrieved_doc.get_first(created_at)?.as_datetime())
+            .and_then(ranking::format_date),
+        explanation: None,
+    })
+}
@@ -0,0 +1,229 @@
+//! Helpers turning the fragments of the snippet generator into passages of the original content.
+
+use std::{collections::HashSet, ops::Range};
+use tantivy::{snippet::Snippet, tokenizer::TextAnalyzer};
+
+/// Byte range of the snippet fragment within `text`, which must be the text the snippet was
+/// generated from, with `tokenizer`, the tokenizer of the field.
+///
+/// The snippet generator does not expose the offset of its fragment, but builds it from the tokens
+/// of `text`: the fragment starts where a token starts, ends where a token ends, and its
+/// highlighted ranges are tokens too. Among the occurrences of the fragment text, the first one
+/// aligned on the tokens this way is used, as the generator picks the first of equally scored
+/// fragments. Occurrences inside longer words or across filtered tokens are thus skipped.
+pub(crate) fn locate_fragment(
+    text: &str,
+    snippet: &Snippet,
+    tokenizer: &mut TextAnalyzer,
+) -> Option<Range<usize>> {
+    let fragment = snippet.fragment();
+    if fragment.is_empty() {
+        return None;
+    }
+
+    // offsets of the tokens, in the order of the text
+    let mut token_starts = Vec::new();
+    let mut token_ends = HashSet::new();
+    let mut stream = tokenizer.token_stream(text);
+    while let Some(token) = stream.next() {
+        token_starts.push(token.offset_from);
+        token_ends.insert(token.offset_to);
+    }
+    let starts: HashSet<_> = token_starts.iter().copied().collect();
+
+    let is_aligned = |start: usize| {
+        text[start..].starts_with(fragment)
+            && token_ends.contains(&(start + fragment.len()))
+            && snippet.highlighted().iter().all(|item| {
+                starts.contains(&(start + item.start)) && token_ends.contains(&(start + item.end))
+            })
+    };
+    token_starts
+        .into_iter()
+        .find(|&start| is_aligned(start))
+        .map(|start| start..start + fragment.len())
+}
+
+/// Expand `range` to the boundaries of the sentences it overlaps, plus `context` sentences on each
+/// side.
+pub(crate) fn expand_to_sentences(text: &str, range: Range<usize>, context: usize) -> Range<usize> {
+    let starts = sentence_starts(text);
+
+    // sentences containing the first and the last byte of the range
+    let first = starts
+        .iter()
+        .rposition(|&start| start <= range.start)
+        .unwrap_or(0);
+    let last = starts
+        .iter()
+        .rposition(|&start| start < range.end.max(range.start + 1))
+        .unwrap_or(first);
+
+    let start = starts[first.saturating_sub(context)];
+    let end = starts
+        .get(last + 1 + context)
+        .copied()
+        .unwrap_or(text.len());
+
+    // drop the whitespace separating the passage from the next sentence
+    let end = start + text[start..end].trim_end().len();
+
+    start..end
+}
+
+/// Convert the byte range `range` of `text` into `[start, end)` character offsets.
+pub(crate) fn char_offsets(text: &str, range: Range<usize>) -> [usize; 2] {
+    let start = text[..range.start].chars().count();
+    let end = start + text[range].chars().count();
+    [start, end]
+}
+
+/// Render `text[passage]` as HTML, wrapping the `highlighted` byte ranges, expressed relative to
+/// `text`, with the given tags.
+pub(crate) fn highlight_passage(
+    text: &str,
+    passage: Range<usize>,
+    highlighted: &[Range<usize>],
+    pre_tag: &str,
+    post_tag: &str,
+) -> String {
+    let mut html = String::new();
+    let mut cursor = passage.start;
+    for item in highlighted {
+        if item.start < cursor || item.end > passage.end {
+            continue;
+        }
+        html.push_str(&escape_html(&text[cursor..item.start]));
+        html.push_str(pre_tag);
+        html.push_str(&escape_html(&text[item.clone()]));
+        html.push_str(post_tag);
+        cursor = item.end;
+    }
+    html.push_str(&escape_html(&text[cursor..passage.end]));
+
+    html
+}
+
+// Byte offsets at which sentences start. A sentence ends after `.`, `!` or `?` followed by
+// whitespace, or at a line break.
+fn sentence_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let is_boundary = match c {
+            '.' | '!' | '?' => chars.peek().is_some_and(|(_, next)| next.is_whitespace()),
+            '\n' => true,
+            _ => false,
+        };
+        if !is_boundary {
+            continue;
+        }
+
+        // the next sentence starts after the whitespace following the boundary
+        let mut next_start = offset + c.len_utf8();
+        while let Some(&(next_offset, next)) = chars.peek() {
+            if !next.is_whitespace() {
+                break;
+            }
+            next_start = next_offset + next.len_utf8();
+            chars.next();
+        }
+        if next_start < text.len() {
+            starts.push(next_start);
+        }
+    }
+
+    starts
+}
+
+// Escape the characters of `text` that are significant in HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tantivy::{
+        query::QueryParser,
+        schema::{Schema, TEXT},
+        snippet::SnippetGenerator,
+        Index,
+    };
+
+    // the snippet of `text` for `query`, and the tokenizer it was generated with
+    fn snippet(text: &str, query: &str, max_num_chars: usize) -> (Snippet, TextAnalyzer) {
+        let mut schema = Schema::builder();
+        let body = schema.add_text_field("body", TEXT);
+        let index = Index::create_in_ram(schema.build());
+        // the generator only highlights the terms found in the index
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer.add_document(tantivy::doc!(body => text)).unwrap();
+        writer.commit().unwrap();
+        let query = QueryParser::for_index(&index, vec![body])
+            .parse_query(query)
+            .unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let mut generator = SnippetGenerator::create(&searcher, &*query, body).unwrap();
+        generator.set_max_num_chars(max_num_chars);
+
+        (
+            generator.snippet(text),
+            index.tokenizer_for_field(body).unwrap(),
+        )
+    }
+
+    #[test]
+    fn fragment_inside_a_longer_word_is_skipped() {
+        let text = "Bobcats and cats";
+        let (snippet, mut tokenizer) = snippet(text, "cats", 4);
+        assert_eq!(snippet.fragment(), "cats");
+
+        assert_eq!(
+            locate_fragment(text, &snippet, &mut tokenizer),
+            Some(12..16)
+        );
+    }
+
+    #[test]
+    fn sentences_around_the_located_fragment() {
+        let text = "Bobcats hunt at night. Some cats sleep all day. Dogs bark.";
+        let (snippet, mut tokenizer) = snippet(text, "cats", 4);
+        let fragment = locate_fragment(text, &snippet, &mut tokenizer).unwrap();
+
+        let passage = expand_to_sentences(text, fragment, 0);
+        assert_eq!(&text[passage.clone()], "Some cats sleep all day.");
+        assert_eq!(char_offsets(text, passage), [23, 47]);
+    }
+
+    #[test]
+    fn char_offsets_slice_the_highlighted_term_out_of_multibyte_text() {
+        let text = "Le café est fermé. Les élèves mangent des crêpes à midi. Fin.";
+        let (snippet, mut tokenizer) = snippet(text, "crêpes", 10);
+        let fragment = locate_fragment(text, &snippet, &mut tokenizer).unwrap();
+        let chars: Vec<char> = text.chars().collect();
+        let slice = |[start, end]: [usize; 2]| chars[start..end].iter().collect::<String>();
+
+        let highlighted = &snippet.highlighted()[0];
+        let term = fragment.start + highlighted.start..fragment.start + highlighted.end;
+        assert_eq!(char_offsets(text, term.clone()), [42, 48]);
+        assert_eq!(slice(char_offsets(text, term)), "crêpes");
+
+        let passage = expand_to_sentences(text, fragment, 0);
+        assert_eq!(
+            slice(char_offsets(text, passage)),
+            "Les élèves mangent des crêpes à midi."
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Completion of a prefix with the indexed terms of the titles and bodies of an index, e.g. for
+//! search-as-you-type UIs. The terms are read from the term dictionaries of the segments, and
+//! ranked by the number of documents containing them.
+
+use crate::{
+    aliases, error::ServerError, index_storage_dir, only_index, reader_cache, validate_index_name,
+    DEFAULT_INDEX,
+};
+use axum::{
+    extract::Query,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::{error, info};
+
+// default maximum number of suggestions returned
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+
+// maximum value of the `limit` parameter
+const MAX_SUGGEST_LIMIT: usize = 100;
+
+// minimum number of characters of the prefix, as shorter prefixes match too many terms
+const MIN_PREFIX_CHARS: usize = 2;
+
+// fields whose terms are suggested
+const SUGGEST_FIELDS: [&str; 2] = ["title", "body"];
+
+/// Query parameters of the `GET /v1/suggest` endpoint
+#[derive(Debug, Deserialize)]
+pub(crate) struct SuggestParams {
+    /// Name of the index, or alias. Defaults to the default index of the server.
+    pub(crate) index: Option<String>,
+    /// Beginning of the term to complete
+    pub(crate) prefix: String,
+    /// Maximum number of suggestions returned
+    pub(crate) limit: Option<usize>,
+}
+
+// complete a prefix with the most frequent indexed terms starting with it
+pub(crate) async fn suggest_handler(
+    Query(params): Query<SuggestParams>,
+) -> Result<Response, ServerError> {
+    info!(index = ?params.index, prefix = %params.prefix, limit = ?params.limit, "Received suggest request");
+
+    let limit = params.limit.unwrap_or(DEFAULT_SUGGEST_LIMIT);
+    if limit == 0 || limit > MAX_SUGGEST_LIMIT {
+        let err_msg =
+            format!("Invalid limit {limit}. It must be between 1 and {MAX_SUGGEST_LIMIT}");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // the terms are indexed in lowercase
+    let prefix = params.prefix.trim().to_lowercase();
+    if prefix.chars().count() < MIN_PREFIX_CHARS {
+        let err_msg = format!("The prefix must have at least {MIN_PREFIX_CHARS} characters");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    // resolve the index if the request does not name one
+    let requested_index = match params.index.or_else(|| DEFAULT_INDEX.get().cloned()) {
+        Some(index) => index,
+        None => match only_index() {
+            Ok(index) => index,
+            Err(err_msg) => {
+                error!("{}", &err_msg);
+
+                return Err(ServerError::BadRequest(err_msg));
+            }
+        },
+    };
+
+    if let Err(err_msg) = validate_index_name(&requested_index) {
+        error!("{}", &err_msg);
+
+        return Err(ServerError::BadRequest(err_msg));
+    }
+
+    let index_name = aliases::resolve(&requested_index);
+    let index_path = index_storage_dir().join(&index_name);
+    if !index_path.is_dir() {
+        let err_msg = format!("Index '{requested_index}' not found");
+
+        error!("{}", &err_msg);
+
+        return Err(ServerError::IndexNotFound(err_msg));
+    }
+
+    let (index, reader) = match reader_cache::get_or_open(&index_name, &index_path) {
+        Ok(entry) => entry,
+        Err(e) => {
+            let err_msg = format!("Failed to open index: {e}");
+
+            error!("{}", &err_msg);
+
+            return Err(ServerError::Operation(err_msg));
+        }
+    };
+
+    // count the documents containing each term starting with the prefix, per field, as the terms
+    // of the dictionaries are sorted
+    let schema = index.schema();
+    let searcher = reader.searcher();
+    let mut counts: HashMap<Vec<u8>, u64> = HashMap::new();
+    for field in SUGGEST_FIELDS
+        .into_iter()
+        .filter_map(|field_name| schema.get_field(field_name).ok())
+    {
+        let mut field_counts: HashMap<Vec<u8>, u64> = HashMap::new();
+        for segment_reader in searcher.segment_readers() {
+            let read = segment_reader
+                .inverted_index(field)
+                .and_then(|inverted_index| {
+                    let mut stream = inverted_index
+                        .terms()
+                        .range()
+                        .ge(prefix.as_bytes())
+                        .into_stream()?;
+                    while stream.advance() && stream.key().starts_with(prefix.as_bytes()) {
+                        *field_counts.entry(stream.key().to_vec()).or_default() +=
+                            stream.value().doc_freq as u64;
+                    }
+                    Ok(())
+                });
+            if let Err(e) = read {
+                let err_msg = format!("Failed to read the terms of index '{index_name}': {e}");
+
+                error!("{}", &err_msg);
+
+                return Err(ServerError::Operation(err_msg));
+            }
+        }
+
+        // a document may contain a term in both fields, so the counts of the fields are not added,
+        // and the larger one approximates the number of documents containing the term
+        for (term, count) in field_counts {
+            let total = counts.entry(term).or_default();
+            *total = (*total).max(count);
+        }
+    }
+
+    // the most frequent terms first, and in lexicographic order for the same frequency
+    let mut counts: Vec<(Vec<u8>, u64)> = counts.into_iter().collect();
+    counts.sort_by(|(term_a, count_a), (term_b, count_b)| {
+        count_b.cmp(count_a).then_with(|| term_a.cmp(term_b))
+    });
+    let suggestions: Vec<String> = counts
+        .into_iter()
+        .take(limit)
+        .map(|(term, _)| String::from_utf8_lossy(&term).into_owned())
+        .collect();
+
+    info!(index_name = %index_name, suggestions = suggestions.len(), "Suggestions computed");
+
+    Ok(Json(suggestions).into_response())
+}
@@ -0,0 +1,349 @@
+//! Indexing of CSV and TSV files as structured documents: each data row becomes a document whose
+//! values are stored under their column names in the `fields` JSON field, so that they can be
+//! searched with queries like `fields.city:paris`.
+
+use std::collections::BTreeMap;
+use tantivy::{
+    schema::{Field, OwnedValue},
+    TantivyDocument,
+};
+
+/// Name of the JSON field holding the named values of structured documents
+pub(crate) const FIELDS_FIELD: &str = "fields";
+
+/// Add the named values of a structured document to the `fields` JSON field of `doc`, if any.
+pub(crate) fn add_fields(
+    doc: &mut TantivyDocument,
+    field: Field,
+    fields: &BTreeMap<String, String>,
+) {
+    if fields.is_empty() {
+        return;
+    }
+
+    let object = fields
+        .iter()
+        .map(|(name, value)| (name.clone(), OwnedValue::Str(value.clone())))
+        .collect();
+    doc.add_object(field, object);
+}
+
+/// How a CSV or TSV file is parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TabularOptions {
+    /// Delimiter of the values. Defaults to `,` for CSV files and to a tab for TSV files.
+    pub(crate) delimiter: Option<char>,
+    /// Whether the first row holds the names of the columns. Otherwise, the columns are named
+    /// `column_1`, `column_2`, etc.
+    pub(crate) has_header: bool,
+}
+
+impl Default for TabularOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: None,
+            has_header: true,
+        }
+    }
+}
+
+/// Format of a tabular file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TabularFormat {
+    Csv,
+    Tsv,
+}
+
+impl TabularFormat {
+    /// Format of an uploaded file, from its content type or, for generic content types, from the
+    /// extension of its name. `None` if it is not a tabular file.
+    pub(crate) fn detect(content_type: &str, filename: Option<&str>) -> Option<Self> {
+        match content_type {
+            "text/csv" => Some(Self::Csv),
+            "text/tab-separated-values" => Some(Self::Tsv),
+            "application/octet-stream" | "text/plain" => {
+                let extension = filename?.rsplit_once('.')?.1.to_ascii_lowercase();
+                match extension.as_str() {
+                    "csv" => Some(Self::Csv),
+                    "tsv" => Some(Self::Tsv),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn default_delimiter(self) -> char {
+        match self {
+            Self::Csv => ',',
+            Self::Tsv => '\t',
+        }
+    }
+}
+
+/// A data row of a tabular file
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Row {
+    /// Number of the line the row starts at, from 1
+    pub(crate) line: usize,
+    /// Values of the row by column name, or the reason the row cannot be indexed
+    pub(crate) fields: Result<BTreeMap<String, String>, String>,
+}
+
+impl Row {
+    /// Text of the row, one `name: value` line per non-empty value, indexed as its body
+    pub(crate) fn body(fields: &BTreeMap<String, String>) -> String {
+        fields
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| format!("{name}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Parse the data rows of `text`, named after the header row if `options.has_header`. A row
+/// whose number of values differs from the number of columns, or which is malformed, is
+/// returned with an error, and the following rows are parsed anyway.
+pub(crate) fn parse(
+    text: &str,
+    format: TabularFormat,
+    options: TabularOptions,
+) -> Result<Vec<Row>, String> {
+    let delimiter = options
+        .delimiter
+        .unwrap_or_else(|| format.default_delimiter());
+    let mut records = parse_records(text, delimiter).into_iter();
+
+    let columns: Vec<String> = match options.has_header {
+        true => match records.next() {
+            Some((_, Ok(names))) => names
+                .into_iter()
+                .map(|name| name.trim().to_string())
+                .collect(),
+            Some((line, Err(e))) => return Err(format!("Invalid header row at line {line}: {e}")),
+            None => return Ok(Vec::new()),
+        },
+        false => Vec::new(),
+    };
+    if columns.iter().any(|name| name.is_empty()) {
+        return Err("Invalid header row: column names must not be empty".to_string());
+    }
+    for (i, name) in columns.iter().enumerate() {
+        if columns[..i].contains(name) {
+            return Err(format!("Invalid header row: duplicate column '{name}'"));
+        }
+    }
+
+    Ok(records
+        .map(|(line, values)| {
+            let fields = values.and_then(|values| {
+                let num_columns = match options.has_header {
+                    true => columns.len(),
+                    false => values.len(),
+                };
+                if values.len() != num_columns {
+                    return Err(format!(
+                        "Row has {} values, expected {num_columns}",
+                        values.len()
+                    ));
+                }
+
+                Ok(values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, value)| {
+                        let name = match options.has_header {
+                            true => columns[i].clone(),
+                            false => format!("column_{}", i + 1),
+                        };
+                        (name, value)
+                    })
+                    .collect())
+            });
+            Row { line, fields }
+        })
+        .collect())
+}
+
+// split `text` into records of values, with the line each record starts at. Values may be quoted
+// with `"`, in which case they may contain delimiters, line breaks, and `""` for a quote. Blank
+// lines are skipped.
+fn parse_records(text: &str, delimiter: char) -> Vec<(usize, Result<Vec<String>, String>)> {
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start_line = line;
+        let mut values = Vec::new();
+        let mut value = String::new();
+        let mut error = None;
+        // whether the current value is quoted, and its closing quote was read
+        let mut quoted = false;
+        let mut closed = false;
+        let mut at_value_start = true;
+
+        loop {
+            let Some(c) = chars.next() else {
+                if quoted && !closed {
+                    error.get_or_insert_with(|| "Unterminated quoted value".to_string());
+                }
+                break;
+            };
+
+            if quoted && !closed {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        value.push('"');
+                    }
+                    '"' => closed = true,
+                    '\n' => {
+                        line += 1;
+                        value.push(c);
+                    }
+                    _ => value.push(c),
+                }
+                continue;
+            }
+
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => continue,
+                '\n' | '\r' => {
+                    line += 1;
+                    break;
+                }
+                c if c == delimiter => {
+                    values.push(std::mem::take(&mut value));
+                    quoted = false;
+                    closed = false;
+                    at_value_start = true;
+                    continue;
+                }
+                '"' if at_value_start => quoted = true,
+                _ if closed => {
+                    error.get_or_insert_with(|| {
+                        format!("Unexpected character '{c}' after a quoted value")
+                    });
+                }
+                _ => value.push(c),
+            }
+            at_value_start = false;
+        }
+
+        // skip blank lines
+        if values.is_empty() && value.is_empty() && !quoted {
+            continue;
+        }
+        values.push(value);
+
+        records.push((
+            start_line,
+            match error {
+                Some(e) => Err(e),
+                None => Ok(values),
+            },
+        ));
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn rows_are_named_after_the_header() {
+        let text = "city,country\nParis,France\n\n\"Washington, D.C.\",\"The \"\"US\"\"\"\n";
+
+        let rows = parse(text, TabularFormat::Csv, TabularOptions::default()).unwrap();
+
+        assert_eq!(
+            rows,
+            [
+                Row {
+                    line: 2,
+                    fields: Ok(fields(&[("city", "Paris"), ("country", "France")])),
+                },
+                Row {
+                    line: 4,
+                    fields: Ok(fields(&[
+                        ("city", "Washington, D.C."),
+                        ("country", "The \"US\"")
+                    ])),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tsv_rows_without_header_are_numbered() {
+        let options = TabularOptions {
+            delimiter: None,
+            has_header: false,
+        };
+
+        let rows = parse("Paris\tFrance\r\n", TabularFormat::Tsv, options).unwrap();
+
+        assert_eq!(
+            rows[0].fields,
+            Ok(fields(&[("column_1", "Paris"), ("column_2", "France")]))
+        );
+    }
+
+    #[test]
+    fn custom_delimiter_splits_the_values() {
+        let options = TabularOptions {
+            delimiter: Some(';'),
+            has_header: true,
+        };
+
+        let rows = parse("a;b\n1,5;2\n", TabularFormat::Csv, options).unwrap();
+
+        assert_eq!(rows[0].fields, Ok(fields(&[("a", "1,5"), ("b", "2")])));
+    }
+
+    #[test]
+    fn parse_errors_are_reported_per_row() {
+        let text = "a,b\n1,2,3\n\"open,4\n";
+        let rows = parse(text, TabularFormat::Csv, TabularOptions::default()).unwrap();
+
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(
+            rows[0].fields,
+            Err("Row has 3 values, expected 2".to_string())
+        );
+        assert_eq!(rows[1].line, 3);
+        assert_eq!(rows[1].fields, Err("Unterminated quoted value".to_string()));
+    }
+
+    #[test]
+    fn invalid_header_is_rejected() {
+        let parse = |text| parse(text, TabularFormat::Csv, TabularOptions::default());
+
+        assert!(parse("a,a\n1,2\n").is_err());
+        assert!(parse("a,\n1,2\n").is_err());
+    }
+
+    #[test]
+    fn format_is_detected_from_the_content_type_or_extension() {
+        assert_eq!(
+            TabularFormat::detect("text/csv", None),
+            Some(TabularFormat::Csv)
+        );
+        assert_eq!(
+            TabularFormat::detect("application/octet-stream", Some("data.TSV")),
+            Some(TabularFormat::Tsv)
+        );
+        assert_eq!(TabularFormat::detect("text/plain", Some("notes.txt")), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! Tags of the documents, e.g. a category or a source, given in the `tags` of the documents of a
+//! JSON indexing request. They are indexed as keywords, without tokenization, so that searches can
+//! be filtered to the documents having exact tags with `"filter": { "tags": [...] }`.
+
+use crate::keyword_search::SearchFilter;
+use tantivy::{
+    query::{BooleanQuery, ConstScoreQuery, Occur, Query, TermQuery},
+    schema::{Field, IndexRecordOption, Schema, SchemaBuilder, Value, FAST, STORED, STRING},
+    TantivyDocument, Term,
+};
+
+/// Name of the keyword field holding the tags of the documents
+pub(crate) const TAGS_FIELD: &str = "tags";
+
+/// Maximum number of tags of a filter
+pub(crate) const MAX_FILTER_TAGS: usize = 32;
+
+/// Add the keyword field of the tags to the schema.
+pub(crate) fn add_field(schema_builder: &mut SchemaBuilder) -> Field {
+    schema_builder.add_text_field(TAGS_FIELD, STRING | STORED | FAST)
+}
+
+/// Add `tags` to `doc`, one value of the tags field per tag.
+pub(crate) fn add_tags(doc: &mut TantivyDocument, field: Field, tags: &[String]) {
+    for tag in tags {
+        doc.add_text(field, tag);
+    }
+}
+
+/// Tags the hits of a search must have
+#[derive(Debug, Clone)]
+pub(crate) struct TagFilter {
+    field: Field,
+    tags: Vec<String>,
+}
+
+impl TagFilter {
+    /// Tag filter of the `filter` of a search request against an index of `schema`, if it has
+    /// tags. Fails if the filter has too many tags, or if the index has no tags field, e.g. as it
+    /// was created by an earlier version of the server.
+    pub(crate) fn new(
+        schema: &Schema,
+        filter: Option<&SearchFilter>,
+    ) -> Result<Option<Self>, String> {
+        let Some(filter) = filter.filter(|filter| !filter.tags.is_empty()) else {
+            return Ok(None);
+        };
+        if filter.tags.len() > MAX_FILTER_TAGS {
+            return Err(format!(
+                "Too many tags in filter. At most {MAX_FILTER_TAGS} tags are allowed"
+            ));
+        }
+        let field = schema.get_field(TAGS_FIELD).map_err(|_| {
+            format!("The index has no `{TAGS_FIELD}` field and cannot be filtered by tags")
+        })?;
+
+        Ok(Some(Self {
+            field,
+            tags: filter.tags.clone(),
+        }))
+    }
+
+    /// Restrict `query` to the documents having all the tags of the filter.
+    pub(crate) fn apply(&self, query: Box<dyn Query>) -> Box<dyn Query> {
+        // the tags only filter the hits, and do not contribute to their scores
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, query)];
+        for tag in &self.tags {
+            let term_query = TermQuery::new(
+                Term::from_field_text(self.field, tag),
+                IndexRecordOption::Basic,
+            );
+            clauses.push((
+                Occur::Must,
+                Box::new(ConstScoreQuery::new(Box::new(term_query), 0.0)),
+            ));
+        }
+
+        Box::new(BooleanQuery::new(clauses))
+    }
+}
+
+/// Tags of a retrieved document
+pub(crate) fn values(schema: &Schema, doc: &TantivyDocument) -> Vec<String> {
+    let Ok(field) = schema.get_field(TAGS_FIELD) else {
+        return Vec::new();
+    };
+
+    doc.get_all(field)
+        .filter_map(|value| value.as_str())
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,58 @@
+//! Helpers of the unit tests: a temporary index storage directory shared by all the tests,
+//! shortcuts to create and search indexes in it, and a local server for the tests of layers. The
+//! global options the indexing needs are set up along with the directory.
+
+use crate::{
+    error::ServerError,
+    keyword_search::{IndexRequest, IndexResponse, QueryRequest, QueryResponse},
+    process_json, search_index, DOWNLOAD_URL_PREFIX, INDEX_STORAGE_DIR,
+};
+use axum::{http::StatusCode, Json, Router};
+use once_cell::sync::Lazy;
+use std::path::Path;
+
+// index storage directory of the tests, removed by the system with the other temporary files
+static STORAGE_DIR: Lazy<tempfile::TempDir> = Lazy::new(|| {
+    let dir = tempfile::tempdir().unwrap();
+    INDEX_STORAGE_DIR.set(dir.path().to_path_buf()).unwrap();
+    DOWNLOAD_URL_PREFIX
+        .set(url::Url::parse("http://localhost:12306").unwrap())
+        .unwrap();
+    dir
+});
+
+/// Index storage directory of the tests, set up on first use
+pub(crate) fn storage_dir() -> &'static Path {
+    STORAGE_DIR.path()
+}
+
+/// Name of a new index starting with `prefix`, so that concurrent tests do not share indexes
+pub(crate) fn unique_index_name(prefix: &str) -> String {
+    format!("{prefix}-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// Index the JSON indexing request `request`, and return its status and response.
+pub(crate) async fn index(request: serde_json::Value) -> (StatusCode, IndexResponse) {
+    storage_dir();
+    let request: IndexRequest = serde_json::from_value(request).unwrap();
+    let (status, Json(response)) = process_json(request).await;
+    (status, response)
+}
+
+/// Search with the JSON search request `request`.
+pub(crate) fn search(request: serde_json::Value) -> Result<QueryResponse, ServerError> {
+    storage_dir();
+    let request: QueryRequest = serde_json::from_value(request).unwrap();
+    search_index(request)
+}
+
+/// Serve `app` on a free local port, and return its base URL and a client calling it directly.
+pub(crate) async fn serve(app: Router) -> (String, reqwest::Client) {
+    storage_dir();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+
+    (base_url, client)
+}
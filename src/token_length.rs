@@ -0,0 +1,370 @@
+//! Tokenization of the `title` and `body` fields, set when an index is created: the tokenizer,
+//! e.g. `en_stem` to match the morphological variants of English words, the minimum length of the
+//! tokens, set with `--min-token-length`, and the stop words. Shorter tokens, like "a" or "I",
+//! and stop words, like "the", are dropped both when indexing and when parsing queries, which
+//! reduces the size of the index of large corpora.
+//!
+//! Tantivy does not persist tokenizers, so the choices are stored in the metadata of the index,
+//! and the tokenizer is registered again every time the index is opened. The tokenizers keeping
+//! all tokens are the built-in ones of tantivy, registered in every index.
+
+use crate::{index_meta, keyword_search::TextTokenizer};
+use tantivy::{
+    schema::{IndexRecordOption, TextFieldIndexing, TextOptions, STORED, TEXT},
+    tokenizer::{
+        Language, LowerCaser, RawTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer,
+        StopWordFilter, TextAnalyzer, Token, TokenFilter, TokenStream, Tokenizer,
+        WhitespaceTokenizer,
+    },
+    Index,
+};
+
+/// Default minimum length of the tokens, which keeps all tokens
+pub(crate) const DEFAULT_MIN_TOKEN_LENGTH: usize = 1;
+
+/// English stop words removed by default, the list of Lucene, as in tantivy
+pub(crate) const ENGLISH_STOPWORDS: [&str; 33] = [
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+// suffix of the names of the tokenizers of the text fields of indexes with a minimum token length
+const MIN_LENGTH_SUFFIX: &str = "_min_length";
+
+// suffix of the names of the tokenizers of the text fields of indexes with stop words
+const STOPWORDS_SUFFIX: &str = "_stopwords";
+
+// maximum length in bytes of a token, as in the `default` tokenizer of tantivy
+const MAX_TOKEN_LENGTH: usize = 40;
+
+/// Tokenization of the text fields of an index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Tokenization {
+    /// Tokenizer splitting the text into tokens
+    pub(crate) tokenizer: TextTokenizer,
+    /// Minimum length in characters of the tokens
+    pub(crate) min_token_length: usize,
+    /// Tokens removed, compared after lowercasing with the tokenizers lowercasing the tokens
+    pub(crate) stopwords: Vec<String>,
+}
+
+impl Tokenization {
+    /// Tokenization by `tokenizer` dropping the tokens shorter than `min_token_length` characters,
+    /// and the stop words if `remove_stopwords`: `stopwords` if any, else `ENGLISH_STOPWORDS`.
+    pub(crate) fn new(
+        tokenizer: TextTokenizer,
+        min_token_length: usize,
+        remove_stopwords: bool,
+        stopwords: &[String],
+    ) -> Self {
+        let stopwords = match (remove_stopwords, stopwords.is_empty()) {
+            (false, _) => Vec::new(),
+            (true, true) => ENGLISH_STOPWORDS.map(str::to_string).to_vec(),
+            (true, false) => stopwords
+                .iter()
+                .map(|word| match tokenizer.lowercases() {
+                    true => word.trim().to_lowercase(),
+                    false => word.trim().to_string(),
+                })
+                .filter(|word| !word.is_empty())
+                .collect(),
+        };
+
+        Self {
+            tokenizer,
+            min_token_length,
+            stopwords,
+        }
+    }
+
+    /// Tokenization stored in the metadata of `index`
+    pub(crate) fn from_metadata(index: &Index) -> Self {
+        let metadata = index_meta::load(index);
+        Self {
+            tokenizer: metadata.tokenizer.unwrap_or_default(),
+            min_token_length: metadata
+                .min_token_length
+                .unwrap_or(DEFAULT_MIN_TOKEN_LENGTH),
+            stopwords: metadata.stopwords,
+        }
+    }
+
+    // whether the tokenizer is one of the built-in tokenizers of tantivy, as all tokens are kept
+    fn is_builtin(&self) -> bool {
+        self.min_token_length <= DEFAULT_MIN_TOKEN_LENGTH && self.stopwords.is_empty()
+    }
+
+    // name of the tokenizer of the text fields
+    fn name(&self) -> String {
+        let mut name = self.tokenizer.name().to_string();
+        if self.min_token_length > DEFAULT_MIN_TOKEN_LENGTH {
+            name.push_str(MIN_LENGTH_SUFFIX);
+        }
+        if !self.stopwords.is_empty() {
+            name.push_str(STOPWORDS_SUFFIX);
+        }
+        name
+    }
+
+    /// Options of the text fields of an index tokenized this way
+    pub(crate) fn text_options(&self) -> TextOptions {
+        if self.tokenizer == TextTokenizer::Default && self.is_builtin() {
+            return TEXT | STORED;
+        }
+
+        let indexing = TextFieldIndexing::default()
+            .set_tokenizer(&self.name())
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+        TextOptions::default()
+            .set_indexing_options(indexing)
+            .set_stored()
+    }
+
+    /// Register the tokenizer of the text fields in `index`. The built-in tokenizers need no
+    /// registration.
+    pub(crate) fn register(&self, index: &Index) {
+        if self.is_builtin() {
+            return;
+        }
+
+        let mut builder = match self.tokenizer {
+            TextTokenizer::Default | TextTokenizer::EnStem => {
+                TextAnalyzer::builder(SimpleTokenizer::default())
+                    .filter(RemoveLongFilter::limit(MAX_TOKEN_LENGTH))
+                    .filter(LowerCaser)
+                    .dynamic()
+            }
+            TextTokenizer::Whitespace => {
+                TextAnalyzer::builder(WhitespaceTokenizer::default()).dynamic()
+            }
+            TextTokenizer::Raw => TextAnalyzer::builder(RawTokenizer::default()).dynamic(),
+        };
+        // short tokens and stop words are dropped before stemming, as they are written
+        if self.min_token_length > DEFAULT_MIN_TOKEN_LENGTH {
+            builder = builder.filter_dynamic(RemoveShortFilter {
+                min_token_length: self.min_token_length,
+            });
+        }
+        if !self.stopwords.is_empty() {
+            builder = builder.filter_dynamic(StopWordFilter::remove(self.stopwords.clone()));
+        }
+        if self.tokenizer == TextTokenizer::EnStem {
+            builder = builder.filter_dynamic(Stemmer::new(Language::English));
+        }
+        index.tokenizers().register(&self.name(), builder.build());
+    }
+}
+
+/// Check that custom `stopwords` are only given along with `remove_stopwords`.
+pub(crate) fn validate_stopwords(
+    remove_stopwords: bool,
+    stopwords: &[String],
+) -> Result<(), String> {
+    match stopwords.is_empty() || remove_stopwords {
+        true => Ok(()),
+        false => Err("`stopwords` requires `remove_stopwords` to be `true`".to_string()),
+    }
+}
+
+/// Register the tokenizer of `index` with the tokenization stored in its metadata, if any.
+pub(crate) fn register_from_metadata(index: &Index) {
+    Tokenization::from_metadata(index).register(index);
+}
+
+// token filter dropping the tokens shorter than a number of characters
+#[derive(Clone)]
+struct RemoveShortFilter {
+    min_token_length: usize,
+}
+
+impl TokenFilter for RemoveShortFilter {
+    type Tokenizer<T: Tokenizer> = RemoveShortFilterWrapper<T>;
+
+    fn transform<T: Tokenizer>(self, tokenizer: T) -> RemoveShortFilterWrapper<T> {
+        RemoveShortFilterWrapper {
+            min_token_length: self.min_token_length,
+            inner: tokenizer,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RemoveShortFilterWrapper<T> {
+    min_token_length: usize,
+    inner: T,
+}
+
+impl<T: Tokenizer> Tokenizer for RemoveShortFilterWrapper<T> {
+    type TokenStream<'a> = RemoveShortFilterStream<T::TokenStream<'a>>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> Self::TokenStream<'a> {
+        RemoveShortFilterStream {
+            min_token_length: self.min_token_length,
+            tail: self.inner.token_stream(text),
+        }
+    }
+}
+
+struct RemoveShortFilterStream<T> {
+    min_token_length: usize,
+    tail: T,
+}
+
+impl<T: TokenStream> TokenStream for RemoveShortFilterStream<T> {
+    fn advance(&mut self) -> bool {
+        while self.tail.advance() {
+            if self.tail.token().text.chars().count() >= self.min_token_length {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn token(&self) -> &Token {
+        self.tail.token()
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        self.tail.token_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index_meta::IndexMetadata;
+    use tantivy::{collector::Count, doc, query::QueryParser, schema::Schema};
+
+    // create an index of `content` in `dir` tokenized by `tokenization`, with its metadata
+    fn create_index(dir: &std::path::Path, tokenization: &Tokenization, content: &str) -> Index {
+        let mut schema = Schema::builder();
+        let body = schema.add_text_field("body", tokenization.text_options());
+        let index = Index::create_in_dir(dir, schema.build()).unwrap();
+        tokenization.register(&index);
+
+        let metadata = IndexMetadata {
+            min_token_length: Some(tokenization.min_token_length),
+            tokenizer: Some(tokenization.tokenizer),
+            stopwords: tokenization.stopwords.clone(),
+            ..Default::default()
+        };
+        let mut writer = index.writer(15_000_000).unwrap();
+        writer.add_document(doc!(body => content)).unwrap();
+        index_meta::commit_with_metadata(&mut writer, &metadata).unwrap();
+
+        index
+    }
+
+    // number of documents of `index` matching `query` on its `body` field
+    fn hits(index: &Index, query: &str) -> usize {
+        let body = index.schema().get_field("body").unwrap();
+        let query = QueryParser::for_index(index, vec![body])
+            .parse_query(query)
+            .unwrap();
+
+        index
+            .reader()
+            .unwrap()
+            .searcher()
+            .search(&*query, &Count)
+            .unwrap()
+    }
+
+    // number of documents of `index` with the term `word` in their `body` field
+    fn doc_freq(index: &Index, word: &str) -> u64 {
+        let body = index.schema().get_field("body").unwrap();
+        let term = tantivy::Term::from_field_text(body, word);
+
+        index.reader().unwrap().searcher().doc_freq(&term).unwrap()
+    }
+
+    #[test]
+    fn short_tokens_are_neither_indexed_nor_matched() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokenization = Tokenization::new(TextTokenizer::Default, 3, false, &[]);
+        let index = create_index(dir.path(), &tokenization, "I saw a cat at the zoo");
+
+        assert_eq!(doc_freq(&index, "i"), 0);
+        assert_eq!(doc_freq(&index, "at"), 0);
+        assert_eq!(doc_freq(&index, "cat"), 1);
+        assert_eq!(hits(&index, "at"), 0);
+        assert_eq!(hits(&index, "+a +cat"), 1);
+    }
+
+    #[test]
+    fn default_min_token_length_keeps_all_tokens() {
+        let tokenization =
+            Tokenization::new(TextTokenizer::Default, DEFAULT_MIN_TOKEN_LENGTH, false, &[]);
+
+        assert!(tokenization.is_builtin());
+        assert_eq!(tokenization.name(), "default");
+    }
+
+    #[test]
+    fn stopwords_are_dropped_when_indexing_and_searching() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokenization = Tokenization::new(TextTokenizer::Default, 1, true, &[]);
+        let index = create_index(dir.path(), &tokenization, "The cat is on the mat");
+
+        assert_eq!(doc_freq(&index, "the"), 0);
+        assert_eq!(doc_freq(&index, "cat"), 1);
+        assert_eq!(hits(&index, "+the +cat"), 1);
+        assert_eq!(hits(&index, "the"), 0);
+    }
+
+    #[test]
+    fn custom_stopwords_replace_the_english_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let stopwords = ["Cat".to_string(), " ".to_string()];
+        let tokenization = Tokenization::new(TextTokenizer::Default, 1, true, &stopwords);
+        assert_eq!(tokenization.stopwords, ["cat"]);
+        let index = create_index(dir.path(), &tokenization, "The cat is on the mat");
+
+        assert_eq!(doc_freq(&index, "cat"), 0);
+        assert_eq!(doc_freq(&index, "the"), 1);
+        assert_eq!(hits(&index, "the"), 1);
+    }
+
+    #[test]
+    fn stopwords_require_remove_stopwords() {
+        let stopwords = ["cat".to_string()];
+
+        assert!(validate_stopwords(false, &stopwords).is_err());
+        assert!(validate_stopwords(true, &stopwords).is_ok());
+        assert!(validate_stopwords(false, &[]).is_ok());
+        assert!(validate_stopwords(true, &[]).is_ok());
+    }
+
+    #[test]
+    fn en_stem_matches_variants_of_a_word() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokenization = Tokenization::new(TextTokenizer::EnStem, 1, false, &[]);
+        let index = create_index(dir.path(), &tokenization, "The dog was running");
+
+        assert_eq!(hits(&index, "run"), 1);
+        assert_eq!(hits(&index, "runs"), 1);
+        assert_eq!(hits(&index, "walk"), 0);
+    }
+
+    #[test]
+    fn tokenizer_is_registered_again_on_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokenization = Tokenization::new(TextTokenizer::EnStem, 3, false, &[]);
+        drop(create_index(
+            dir.path(),
+            &tokenization,
+            "I am running to a store",
+        ));
+
+        let index = Index::open_in_dir(dir.path()).unwrap();
+        let body = index.schema().get_field("body").unwrap();
+        assert!(index.tokenizer_for_field(body).is_err());
+
+        register_from_metadata(&index);
+        assert_eq!(Tokenization::from_metadata(&index), tokenization);
+        assert!(index.tokenizer_for_field(body).is_ok());
+        assert_eq!(hits(&index, "run"), 1);
+        assert_eq!(hits(&index, "am"), 0);
+    }
+}
@@ -0,0 +1,105 @@
+//! Unique key of the documents of an index, declared with the `unique_key` of an indexing request
+//! and stored in the metadata of the index. Indexing a document whose key was already indexed
+//! replaces the earlier document, and searches can collapse their hits by key with
+//! `"dedup_by": "unique_key"`.
+//!
+//! The key is either the `content_hash` field, or a keyword field added to the schema under the
+//! name of the key, holding the value of the same name in the `fields` of each document, e.g. the
+//! `url` column of a CSV file.
+
+use crate::{keyword_search::DocumentInput, ranking, tabular, tags};
+use tantivy::schema::{SchemaBuilder, STORED, STRING};
+
+// keyword field holding the hash of the content of the documents
+const CONTENT_HASH_FIELD: &str = "content_hash";
+
+// fields of the schema of new indexes other than the keyword ones
+const NON_KEYWORD_FIELDS: [&str; 6] = [
+    "title",
+    "body",
+    ranking::QUALITY_FIELD,
+    ranking::BODY_LENGTH_FIELD,
+    ranking::CREATED_AT_FIELD,
+    tabular::FIELDS_FIELD,
+];
+
+/// Check that `name` can be the unique key of new indexes: `content_hash`, or a name free in
+/// their schema, usable as a field name.
+pub(crate) fn validate(name: &str) -> Result<(), String> {
+    if NON_KEYWORD_FIELDS.contains(&name) {
+        return Err(format!(
+            "Field '{name}' cannot be the unique key, as it is not a keyword field. Use `{CONTENT_HASH_FIELD}` or the name of a value of the `fields` of the documents"
+        ));
+    }
+    if name == tags::TAGS_FIELD {
+        return Err(format!(
+            "Field '{name}' cannot be the unique key, as a document may have several tags"
+        ));
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(format!(
+            "Invalid unique key '{name}'. It must be made of letters, digits and `_`"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Add the keyword field of the unique key `name` to the schema, unless it is `content_hash`.
+pub(crate) fn add_field(schema_builder: &mut SchemaBuilder, name: &str) {
+    if name != CONTENT_HASH_FIELD {
+        schema_builder.add_text_field(name, STRING | STORED);
+    }
+}
+
+/// Whether the value of the key `name` must be added to the documents, as it is not the
+/// `content_hash` field, which is always filled.
+pub(crate) fn is_separate_field(name: &str) -> bool {
+    name != CONTENT_HASH_FIELD
+}
+
+/// Value of the unique key `name` of `document`, whose content hash is `content_hash`. `None` if
+/// the `fields` of the document have no non-empty value of this name.
+pub(crate) fn value(name: &str, document: &DocumentInput, content_hash: &str) -> Option<String> {
+    match name {
+        CONTENT_HASH_FIELD => Some(content_hash.to_string()),
+        _ => document
+            .fields
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_keyword_fields_can_be_keys() {
+        assert!(validate("url").is_ok());
+        assert!(validate(CONTENT_HASH_FIELD).is_ok());
+        for name in ["title", "body", "quality", "", "external-id"] {
+            assert!(validate(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn key_is_read_from_the_fields_of_the_document() {
+        let document: DocumentInput = serde_json::from_value(serde_json::json!({
+            "content": "Paris",
+            "fields": { "url": "https://example.com/paris", "path": "" },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            value("url", &document, "hash").as_deref(),
+            Some("https://example.com/paris")
+        );
+        assert_eq!(value("path", &document, "hash"), None);
+        assert_eq!(
+            value(CONTENT_HASH_FIELD, &document, "hash").as_deref(),
+            Some("hash")
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Temporary files holding the uploaded multipart fields and request bodies, so that uploads are
+//! never buffered in memory as a whole.
+
+use crate::error::ServerError;
+use axum::{
+    body::{Body, Bytes},
+    extract::multipart::{Field, MultipartError},
+};
+use futures_util::{Stream, StreamExt};
+use http::status::StatusCode;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, warn};
+
+/// An uploaded field stored in a temporary file, which is removed when dropped, including on the
+/// error paths.
+#[derive(Debug)]
+pub(crate) struct TempUpload {
+    path: PathBuf,
+    size_bytes: u64,
+    // whether the file was moved to its final location, so that there is nothing to remove
+    persisted: bool,
+}
+
+impl TempUpload {
+    /// Path of the temporary file
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of bytes of the upload
+    pub(crate) fn size_bytes(&self) -> u64 {
+        self.size_bytes
+    }
+
+    /// Keep the upload at `destination`, moving the temporary file there, or copying it if the
+    /// temporary directory is on another file system.
+    pub(crate) fn persist(mut self, destination: &Path) -> std::io::Result<()> {
+        if std::fs::rename(&self.path, destination).is_ok() {
+            self.persisted = true;
+            return Ok(());
+        }
+
+        std::fs::copy(&self.path, destination).map(|_| ())
+    }
+}
+
+impl Drop for TempUpload {
+    fn drop(&mut self) {
+        if self.persisted {
+            return;
+        }
+
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => debug!(path = %self.path.display(), "Removed temporary upload file"),
+            Err(e) => {
+                warn!(path = %self.path.display(), error = %e, "Failed to remove temporary upload file")
+            }
+        }
+    }
+}
+
+/// Why an upload could not be stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum UploadError {
+    /// The upload exceeds the size limit of the request or of the field
+    TooLarge(String),
+    /// The upload could not be read or stored
+    Failed(String),
+}
+
+impl From<UploadError> for ServerError {
+    fn from(e: UploadError) -> Self {
+        match e {
+            UploadError::TooLarge(message) => ServerError::PayloadTooLarge(message),
+            UploadError::Failed(message) => ServerError::BadRequest(message),
+        }
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(message) | Self::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<MultipartError> for UploadError {
+    fn from(e: MultipartError) -> Self {
+        // the body limit of the route is reported as a failure to read the field
+        match e.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => {
+                Self::TooLarge("The request exceeds the maximum upload size".to_string())
+            }
+            _ => Self::Failed(format!("Failed to read file: {e}")),
+        }
+    }
+}
+
+impl From<axum::Error> for UploadError {
+    fn from(e: axum::Error) -> Self {
+        Self::Failed(format!("Failed to read file: {e}"))
+    }
+}
+
+/// Stream the content of `field` to a temporary file, chunk by chunk as it arrives. Fields larger
+/// than `max_bytes`, if set, are rejected.
+pub(crate) async fn stream_to_temp_file(
+    field: Field<'_>,
+    max_bytes: Option<u64>,
+) -> Result<TempUpload, UploadError> {
+    write_temp_file(field, max_bytes).await
+}
+
+/// Stream a request body to a temporary file, chunk by chunk as it arrives. Bodies larger than
+/// `max_bytes` are rejected.
+pub(crate) async fn stream_body_to_temp_file(
+    body: Body,
+    max_bytes: u64,
+) -> Result<TempUpload, UploadError> {
+    write_temp_file(body.into_data_stream(), Some(max_bytes)).await
+}
+
+async fn write_temp_file<S, E>(chunks: S, max_bytes: Option<u64>) -> Result<TempUpload, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    UploadError: From<E>,
+{
+    let mut chunks = std::pin::pin!(chunks);
+    let path = std::env::temp_dir().join(format!("kw-search-upload-{}", uuid::Uuid::new_v4()));
+    let mut file = tokio::fs::File::create(&path)
+        .await
+        .map_err(|e| UploadError::Failed(format!("Failed to create temporary file: {e}")))?;
+
+    // from now on, the guard removes the file whatever happens
+    let mut upload = TempUpload {
+        path,
+        size_bytes: 0,
+        persisted: false,
+    };
+
+    while let Some(chunk) = chunks.next().await.transpose()? {
+        if let Some(max_bytes) = max_bytes {
+            if upload.size_bytes + chunk.len() as u64 > max_bytes {
+                return Err(UploadError::TooLarge(format!(
+                    "The upload exceeds the maximum size of {max_bytes} bytes"
+                )));
+            }
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| UploadError::Failed(format!("Failed to write temporary file: {e}")))?;
+        upload.size_bytes += chunk.len() as u64;
+    }
+    file.flush()
+        .await
+        .map_err(|e| UploadError::Failed(format!("Failed to write temporary file: {e}")))?;
+
+    Ok(upload)
+}